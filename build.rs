@@ -0,0 +1,36 @@
+/*
+  llvm-sys is written against LLVM 7's C API, but may be linked to a later
+  LLVM. From LLVM 13 on, attributes like `sret` and `byval` must be given
+  the type they're about, through `LLVMCreateTypeAttribute`, which LLVM 7
+  doesn't have; so `llvm_typed_attributes` is set when it's needed.
+
+  the version comes from the headers, rather than from `llvm-config
+  --version`, since the `llvm-config` llvm-sys was pointed to may claim to be
+  LLVM 7 in order to be accepted
+*/
+
+use std::path::Path;
+use std::process::Command;
+
+fn llvm_major_version() -> Option<u32> {
+  let llvm_config = std::env::var_os("DEP_LLVM_CONFIG_PATH")?;
+  let output = Command::new(llvm_config)
+    .arg("--includedir")
+    .output()
+    .ok()?;
+  let include_dir = String::from_utf8(output.stdout).ok()?;
+  let header = Path::new(include_dir.trim()).join("llvm/Config/llvm-config.h");
+  let header = std::fs::read_to_string(header).ok()?;
+  header.lines().find_map(|line| {
+    let version = line.strip_prefix("#define LLVM_VERSION_MAJOR ")?;
+    version.trim().parse().ok()
+  })
+}
+
+fn main() {
+  println!("cargo:rerun-if-env-changed=DEP_LLVM_CONFIG_PATH");
+  println!("cargo:rustc-check-cfg=cfg(llvm_typed_attributes)");
+  if llvm_major_version().is_some_and(|major| major >= 13) {
+    println!("cargo:rustc-cfg=llvm_typed_attributes");
+  }
+}
@@ -1,5 +1,5 @@
 use std::borrow::Borrow;
-use std::cmp::Ord;
+use std::cmp::{Ord, Ordering};
 use std::{cell, collections, fmt, hash};
 
 pub struct Interned<'a, T: ?Sized> {
@@ -53,6 +53,21 @@ impl<'a, T: 'a + ?Sized> PartialEq for Interned<'a, T> {
 }
 impl<'a, T: 'a + ?Sized> Eq for Interned<'a, T> {}
 
+/*
+  interned values are unique, so comparing the values themselves agrees with
+  the pointer equality above
+*/
+impl<'a, T: 'a + ?Sized + Ord> PartialOrd for Interned<'a, T> {
+  fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+    Some(self.cmp(other))
+  }
+}
+impl<'a, T: 'a + ?Sized + Ord> Ord for Interned<'a, T> {
+  fn cmp(&self, other: &Self) -> Ordering {
+    self.__ptr.cmp(other.__ptr)
+  }
+}
+
 pub trait Internable: Ord + Borrow<<Self as Internable>::Comparable> {
   type Borrowed: ?Sized;
   type External: ?Sized;
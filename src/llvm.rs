@@ -31,6 +31,24 @@ macro_rules! cstr {
   };
 }
 
+macro_rules! binary_builders {
+  ($($name:ident => $llvm:ident,)*) => {
+    $(
+      pub fn $name(&mut self, lhs: Value<'a>, rhs: Value<'a>) -> Value<'a> {
+        unsafe {
+          Value {
+            value: $llvm(self.builder, lhs.value, rhs.value, cstr!("")),
+            ctxt: self.ctxt,
+          }
+        }
+      }
+    )*
+  };
+}
+
+pub use llvm_sys::LLVMIntPredicate as IntPredicate;
+pub use llvm_sys::LLVMRealPredicate as RealPredicate;
+
 #[allow(non_upper_case_globals)]
 const LLVMFalse: LLVMBool = false as LLVMBool;
 #[allow(non_upper_case_globals)]
@@ -110,6 +128,10 @@ impl Context {
     }
   }
 
+  pub fn target_triple(&self) -> &str {
+    unsafe { CStr::from_ptr(self.triple).to_str().unwrap() }
+  }
+
   pub fn pointer_size(&self) -> libc::c_uint {
     unsafe { LLVMPointerSize(self.target_data) }
   }
@@ -188,7 +210,8 @@ impl Drop for Context {
   }
 }
 
-#[derive(Copy, Clone)]
+// types are uniqued by their context, so equal types are the same pointer
+#[derive(Copy, Clone, PartialEq, Eq)]
 #[repr(transparent)]
 pub struct Type<'a> {
   ty: LLVMTypeRef,
@@ -196,6 +219,30 @@ pub struct Type<'a> {
 }
 
 impl<'a> Type<'a> {
+  pub fn void(ctxt: &'a Context) -> Self {
+    unsafe {
+      Type {
+        ty: LLVMVoidTypeInContext(ctxt.context),
+        ctxt: PhantomData,
+      }
+    }
+  }
+  pub fn int(ctxt: &'a Context, bits: libc::c_uint) -> Self {
+    unsafe {
+      Type {
+        ty: LLVMIntTypeInContext(ctxt.context, bits),
+        ctxt: PhantomData,
+      }
+    }
+  }
+  pub fn int1(ctxt: &'a Context) -> Self {
+    unsafe {
+      Type {
+        ty: LLVMInt1TypeInContext(ctxt.context),
+        ctxt: PhantomData,
+      }
+    }
+  }
   pub fn int8(ctxt: &'a Context) -> Self {
     unsafe {
       Type {
@@ -212,6 +259,22 @@ impl<'a> Type<'a> {
       }
     }
   }
+  pub fn float(ctxt: &'a Context) -> Self {
+    unsafe {
+      Type {
+        ty: LLVMFloatTypeInContext(ctxt.context),
+        ctxt: PhantomData,
+      }
+    }
+  }
+  pub fn double(ctxt: &'a Context) -> Self {
+    unsafe {
+      Type {
+        ty: LLVMDoubleTypeInContext(ctxt.context),
+        ctxt: PhantomData,
+      }
+    }
+  }
   pub fn size_type(ctxt: &'a Context) -> Self {
     unsafe {
       Type {
@@ -228,6 +291,31 @@ impl<'a> Type<'a> {
       }
     }
   }
+  pub fn structure(ctxt: &'a Context, fields: &[Type<'a>]) -> Self {
+    let (ptr, len) = Type::slice_to_llvm(fields);
+    unsafe {
+      Type {
+        ty: LLVMStructTypeInContext(ctxt.context, ptr, len, LLVMFalse),
+        ctxt: PhantomData,
+      }
+    }
+  }
+  pub fn array(elem: Type<'a>, len: u64) -> Self {
+    unsafe {
+      Type {
+        ty: LLVMArrayType(elem.ty, len as libc::c_uint),
+        ctxt: elem.ctxt,
+      }
+    }
+  }
+  pub fn vector(elem: Type<'a>, count: libc::c_uint) -> Self {
+    unsafe {
+      Type {
+        ty: LLVMVectorType(elem.ty, count),
+        ctxt: elem.ctxt,
+      }
+    }
+  }
 
   slice_to_llvm!(LLVMTypeRef);
 }
@@ -291,10 +379,49 @@ impl<'a> Function<'a> {
     }
   }
 
+  pub fn param(self, idx: libc::c_uint) -> Value<'a> {
+    unsafe {
+      Value {
+        value: LLVMGetParam(self.0.value, idx),
+        ctxt: self.0.ctxt,
+      }
+    }
+  }
+
+  fn add_attribute(
+    self,
+    idx: LLVMAttributeIndex,
+    name: &str,
+    ty: Option<Type<'a>>,
+  ) {
+    unsafe {
+      let ctxt = LLVMGetTypeContext(LLVMTypeOf(self.0.value));
+      let attr = enum_attribute(ctxt, name, ty);
+      LLVMAddAttributeAtIndex(self.0.value, idx, attr);
+    }
+  }
+
+  pub fn add_param_attribute(
+    self,
+    param: libc::c_uint,
+    name: &str,
+    ty: Option<Type<'a>>,
+  ) {
+    self.add_attribute(param + 1, name, ty)
+  }
+
+  pub fn add_return_attribute(self, name: &str) {
+    self.add_attribute(LLVMAttributeReturnIndex, name, None)
+  }
+
   pub fn append_bb(self) -> BasicBlock<'a> {
     unsafe {
       BasicBlock {
-        bb: LLVMAppendBasicBlock(self.0.value, cstr!("")),
+        bb: LLVMAppendBasicBlockInContext(
+          LLVMGetTypeContext(LLVMTypeOf(self.0.value)),
+          self.0.value,
+          cstr!(""),
+        ),
         ctxt: self.0.ctxt,
       }
     }
@@ -323,21 +450,301 @@ impl<'a> Builder<'a> {
     }
   }
 
+  pub fn current_bb(&self) -> BasicBlock<'a> {
+    unsafe {
+      BasicBlock {
+        bb: LLVMGetInsertBlock(self.builder),
+        ctxt: self.ctxt,
+      }
+    }
+  }
+
+  pub fn current_function(&self) -> Function<'a> {
+    unsafe {
+      Function(Value {
+        value: LLVMGetBasicBlockParent(LLVMGetInsertBlock(self.builder)),
+        ctxt: self.ctxt,
+      })
+    }
+  }
+
+  pub fn build_br(&mut self, dest: BasicBlock<'a>) {
+    unsafe {
+      LLVMBuildBr(self.builder, dest.bb);
+    }
+  }
+
+  pub fn build_cond_br(
+    &mut self,
+    cond: Value<'a>,
+    then: BasicBlock<'a>,
+    els: BasicBlock<'a>,
+  ) {
+    unsafe {
+      LLVMBuildCondBr(self.builder, cond.value, then.bb, els.bb);
+    }
+  }
+
+  // `incoming` is the value for each predecessor
+  pub fn build_phi(
+    &mut self,
+    ty: Type<'a>,
+    incoming: &[(Value<'a>, BasicBlock<'a>)],
+  ) -> Value<'a> {
+    let (mut values, mut bbs): (Vec<_>, Vec<_>) = incoming
+      .iter()
+      .map(|&(value, bb)| (value.value, bb.bb))
+      .unzip();
+    unsafe {
+      let phi = LLVMBuildPhi(self.builder, ty.ty, cstr!(""));
+      LLVMAddIncoming(
+        phi,
+        values.as_mut_ptr(),
+        bbs.as_mut_ptr(),
+        values.len() as libc::c_uint,
+      );
+      Value {
+        value: phi,
+        ctxt: self.ctxt,
+      }
+    }
+  }
+
   pub fn build_ret(&mut self, val: Value<'a>) {
     unsafe {
       LLVMBuildRet(self.builder, val.value);
     }
   }
 
+  pub fn build_unreachable(&mut self) {
+    unsafe {
+      LLVMBuildUnreachable(self.builder);
+    }
+  }
+
+  pub fn build_ret_void(&mut self) {
+    unsafe {
+      LLVMBuildRetVoid(self.builder);
+    }
+  }
+
+  pub fn build_alloca(&mut self, ty: Type<'a>) -> Value<'a> {
+    unsafe {
+      Value {
+        value: LLVMBuildAlloca(self.builder, ty.ty, cstr!("")),
+        ctxt: self.ctxt,
+      }
+    }
+  }
+
+  // an `alloca` at the start of the entry block, so that it only runs once
+  pub fn build_entry_alloca(&mut self, ty: Type<'a>) -> Value<'a> {
+    unsafe {
+      let current = LLVMGetInsertBlock(self.builder);
+      let entry = LLVMGetEntryBasicBlock(LLVMGetBasicBlockParent(current));
+      let first = LLVMGetFirstInstruction(entry);
+      if first.is_null() {
+        LLVMPositionBuilderAtEnd(self.builder, entry);
+      } else {
+        LLVMPositionBuilderBefore(self.builder, first);
+      }
+      let slot = LLVMBuildAlloca(self.builder, ty.ty, cstr!(""));
+      LLVMPositionBuilderAtEnd(self.builder, current);
+      Value {
+        value: slot,
+        ctxt: self.ctxt,
+      }
+    }
+  }
+
+  pub fn build_load(&mut self, ptr: Value<'a>) -> Value<'a> {
+    unsafe {
+      Value {
+        value: LLVMBuildLoad(self.builder, ptr.value, cstr!("")),
+        ctxt: self.ctxt,
+      }
+    }
+  }
+
+  pub fn build_store(&mut self, val: Value<'a>, ptr: Value<'a>) {
+    unsafe {
+      LLVMBuildStore(self.builder, val.value, ptr.value);
+    }
+  }
+
+  pub fn build_bitcast(&mut self, val: Value<'a>, ty: Type<'a>) -> Value<'a> {
+    unsafe {
+      Value {
+        value: LLVMBuildBitCast(self.builder, val.value, ty.ty, cstr!("")),
+        ctxt: self.ctxt,
+      }
+    }
+  }
+
+  pub fn build_struct_gep(
+    &mut self,
+    ptr: Value<'a>,
+    idx: libc::c_uint,
+  ) -> Value<'a> {
+    unsafe {
+      Value {
+        value: LLVMBuildStructGEP(self.builder, ptr.value, idx, cstr!("")),
+        ctxt: self.ctxt,
+      }
+    }
+  }
+
+  pub fn build_gep(
+    &mut self,
+    ptr: Value<'a>,
+    indices: &[Value<'a>],
+  ) -> Value<'a> {
+    let (indices, len) = Value::slice_to_llvm(indices);
+    unsafe {
+      Value {
+        value: LLVMBuildInBoundsGEP(
+          self.builder,
+          ptr.value,
+          indices,
+          len,
+          cstr!(""),
+        ),
+        ctxt: self.ctxt,
+      }
+    }
+  }
+
+  pub fn build_extract_value(
+    &mut self,
+    agg: Value<'a>,
+    idx: libc::c_uint,
+  ) -> Value<'a> {
+    unsafe {
+      Value {
+        value: LLVMBuildExtractValue(self.builder, agg.value, idx, cstr!("")),
+        ctxt: self.ctxt,
+      }
+    }
+  }
+
+  pub fn build_insert_value(
+    &mut self,
+    agg: Value<'a>,
+    val: Value<'a>,
+    idx: libc::c_uint,
+  ) -> Value<'a> {
+    unsafe {
+      Value {
+        value: LLVMBuildInsertValue(
+          self.builder,
+          agg.value,
+          val.value,
+          idx,
+          cstr!(""),
+        ),
+        ctxt: self.ctxt,
+      }
+    }
+  }
+
+  // `fun` is a function, or a pointer to one
   pub fn build_call(
     &mut self,
-    fun: Function<'a>,
+    fun: impl Into<Value<'a>>,
     args: &[Value<'a>],
   ) -> Value<'a> {
     let (ptr, len) = Value::slice_to_llvm(args);
     unsafe {
       Value {
-        value: LLVMBuildCall(self.builder, fun.0.value, ptr, len, cstr!("")),
+        value: LLVMBuildCall(
+          self.builder,
+          fun.into().value,
+          ptr,
+          len,
+          cstr!(""),
+        ),
+        ctxt: self.ctxt,
+      }
+    }
+  }
+
+  binary_builders! {
+    build_add => LLVMBuildAdd,
+    build_sub => LLVMBuildSub,
+    build_mul => LLVMBuildMul,
+    build_sdiv => LLVMBuildSDiv,
+    build_udiv => LLVMBuildUDiv,
+    build_srem => LLVMBuildSRem,
+    build_urem => LLVMBuildURem,
+    build_fadd => LLVMBuildFAdd,
+    build_fsub => LLVMBuildFSub,
+    build_fmul => LLVMBuildFMul,
+    build_fdiv => LLVMBuildFDiv,
+    build_frem => LLVMBuildFRem,
+  }
+
+  pub fn build_neg(&mut self, val: Value<'a>) -> Value<'a> {
+    unsafe {
+      Value {
+        value: LLVMBuildNeg(self.builder, val.value, cstr!("")),
+        ctxt: self.ctxt,
+      }
+    }
+  }
+
+  pub fn build_not(&mut self, val: Value<'a>) -> Value<'a> {
+    unsafe {
+      Value {
+        value: LLVMBuildNot(self.builder, val.value, cstr!("")),
+        ctxt: self.ctxt,
+      }
+    }
+  }
+
+  pub fn build_fneg(&mut self, val: Value<'a>) -> Value<'a> {
+    unsafe {
+      Value {
+        value: LLVMBuildFNeg(self.builder, val.value, cstr!("")),
+        ctxt: self.ctxt,
+      }
+    }
+  }
+
+  pub fn build_icmp(
+    &mut self,
+    pred: IntPredicate,
+    lhs: Value<'a>,
+    rhs: Value<'a>,
+  ) -> Value<'a> {
+    unsafe {
+      Value {
+        value: LLVMBuildICmp(
+          self.builder,
+          pred,
+          lhs.value,
+          rhs.value,
+          cstr!(""),
+        ),
+        ctxt: self.ctxt,
+      }
+    }
+  }
+
+  pub fn build_fcmp(
+    &mut self,
+    pred: RealPredicate,
+    lhs: Value<'a>,
+    rhs: Value<'a>,
+  ) -> Value<'a> {
+    unsafe {
+      Value {
+        value: LLVMBuildFCmp(
+          self.builder,
+          pred,
+          lhs.value,
+          rhs.value,
+          cstr!(""),
+        ),
         ctxt: self.ctxt,
       }
     }
@@ -353,6 +760,65 @@ pub struct Value<'a> {
 
 impl<'a> Value<'a> {
   slice_to_llvm!(LLVMValueRef);
+
+  // for a call, which has to give the attributes its callee isn't known to
+  fn add_call_attribute(
+    self,
+    idx: LLVMAttributeIndex,
+    name: &str,
+    ty: Option<Type<'a>>,
+  ) {
+    unsafe {
+      let ctxt = LLVMGetTypeContext(LLVMTypeOf(self.value));
+      let attr = enum_attribute(ctxt, name, ty);
+      LLVMAddCallSiteAttribute(self.value, idx, attr);
+    }
+  }
+
+  pub fn add_call_param_attribute(
+    self,
+    param: libc::c_uint,
+    name: &str,
+    ty: Option<Type<'a>>,
+  ) {
+    self.add_call_attribute(param + 1, name, ty)
+  }
+
+  pub fn add_call_return_attribute(self, name: &str) {
+    self.add_call_attribute(LLVMAttributeReturnIndex, name, None)
+  }
+}
+
+// not in llvm-sys, since it was added in LLVM 13; see build.rs
+#[cfg(llvm_typed_attributes)]
+extern "C" {
+  fn LLVMCreateTypeAttribute(
+    ctxt: LLVMContextRef,
+    kind: libc::c_uint,
+    ty: LLVMTypeRef,
+  ) -> LLVMAttributeRef;
+}
+
+/*
+  `name` is the name of an enum attribute, like `zeroext` or `byval`; those
+  about the memory a pointer points to, like `byval` and `sret`, are given
+  its type `ty`, which LLVM 13 and later require
+*/
+unsafe fn enum_attribute(
+  ctxt: LLVMContextRef,
+  name: &str,
+  ty: Option<Type>,
+) -> LLVMAttributeRef {
+  let kind = LLVMGetEnumAttributeKindForName(
+    name.as_ptr() as *const libc::c_char,
+    name.len(),
+  );
+  assert!(kind != 0, "unknown attribute: {}", name);
+  match ty {
+    #[cfg(llvm_typed_attributes)]
+    Some(ty) => LLVMCreateTypeAttribute(ctxt, kind, ty.ty),
+    _ => LLVMCreateEnumAttribute(ctxt, kind, 0),
+  }
 }
 
 impl<'a> From<ConstValue<'a>> for Value<'a> {
@@ -383,6 +849,51 @@ impl<'a> ConstValue<'a> {
     }
   }
 
+  pub fn null(ty: Type<'a>) -> Self {
+    unsafe {
+      ConstValue(Value {
+        value: LLVMConstNull(ty.ty),
+        ctxt: ty.ctxt,
+      })
+    }
+  }
+
+  pub fn undef(ty: Type<'a>) -> Self {
+    unsafe {
+      ConstValue(Value {
+        value: LLVMGetUndef(ty.ty),
+        ctxt: ty.ctxt,
+      })
+    }
+  }
+
+  pub fn type_of(self) -> Type<'a> {
+    unsafe {
+      Type {
+        ty: LLVMTypeOf(self.0.value),
+        ctxt: PhantomData,
+      }
+    }
+  }
+
+  pub fn bitcast(value: ConstValue<'a>, ty: Type<'a>) -> Self {
+    unsafe {
+      ConstValue(Value {
+        value: LLVMConstBitCast(value.0.value, ty.ty),
+        ctxt: ty.ctxt,
+      })
+    }
+  }
+
+  pub fn real(ty: Type<'a>, value: f64) -> Self {
+    unsafe {
+      ConstValue(Value {
+        value: LLVMConstReal(ty.ty, value),
+        ctxt: ty.ctxt,
+      })
+    }
+  }
+
   pub fn gep(from: ConstValue<'a>, indices: &[ConstValue<'a>]) -> Self {
     let (ptr, len) = Self::slice_to_llvm(indices);
     unsafe {
@@ -405,14 +916,42 @@ impl<'a> ConstValue<'a> {
     }
   }
 
-  pub fn global(ctxt: &'a Context, init: ConstValue<'a>) -> Self {
+  pub fn structure(ctxt: &'a Context, fields: &[ConstValue<'a>]) -> Self {
     unsafe {
+      let (ptr, len) = Self::slice_to_llvm(fields);
+      ConstValue(Value {
+        value: LLVMConstStructInContext(ctxt.context, ptr, len, LLVMFalse),
+        ctxt: PhantomData,
+      })
+    }
+  }
+
+  /*
+    a global without a name is private to the module, and its address isn't
+    significant; one with a name is visible to other modules
+  */
+  pub fn global(
+    ctxt: &'a Context,
+    name: Option<Interned<NfcString>>,
+    init: ConstValue<'a>,
+    constant: bool,
+  ) -> Self {
+    unsafe {
+      let name_ptr = match name {
+        Some(name) => name.as_cstr_ptr(),
+        None => cstr!(""),
+      };
       let glob_ptr =
-        LLVMAddGlobal(ctxt.module, LLVMTypeOf(init.0.value), cstr!(""));
+        LLVMAddGlobal(ctxt.module, LLVMTypeOf(init.0.value), name_ptr);
       LLVMSetInitializer(glob_ptr, init.0.value);
-      LLVMSetGlobalConstant(glob_ptr, LLVMTrue);
-      LLVMSetUnnamedAddress(glob_ptr, LLVMUnnamedAddr::LLVMGlobalUnnamedAddr);
-      LLVMSetLinkage(glob_ptr, LLVMLinkage::LLVMPrivateLinkage);
+      LLVMSetGlobalConstant(glob_ptr, constant as LLVMBool);
+      if name.is_none() {
+        LLVMSetUnnamedAddress(
+          glob_ptr,
+          LLVMUnnamedAddr::LLVMGlobalUnnamedAddr,
+        );
+        LLVMSetLinkage(glob_ptr, LLVMLinkage::LLVMPrivateLinkage);
+      }
 
       ConstValue(Value {
         value: glob_ptr,
@@ -3,33 +3,27 @@ mod interner;
 mod llvm;
 mod module;
 mod parser;
+mod session;
 mod string;
 
-use crate::llvm::*;
-use crate::parser::Parser;
-use crate::module::Module;
-
-const PROGRAM: &str = r#"
-extern func ccosf([% _: FloatComplex %]) -> FloatComplex;
+use std::path::PathBuf;
 
-[%
-type FloatComplex = struct {
-  x: Float32;
-  y: Float32;
-};
-%]
-
-func main() -> Int32 {
-  0
-}
-"#;
+use crate::llvm::*;
+use crate::session::Session;
 
 fn main() {
-  let parse_ctxt = parser::Context::new();
-  let parser = Parser::new(PROGRAM, &parse_ctxt);
+  let root = match std::env::args_os().nth(1) {
+    Some(root) => PathBuf::from(root),
+    None => {
+      eprintln!("usage: epsilon <file.nwt>");
+      std::process::exit(1);
+    }
+  };
+  let module = Session::new(&root).module();
 
-  let module_ctxt = module::Context::new(&parse_ctxt);
-  let _module = Module::new(parser, &module_ctxt);
+  let llctxt = llvm::Context::new();
+  module.build(&llctxt);
+  llctxt.dump();
 }
 
 #[allow(unused)]
@@ -60,7 +54,7 @@ fn test_llvm(ctxt: &parser::Context) {
     ConstValue::int(char_ty, 0u64, false),
   ];
   let hello = ConstValue::array(char_ty, &hello_array);
-  let hello_glob = ConstValue::global(&llctxt, hello);
+  let hello_glob = ConstValue::global(&llctxt, None, hello, true);
 
   let zero = ConstValue::int(Type::size_type(&llctxt), 0u64, false);
   let arg = ConstValue::gep(hello_glob, &[zero, zero]);
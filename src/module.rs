@@ -1,65 +1,1461 @@
 pub mod types;
+mod abi;
+mod codegen;
+mod eval;
+mod exhaustiveness;
+mod monomorphize;
+mod typeck;
 
+use std::cell::UnsafeCell;
 use std::collections::HashMap;
+use std::rc::Rc;
 
-use self::types::Type;
+use self::types::{FloatSize, IntSize, Mutability, StructField, Type, Variant};
 
-use crate::parser::{self, Parser};
-use crate::interner::{Interner, Interned};
+use self::eval::{Evaluator, Value};
+use self::typeck::TypeChecker;
+
+use crate::interner::{Interned, Interner};
+use crate::parser::{
+  self, BinaryOp, IntegerSuffix, Span, StringKind, UnaryOp, Visibility,
+};
+use crate::session::SourceModule;
 use crate::string::NfcString;
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
-pub enum Expression<'cx> {
+// an index into `FunctionDefinition::locals`
+pub type LocalId = usize;
+
+// loops are numbered within a function, so that `break` can name its loop
+pub type LoopId = usize;
+
+// a generic function, and the type arguments of one of its instances
+type InstanceKey<'cx> =
+  (Interned<'cx, NfcString>, Vec<Interned<'cx, Type<'cx>>>);
+
+// a type, and the name of one of its methods
+type MethodKey<'cx> = (Interned<'cx, Type<'cx>>, Interned<'cx, NfcString>);
+
+// names, as they're written in a module, and the items they name
+type Names<'cx> =
+  HashMap<Interned<'cx, NfcString>, Option<Interned<'cx, NfcString>>>;
+
+/*
+  a method which hasn't been added to its type yet: the name of the type,
+  as an item, and the method's name in it, each with the span it's written
+  at
+*/
+type PendingMethod<'cx> = (
+  Interned<'cx, NfcString>,
+  Span,
+  Interned<'cx, NfcString>,
+  Span,
+  Method<'cx>,
+);
+
+#[derive(Debug, Clone)]
+pub enum ExpressionKind<'cx> {
   IntegerLiteral(u64),
-  StringLiteral(Interned<'cx, NfcString>)
+  FloatLiteral(f64),
+  CharLiteral(char),
+  BoolLiteral(bool),
+  StringLiteral(StringKind, &'cx str),
+  Local(LocalId),
+  // a `const` or a `static`
+  Global(Interned<'cx, NfcString>),
+  UnaryOp(UnaryOp, Box<Expression<'cx>>),
+  /*
+    a pointer to the value, which is its place if it's in memory, and
+    otherwise a temporary copy; and the value a pointer points to. These
+    are how receivers are passed to methods, and fields are reached
+    through pointers, since neither can be written in the source
+  */
+  AddressOf(Box<Expression<'cx>>),
+  Deref(Box<Expression<'cx>>),
+  BinaryOp(BinaryOp, Box<Expression<'cx>>, Box<Expression<'cx>>),
+  /*
+    the type arguments are those of a generic function; once it's been
+    monomorphized, a call is to the instance, and has none
+  */
+  Call(
+    Interned<'cx, NfcString>,
+    Vec<Interned<'cx, Type<'cx>>>,
+    Vec<Expression<'cx>>,
+  ),
+  /*
+    `T::name(args)` in a generic function, where `name` is a function of
+    the interface, which `T`'s bounds require; once the function is
+    instantiated, and `T` is known, it's a call to the implementation
+  */
+  InterfaceCall(
+    Interned<'cx, NfcString>,
+    Interned<'cx, Type<'cx>>,
+    Interned<'cx, NfcString>,
+    Vec<Expression<'cx>>,
+  ),
+  /*
+    a function as a `func`, and a closure, as the function it was lifted
+    into along with the values it captured; the type arguments are those of
+    the function, as for a call. A closure's function takes its environment,
+    which holds the captured values, after its parameters
+  */
+  Function(Interned<'cx, NfcString>, Vec<Interned<'cx, Type<'cx>>>),
+  Closure(
+    Interned<'cx, NfcString>,
+    Vec<Interned<'cx, Type<'cx>>>,
+    Vec<Expression<'cx>>,
+  ),
+  // a `func`, as a `closure` which calls it
+  ToClosure(Box<Expression<'cx>>),
+  // a call to a `func` or `closure` value
+  CallIndirect(Box<Expression<'cx>>, Vec<Expression<'cx>>),
+  Array(Vec<Expression<'cx>>),
+  ArrayRepeat(Box<Expression<'cx>>, u64),
+  Index(Box<Expression<'cx>>, Box<Expression<'cx>>),
+  // fields are referred to by their index in the struct type
+  Field(Box<Expression<'cx>>, usize),
+  /*
+    the fields, in the order they're written, which is the order they're
+    run; this is also how tuples are built, their elements being its fields
+  */
+  Struct(Vec<(usize, Expression<'cx>)>),
+  // a variant of the enum type of the expression, and the value it holds
+  Variant(usize, Option<Box<Expression<'cx>>>),
+  Block(Vec<Statement<'cx>>, Option<Box<Expression<'cx>>>),
+  If(
+    Box<Expression<'cx>>,
+    Box<Expression<'cx>>,
+    Option<Box<Expression<'cx>>>,
+  ),
+  While(LoopId, Box<Expression<'cx>>, Box<Expression<'cx>>),
+  Loop(LoopId, Box<Expression<'cx>>),
+  Break(LoopId, Option<Box<Expression<'cx>>>),
+  Continue(LoopId),
+  Return(Option<Box<Expression<'cx>>>),
+  // the arms are tried in order, and some arm always matches
+  Match(Box<Expression<'cx>>, Vec<MatchArm<'cx>>),
+  /*
+    the place is a `Local` or `Global`, or an `Index` or `Field` of a place;
+    the operator is for `+=` and such
+  */
+  Assign(
+    Option<BinaryOp>,
+    Box<Expression<'cx>>,
+    Box<Expression<'cx>>,
+  ),
+  // something which failed to type check; these never reach codegen
+  Error,
+}
+
+#[derive(Debug, Clone)]
+pub struct Expression<'cx> {
+  pub kind: ExpressionKind<'cx>,
+  pub ty: Interned<'cx, Type<'cx>>,
+  pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub struct MatchArm<'cx> {
+  pub pattern: Pattern<'cx>,
+  pub body: Expression<'cx>,
+}
+
+// `ty` is the type of the value the pattern is matched against
+#[derive(Debug, Clone)]
+pub struct Pattern<'cx> {
+  pub kind: PatternKind<'cx>,
+  pub ty: Interned<'cx, Type<'cx>>,
+  pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub enum PatternKind<'cx> {
+  Wildcard,
+  Binding(LocalId),
+  Integer(i128),
+  Char(char),
+  Bool(bool),
+  Variant(usize, Option<Box<Pattern<'cx>>>),
+  // something which failed to type check, and so matches anything
+  Error,
+}
+
+#[derive(Debug, Clone)]
+pub enum Statement<'cx> {
+  Let(LocalId, Expression<'cx>),
+  Expression(Expression<'cx>),
+  /*
+    an expression which is run when the block it's in is left, however it's
+    left: at its end, or by `return`, `break`, or `continue`. Deferred
+    expressions run in the reverse of the order they're reached in, and only
+    once they've been reached
+  */
+  Defer(Expression<'cx>),
+}
+
+// the parameters of a function are its first locals
+#[derive(Debug, Copy, Clone)]
+pub struct Local<'cx> {
+  name: Option<Interned<'cx, NfcString>>,
+  ty: Interned<'cx, Type<'cx>>,
+  // only `let mut` locals may be assigned to
+  mutability: Mutability,
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Parameter<'cx> {
+  name: Option<Interned<'cx, NfcString>>,
+  ty: Interned<'cx, Type<'cx>>,
+}
+
+// a type argument for the parameter must implement the interfaces `bounds`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TypeParam<'cx> {
+  name: Interned<'cx, NfcString>,
+  bounds: Vec<Interned<'cx, NfcString>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct FunctionDeclaration<'cx> {
   name: Interned<'cx, NfcString>,
+  is_const: bool,
+  // the parameter types may use these, as `Type::Param`s
+  type_params: Vec<TypeParam<'cx>>,
+  params: Vec<Parameter<'cx>>,
   ret_ty: Interned<'cx, Type<'cx>>,
 }
 
 #[derive(Debug, Clone)]
 pub struct FunctionDefinition<'cx> {
   declaration: FunctionDeclaration<'cx>,
+  locals: Vec<Local<'cx>>,
   body: Expression<'cx>,
 }
 
+/*
+  the functions an implementation of the interface must have; they're
+  declared with `Self`, the implementing type, as their one type parameter
+*/
+#[derive(Debug, Clone)]
+pub struct Interface<'cx> {
+  name: Interned<'cx, NfcString>,
+  functions: Vec<FunctionDeclaration<'cx>>,
+}
+
+// a method or an associated function, in the type it belongs to
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Method<'cx> {
+  // the function's name as an item, as in `FloatComplex.abs`
+  function: Interned<'cx, NfcString>,
+  // whether it takes a receiver, and may be called as `value.name(...)`
+  is_method: bool,
+}
+
 #[derive(Debug, Clone)]
 pub enum Function<'cx> {
   External(FunctionDeclaration<'cx>),
   Definition(FunctionDefinition<'cx>),
 }
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum GlobalKind {
+  Const,
+  Static(Mutability),
+}
+
+// what a function body needs to know about a global to use it
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct GlobalDeclaration<'cx> {
+  name: Interned<'cx, NfcString>,
+  kind: GlobalKind,
+  ty: Interned<'cx, Type<'cx>>,
+}
+
+// the initializer of a global is evaluated at compile time
+#[derive(Debug, Clone)]
+pub struct Global<'cx> {
+  declaration: GlobalDeclaration<'cx>,
+  value: Value<'cx>,
+}
+
 pub struct Context<'cx> {
   pub parse_context: &'cx parser::Context,
-  types: Interner<Type<'cx>>,
-  type_names: UnsafeCell<HashMap<Interned<'cx, NfcString>, Interned<'cx, Type<'cx>>>>,
+  types: Interner<Box<Type<'cx>>>,
+  struct_fields: Interner<Vec<StructField<'cx>>>,
+  enum_variants: Interner<Vec<Variant<'cx>>>,
+  // the parameter types of `func` and `closure` types, and tuples' elements
+  type_lists: Interner<Vec<&'cx Type<'cx>>>,
+  type_names:
+    UnsafeCell<HashMap<Interned<'cx, NfcString>, Interned<'cx, Type<'cx>>>>,
+  // the interfaces each type implements, in the order they're implemented
+  impls: UnsafeCell<
+    HashMap<Interned<'cx, Type<'cx>>, Vec<Interned<'cx, NfcString>>>,
+  >,
+  methods: UnsafeCell<HashMap<MethodKey<'cx>, Method<'cx>>>,
 }
 
 pub struct Module<'cx> {
   context: &'cx Context<'cx>,
   functions: HashMap<Interned<'cx, NfcString>, Function<'cx>>,
+  globals: HashMap<Interned<'cx, NfcString>, Global<'cx>>,
+}
+
+/*
+  the items of a module, which are resolved the first time they're needed
+
+  the length of an array type is a constant expression, so resolving a type
+  may mean checking and evaluating `const`s and `const func`s, which may in
+  turn need the declarations of other items; so items aren't resolved in any
+  fixed order. Each is taken out of its `unresolved_*` map when it's first
+  needed, and the result is kept; `in_progress` holds the chain of items
+  currently being resolved, which is how we find cycles. Type aliases have
+  their own chain, since types and values have separate names.
+
+  generic items are resolved and checked once, with their type parameters
+  as `Type::Param`s; a generic type alias is instantiated wherever it's
+  given type arguments, and a generic function is monomorphized into an
+  instance for each list of type arguments it's called with, which is kept
+  as a function of its own, under its mangled name.
+
+  the functions of an `impl` are functions of their own as well, named
+  after the type and the interface, as in `<Int32 as Ord>::less`. An impl
+  is found by its type, which has to be resolved first, so they're all
+  resolved together, the first time any of them is needed. The same goes
+  for methods and associated functions, which are found by the type they
+  belong to; they're functions named after the type as it's written, as in
+  `FloatComplex.abs`.
+
+  a closure is lifted into a function of its own when the function it's
+  written in is checked, named after that function, as in
+  `main::{closure0}`. It takes the type parameters of that function, so a
+  closure in a generic function is instantiated along with it.
+
+  the items of every module of the program are put together here, each
+  named after the module it's in, as in `a::b::name`, so that modules can't
+  clash; `extern` functions keep their names, since they're linked by them.
+  An item is resolved and checked with the names of its own module in
+  scope, whichever module needs it first.
+*/
+struct Items<'cx> {
+  context: &'cx Context<'cx>,
+  modules: Vec<ModuleScope<'cx>>,
+  // the module which is last, and where the program starts
+  root: usize,
+  // the module whose names are in scope
+  module: usize,
+  // the module each item is written in
+  item_modules: HashMap<Interned<'cx, NfcString>, usize>,
+  // `extern` functions which were declared again, by other modules
+  extern_redeclarations: Vec<(usize, parser::FunctionDecl<'cx>)>,
+  aliases: HashMap<Interned<'cx, NfcString>, parser::TypeAlias<'cx>>,
+  aliases_in_progress: Vec<Interned<'cx, NfcString>>,
+  // the bodies of generic type aliases, and how many parameters they take
+  generic_aliases:
+    HashMap<Interned<'cx, NfcString>, (usize, Interned<'cx, Type<'cx>>)>,
+  // the type parameters of the generic item being resolved or checked
+  type_params: Vec<TypeParam<'cx>>,
+  unresolved_interfaces:
+    HashMap<Interned<'cx, NfcString>, parser::Interface<'cx>>,
+  unresolved_impls: Vec<(usize, parser::Impl<'cx>)>,
+  unresolved_methods: Vec<PendingMethod<'cx>>,
+  unresolved_functions:
+    HashMap<Interned<'cx, NfcString>, parser::FunctionDecl<'cx>>,
+  unresolved_globals:
+    HashMap<Interned<'cx, NfcString>, (parser::GlobalKind, parser::Type<'cx>)>,
+  bodies: HashMap<Interned<'cx, NfcString>, parser::Expression<'cx>>,
+  unchecked_initializers:
+    HashMap<Interned<'cx, NfcString>, parser::Expression<'cx>>,
+  interfaces: HashMap<Interned<'cx, NfcString>, Rc<Interface<'cx>>>,
+  // the functions of the impls which have been resolved
+  impl_functions: Vec<Interned<'cx, NfcString>>,
+  // the functions closures were lifted into, in the functions checked so far
+  closure_functions: Vec<Interned<'cx, NfcString>>,
+  functions: HashMap<Interned<'cx, NfcString>, FunctionDeclaration<'cx>>,
+  globals: HashMap<Interned<'cx, NfcString>, GlobalDeclaration<'cx>>,
+  // `None` for the items which failed to type check
+  definitions:
+    HashMap<Interned<'cx, NfcString>, Option<Rc<FunctionDefinition<'cx>>>>,
+  initializers: HashMap<Interned<'cx, NfcString>, Option<Rc<Expression<'cx>>>>,
+  // `None` for the globals whose initializers couldn't be evaluated
+  values: HashMap<Interned<'cx, NfcString>, Option<Value<'cx>>>,
+  in_progress: Vec<Interned<'cx, NfcString>>,
+  instances: HashMap<InstanceKey<'cx>, Interned<'cx, NfcString>>,
+  // how many instantiations led to each instance, to stop endless ones
+  instance_depths: HashMap<Interned<'cx, NfcString>, usize>,
+  // the instances which haven't been monomorphized yet
+  pending_instances: Vec<Interned<'cx, NfcString>>,
+  // rendered diagnostics, for the whole module
+  errors: Vec<String>,
+  warnings: Vec<String>,
+}
+
+/*
+  the names a module can use, and the items they name: its own items, and
+  the public items of the modules it imports. A name which more than one
+  import has, for different items, is `None`, and can't be used
+*/
+struct ModuleScope<'cx> {
+  // what the names of its items start with, as in `a::b::`
+  prefix: String,
+  names: Names<'cx>,
+  // the names a module which imports this one can use
+  exports: Names<'cx>,
+}
+
+impl<'cx> ModuleScope<'cx> {
+  fn add_name(
+    names: &mut Names<'cx>,
+    name: Interned<'cx, NfcString>,
+    item: Option<Interned<'cx, NfcString>>,
+  ) {
+    let found = names.entry(name).or_insert(item);
+    if *found != item {
+      *found = None;
+    }
+  }
 }
 
 impl<'cx> Function<'cx> {
-  pub fn declaration(&self) -> FunctionDeclaration<'cx> {
+  pub fn declaration(&self) -> &FunctionDeclaration<'cx> {
     match *self {
-      Function::External(decl) => decl,
-      Function::Definition(FunctionDefinition { declaration, .. }) => declaration,
+      Function::External(ref decl) => decl,
+      Function::Definition(FunctionDefinition {
+        ref declaration, ..
+      }) => declaration,
     }
   }
 }
 
 impl<'cx> Type<'cx> {
-  fn from_parse(ty: parser::Type<'cx>, ctxt: &'cx Context<'cx>) -> Self {
-    match ty {
-      parser::Type::Named(name) => match name.as_str() {
-        "Int32" =>
-        _ =>
+  fn builtin(name: &str) -> Option<Self> {
+    let ty = match name {
+      "Int8" => Type::SignedInt { size: IntSize::I8 },
+      "Int16" => Type::SignedInt { size: IntSize::I16 },
+      "Int32" => Type::SignedInt { size: IntSize::I32 },
+      "Int64" => Type::SignedInt { size: IntSize::I64 },
+      "ISize" => Type::SignedInt {
+        size: IntSize::ISize,
+      },
+      "UInt8" => Type::UnsignedInt { size: IntSize::I8 },
+      "UInt16" => Type::UnsignedInt { size: IntSize::I16 },
+      "UInt32" => Type::UnsignedInt { size: IntSize::I32 },
+      "UInt64" => Type::UnsignedInt { size: IntSize::I64 },
+      "USize" => Type::UnsignedInt {
+        size: IntSize::ISize,
+      },
+      "Float32" => Type::Float {
+        size: FloatSize::F32,
+      },
+      "Float64" => Type::Float {
+        size: FloatSize::F64,
+      },
+      "Bool" => Type::Bool,
+      "Char" => Type::Char,
+      "CChar" => Type::CChar,
+      "Str" => Type::Str,
+      _ => return None,
+    };
+    Some(ty)
+  }
+
+  fn from_suffix(suffix: IntegerSuffix) -> Self {
+    match suffix {
+      IntegerSuffix::I8 => Type::SignedInt { size: IntSize::I8 },
+      IntegerSuffix::I16 => Type::SignedInt { size: IntSize::I16 },
+      IntegerSuffix::I32 => Type::SignedInt { size: IntSize::I32 },
+      IntegerSuffix::I64 => Type::SignedInt { size: IntSize::I64 },
+      IntegerSuffix::ISize => Type::SignedInt {
+        size: IntSize::ISize,
+      },
+      IntegerSuffix::U8 => Type::UnsignedInt { size: IntSize::I8 },
+      IntegerSuffix::U16 => Type::UnsignedInt { size: IntSize::I16 },
+      IntegerSuffix::U32 => Type::UnsignedInt { size: IntSize::I32 },
+      IntegerSuffix::U64 => Type::UnsignedInt { size: IntSize::I64 },
+      IntegerSuffix::USize => Type::UnsignedInt {
+        size: IntSize::ISize,
+      },
+    }
+  }
+}
+
+// `a -> b -> ... -> name`, where `chain` starts at the first `name`
+fn describe_cycle(
+  chain: &[Interned<NfcString>],
+  name: Interned<NfcString>,
+) -> Option<String> {
+  let idx = chain.iter().position(|&n| n == name)?;
+  let mut cycle = String::new();
+  for item in &chain[idx..] {
+    cycle.push_str(item.as_str());
+    cycle.push_str(" -> ");
+  }
+  cycle.push_str(name.as_str());
+  Some(cycle)
+}
+
+impl<'cx> Items<'cx> {
+  fn new(context: &'cx Context<'cx>, root: usize) -> Self {
+    Items {
+      context,
+      modules: vec![],
+      root,
+      module: root,
+      item_modules: HashMap::new(),
+      extern_redeclarations: vec![],
+      aliases: HashMap::new(),
+      aliases_in_progress: vec![],
+      generic_aliases: HashMap::new(),
+      type_params: vec![],
+      unresolved_interfaces: HashMap::new(),
+      unresolved_impls: vec![],
+      unresolved_methods: vec![],
+      unresolved_functions: HashMap::new(),
+      unresolved_globals: HashMap::new(),
+      bodies: HashMap::new(),
+      unchecked_initializers: HashMap::new(),
+      interfaces: HashMap::new(),
+      impl_functions: vec![],
+      closure_functions: vec![],
+      functions: HashMap::new(),
+      globals: HashMap::new(),
+      definitions: HashMap::new(),
+      initializers: HashMap::new(),
+      values: HashMap::new(),
+      in_progress: vec![],
+      instances: HashMap::new(),
+      instance_depths: HashMap::new(),
+      pending_instances: vec![],
+      errors: vec![],
+      warnings: vec![],
+    }
+  }
+
+  /*
+    adds the names `module` can use; the modules it imports must have been
+    added already
+  */
+  fn add_module(&mut self, module: &SourceModule<'cx>) {
+    let mut prefix = String::new();
+    for name in &module.path {
+      prefix.push_str(name.as_str());
+      prefix.push_str("::");
+    }
+    let idx = self.modules.len();
+    self.modules.push(ModuleScope {
+      prefix,
+      names: HashMap::new(),
+      exports: HashMap::new(),
+    });
+
+    let mut names = HashMap::new();
+    let mut exports = HashMap::new();
+    for &(visibility, import) in &module.imports {
+      for (&name, &item) in &self.modules[import].exports {
+        ModuleScope::add_name(&mut names, name, item);
+        if visibility == Visibility::Public {
+          ModuleScope::add_name(&mut exports, name, item);
+        }
+      }
+    }
+    // its own items hide those it imports
+    for &(visibility, ref item) in &module.items {
+      let (name, is_extern) = match *item {
+        parser::Item::ExternFunction(ref decl) => (decl.name, true),
+        parser::Item::Function(ref func) if func.decl.owner.is_none() => {
+          (func.decl.name, false)
+        }
+        parser::Item::TypeAlias(ref alias) => (alias.name, false),
+        parser::Item::Global(ref global) => (global.name, false),
+        parser::Item::Interface(ref interface) => (interface.name, false),
+        _ => continue,
+      };
+      let item = if is_extern {
+        name
+      } else {
+        self.qualified_name(idx, name)
+      };
+      names.insert(name, Some(item));
+      if visibility == Visibility::Public {
+        exports.insert(name, Some(item));
+      }
+    }
+    self.modules[idx].names = names;
+    self.modules[idx].exports = exports;
+  }
+
+  /*
+    the name the item `name` of `module` is known by, which starts with the
+    path of the module, as in `a::b::name`; except for `main`, in the root
+    module, which is where the program starts
+  */
+  fn qualified_name(
+    &self,
+    module: usize,
+    name: Interned<'cx, NfcString>,
+  ) -> Interned<'cx, NfcString> {
+    if module == self.root && name.as_str() == "main" {
+      return name;
+    }
+    let name = format!("{}{}", self.modules[module].prefix, name);
+    self.context.parse_context.get_ident(&name)
+  }
+
+  /*
+    the item `name` names, in the module being resolved. Builtin types and
+    type parameters aren't items, and are left as they are; any other name
+    the module can't use is taken to be one of its own items, which doesn't
+    exist, and so is reported as unknown wherever it's looked up. `None` for
+    a name more than one import has, which is reported at `span`
+  */
+  fn item_name(
+    &mut self,
+    name: Interned<'cx, NfcString>,
+    span: Span,
+  ) -> Option<Interned<'cx, NfcString>> {
+    match self.modules[self.module].names.get(&name) {
+      Some(&Some(item)) => Some(item),
+      Some(None) => {
+        let msg = format!("{} is ambiguous; more than one import has it", name);
+        self.error(span, msg);
+        None
+      }
+      None if Type::builtin(name.as_str()).is_some() => Some(name),
+      None if self.type_params.iter().any(|p| p.name == name) => Some(name),
+      None => Some(self.qualified_name(self.module, name)),
+    }
+  }
+
+  // runs `f` with the names of `module` in scope
+  fn in_module<T>(
+    &mut self,
+    module: usize,
+    f: impl FnOnce(&mut Self) -> T,
+  ) -> T {
+    let old = std::mem::replace(&mut self.module, module);
+    let ret = f(self);
+    self.module = old;
+    ret
+  }
+
+  // returns the name the alias is added as; `None` if it can't be added
+  fn add_alias(
+    &mut self,
+    module: usize,
+    mut alias: parser::TypeAlias<'cx>,
+  ) -> Option<Interned<'cx, NfcString>> {
+    if Type::builtin(alias.name.as_str()).is_some() {
+      let msg = format!("attempted to redefine builtin type: {}", alias.name);
+      self.error(alias.name_span, msg);
+      return None;
+    }
+    self.check_type_params(&alias.type_params);
+    for param in &alias.type_params {
+      for &(_, span) in &param.bounds {
+        let msg =
+          format!("the type parameters of {} can't have bounds", alias.name);
+        self.error(span, msg);
+      }
+    }
+    let name = self.qualified_name(module, alias.name);
+    if self.aliases.contains_key(&name) {
+      let msg = format!("multiple types with the same name: {}", name);
+      self.error(alias.name_span, msg);
+      return None;
+    }
+    alias.name = name;
+    self.aliases.insert(name, alias);
+    self.item_modules.insert(name, module);
+    Some(name)
+  }
+
+  fn check_type_params(&mut self, params: &[parser::TypeParam<'cx>]) {
+    for (idx, param) in params.iter().enumerate() {
+      if params[..idx].iter().any(|p| p.name == param.name) {
+        let msg = format!(
+          "multiple type parameters with the same name: {}",
+          param.name
+        );
+        self.error(param.name_span, msg);
+      }
+    }
+  }
+
+  // the type parameters of a generic item; the bounds must be interfaces
+  fn type_params(
+    &mut self,
+    params: &[parser::TypeParam<'cx>],
+  ) -> Vec<TypeParam<'cx>> {
+    let mut ret = vec![];
+    for param in params {
+      let mut bounds = vec![];
+      for &(bound, span) in &param.bounds {
+        let interface = match self.item_name(bound, span) {
+          Some(interface) => interface,
+          None => continue,
+        };
+        if self.interface(interface).is_none() {
+          self.error(span, format!("unknown interface: {}", bound));
+          continue;
+        }
+        bounds.push(interface);
+      }
+      ret.push(TypeParam {
+        name: param.name,
+        bounds,
+      });
+    }
+    ret
+  }
+
+  // the type parameter `name`, if it's in scope
+  fn type_param(
+    &self,
+    name: Interned<'cx, NfcString>,
+  ) -> Option<Interned<'cx, Type<'cx>>> {
+    let idx = self.type_params.iter().position(|p| p.name == name)?;
+    let idx = idx as u32;
+    Some(self.context.add_type_definition(Type::Param { idx, name }))
+  }
+
+  // whether `name` is a type, rather than a value
+  fn is_type_name(&self, name: Interned<'cx, NfcString>) -> bool {
+    self.type_params.iter().any(|p| p.name == name)
+      || self.context.get_type(name).is_some()
+      || self.aliases.contains_key(&name)
+      || self.generic_aliases.contains_key(&name)
+  }
+
+  // runs `f` with `params` as the type parameters in scope
+  fn with_type_params<T>(
+    &mut self,
+    params: Vec<TypeParam<'cx>>,
+    f: impl FnOnce(&mut Self) -> T,
+  ) -> T {
+    let old = std::mem::replace(&mut self.type_params, params);
+    let ret = f(self);
+    self.type_params = old;
+    ret
+  }
+
+  /*
+    returns the name the function is added as; `None` for an `extern`
+    function which another module has declared already
+  */
+  fn add_function(
+    &mut self,
+    module: usize,
+    mut decl: parser::FunctionDecl<'cx>,
+    body: Option<parser::Expression<'cx>>,
+  ) -> Option<Interned<'cx, NfcString>> {
+    if let Some((owner, owner_span)) = decl.owner {
+      if body.is_none() {
+        let msg = format!("extern functions can't be methods: {}", decl.name);
+        self.error(decl.name_span, msg);
+        return None;
+      }
+      if !decl.type_params.is_empty() {
+        let msg = format!("methods can't be generic: {}.{}", owner, decl.name);
+        self.error(decl.name_span, msg);
+        return None;
+      }
+      let function = format!("{}.{}", owner, decl.name);
+      let function = self.context.parse_context.get_ident(&function);
+      let method = Method {
+        function: self.qualified_name(module, function),
+        is_method: decl.is_method,
+      };
+      let owner =
+        self.in_module(module, |this| this.item_name(owner, owner_span))?;
+      self.unresolved_methods.push((
+        owner,
+        owner_span,
+        decl.name,
+        decl.name_span,
+        method,
+      ));
+      decl.name = method.function;
+    } else if body.is_some() {
+      decl.name = self.qualified_name(module, decl.name);
+    } else {
+      match self.item_modules.get(&decl.name) {
+        Some(&first) if first != module => {
+          self.extern_redeclarations.push((module, decl));
+          return None;
+        }
+        _ => (),
       }
     }
+    self.insert_function(module, decl, body)
+  }
+
+  /*
+    adds the function `decl.name`, which is the name it's known by already;
+    `None` if it can't be added
+  */
+  fn insert_function(
+    &mut self,
+    module: usize,
+    decl: parser::FunctionDecl<'cx>,
+    body: Option<parser::Expression<'cx>>,
+  ) -> Option<Interned<'cx, NfcString>> {
+    let name = decl.name;
+    if body.is_none() && !decl.type_params.is_empty() {
+      let msg = format!("extern functions can't be generic: {}", name);
+      self.error(decl.name_span, msg);
+      return None;
+    }
+    self.check_type_params(&decl.type_params);
+    if self.unresolved_functions.contains_key(&name) {
+      let msg = format!("multiple functions with the same name: {}", name);
+      self.error(decl.name_span, msg);
+      return None;
+    }
+    if self.unresolved_globals.contains_key(&name) {
+      let msg = format!("multiple items with the same name: {}", name);
+      self.error(decl.name_span, msg);
+      return None;
+    }
+    self.unresolved_functions.insert(name, decl);
+    if let Some(body) = body {
+      self.bodies.insert(name, body);
+    }
+    self.item_modules.insert(name, module);
+    Some(name)
+  }
+
+  /*
+    checks that each `extern` function which is declared again, in another
+    module, is declared the same way
+  */
+  fn check_extern_redeclarations(&mut self) {
+    for (module, decl) in std::mem::take(&mut self.extern_redeclarations) {
+      let name = decl.name;
+      let redeclaration = self.in_module(module, |this| {
+        let type_params = this.type_params(&decl.type_params);
+        this.declaration(name, type_params, &decl)
+      });
+      if self.function(name) != Some(redeclaration) {
+        let msg =
+          format!("conflicting declarations of the extern function {}", name);
+        self.error(decl.name_span, msg);
+      }
+    }
+  }
+
+  fn add_interface(
+    &mut self,
+    module: usize,
+    mut interface: parser::Interface<'cx>,
+  ) -> Option<Interned<'cx, NfcString>> {
+    let name = self.qualified_name(module, interface.name);
+    if self.unresolved_interfaces.contains_key(&name) {
+      let msg = format!("multiple interfaces with the same name: {}", name);
+      self.error(interface.name_span, msg);
+      return None;
+    }
+    interface.name = name;
+    self.unresolved_interfaces.insert(name, interface);
+    self.item_modules.insert(name, module);
+    Some(name)
+  }
+
+  fn add_impl(&mut self, module: usize, imp: parser::Impl<'cx>) {
+    self.unresolved_impls.push((module, imp));
+  }
+
+  fn add_global(
+    &mut self,
+    module: usize,
+    global: parser::Global<'cx>,
+  ) -> Option<Interned<'cx, NfcString>> {
+    let name = self.qualified_name(module, global.name);
+    if self.unresolved_functions.contains_key(&name)
+      || self.unresolved_globals.contains_key(&name)
+    {
+      let msg = format!("multiple items with the same name: {}", name);
+      self.error(global.name_span, msg);
+      return None;
+    }
+    self.unresolved_globals.insert(name, (global.kind, global.ty));
+    self.unchecked_initializers.insert(name, global.init);
+    self.item_modules.insert(name, module);
+    Some(name)
+  }
+
+  // the chain of items from `name` back to itself, if it's being resolved
+  fn cycle(&self, name: Interned<'cx, NfcString>) -> Option<String> {
+    describe_cycle(&self.in_progress, name)
+  }
+
+  /*
+    an error in an item, rather than in a body; one may be found twice, as
+    when the type of a method's receiver is also the type it belongs to
+  */
+  fn error(&mut self, span: Span, msg: impl std::fmt::Display) {
+    let msg = self.context.parse_context.render(span, msg);
+    if !self.errors.contains(&msg) {
+      self.errors.push(msg);
+    }
+  }
+
+  fn error_type(&self) -> Interned<'cx, Type<'cx>> {
+    self.context.add_type_definition(Type::Error)
+  }
+
+  fn resolve_type(
+    &mut self,
+    ty: &parser::Type<'cx>,
+  ) -> Interned<'cx, Type<'cx>> {
+    match *ty {
+      parser::Type::Named(name, ref args, span) => {
+        if let Some(ty) = self.type_param(name) {
+          if !args.is_empty() {
+            let msg =
+              format!("type parameter {} can't take type arguments", name);
+            self.error(span, msg);
+          }
+          return ty;
+        }
+        let name = match self.item_name(name, span) {
+          Some(name) => name,
+          None => return self.error_type(),
+        };
+        if args.is_empty() {
+          return self.resolve_name(name, span);
+        }
+        let args = args
+          .iter()
+          .map(|arg| self.resolve_type(arg))
+          .collect::<Vec<_>>();
+        self.generic_type(name, &args, span)
+      }
+      parser::Type::Pointer {
+        mutable,
+        ref pointee,
+      } => {
+        let mutability = if mutable {
+          Mutability::Mutable
+        } else {
+          Mutability::Immutable
+        };
+        let pointee = self.resolve_type(pointee);
+        self.context.pointer_type(mutability, pointee)
+      }
+      parser::Type::Array(ref len, ref element) => {
+        let element = self.resolve_type(element);
+        match self.array_length(len) {
+          Some(len) => self.context.array_type(element, len),
+          None => self.error_type(),
+        }
+      }
+      parser::Type::Struct(ref parse_fields) => {
+        let mut fields: Vec<StructField> = vec![];
+        for field in parse_fields {
+          if fields.iter().any(|f| f.name == field.name) {
+            let msg = format!(
+              "multiple struct fields with the same name: {}",
+              field.name
+            );
+            self.error(field.name_span, msg);
+            continue;
+          }
+          let ty = self.resolve_type(&field.ty);
+          fields.push(StructField {
+            name: field.name,
+            ty: Interned::as_ref(ty),
+          });
+        }
+        self.context.struct_type(&fields)
+      }
+      parser::Type::Enum(ref parse_variants) => {
+        let mut variants: Vec<Variant> = vec![];
+        for variant in parse_variants {
+          if variants.iter().any(|v| v.name == variant.name) {
+            let msg = format!(
+              "multiple enum variants with the same name: {}",
+              variant.name
+            );
+            self.error(variant.name_span, msg);
+            continue;
+          }
+          let payload = variant.payload.as_ref().map(|payload| {
+            Interned::as_ref(self.resolve_type(payload))
+          });
+          variants.push(Variant {
+            name: variant.name,
+            payload,
+          });
+        }
+        self.context.enum_type(&variants)
+      }
+      parser::Type::Function(ref params, ref ret)
+      | parser::Type::Closure(ref params, ref ret) => {
+        let params = params
+          .iter()
+          .map(|param| self.resolve_type(param))
+          .collect::<Vec<_>>();
+        let ret = self.resolve_type(ret);
+        let closure = matches!(*ty, parser::Type::Closure(..));
+        self.context.function_type(closure, &params, ret)
+      }
+      parser::Type::Tuple(ref elements) => {
+        let elements = elements
+          .iter()
+          .map(|element| self.resolve_type(element))
+          .collect::<Vec<_>>();
+        self.context.tuple_type(&elements)
+      }
+      parser::Type::Unit => self.context.unit_type(),
+      parser::Type::Never => self.context.add_type_definition(Type::Never),
+    }
+  }
+
+  // the type `name`, written at `span`
+  fn resolve_name(
+    &mut self,
+    name: Interned<'cx, NfcString>,
+    span: Span,
+  ) -> Interned<'cx, Type<'cx>> {
+    if let Some(ty) = self.context.get_type(name) {
+      return ty;
+    }
+    if let Some((count, _)) = self.generic_alias(name) {
+      let msg =
+        format!("{} takes {} type arguments, but 0 were given", name, count);
+      self.error(span, msg);
+      return self.error_type();
+    }
+
+    // `span` is in the alias which closes the cycle
+    if let Some(cycle) = describe_cycle(&self.aliases_in_progress, name) {
+      self.error(span, format!("cyclic type alias: {}", cycle));
+      return self.error_type();
+    }
+
+    let (aliased, name_span) = match self.aliases.remove(&name) {
+      Some(alias) => (alias.ty, alias.name_span),
+      None => {
+        self.error(span, format!("unknown type: {}", name));
+        return self.error_type();
+      }
+    };
+
+    self.aliases_in_progress.push(name);
+    let module = self.item_modules[&name];
+    let ty = self.in_module(module, |this| {
+      this.with_type_params(vec![], |this| this.resolve_type(&aliased))
+    });
+    self.aliases_in_progress.pop();
+
+    if !self.context.add_named_type(name, ty) {
+      let msg = format!("multiple types with the same name: {}", name);
+      self.error(name_span, msg);
+    }
+    ty
+  }
+
+  /*
+    the body of the generic type alias `name`, with its parameters as
+    `Type::Param`s, and the number of them; `None` if `name` isn't generic
+  */
+  fn generic_alias(
+    &mut self,
+    name: Interned<'cx, NfcString>,
+  ) -> Option<(usize, Interned<'cx, Type<'cx>>)> {
+    if let Some(&found) = self.generic_aliases.get(&name) {
+      return Some(found);
+    }
+    match self.aliases.get(&name) {
+      Some(alias) if !alias.type_params.is_empty() => (),
+      _ => return None,
+    }
+    let alias = self.aliases.remove(&name).unwrap();
+
+    self.aliases_in_progress.push(name);
+    let parser::TypeAlias {
+      type_params, ty, ..
+    } = alias;
+    let count = type_params.len();
+    let ty = self.in_module(self.item_modules[&name], |this| {
+      let type_params = this.type_params(&type_params);
+      this.with_type_params(type_params, |this| this.resolve_type(&ty))
+    });
+    self.aliases_in_progress.pop();
+
+    self.generic_aliases.insert(name, (count, ty));
+    Some((count, ty))
+  }
+
+  // the type named `name`, given the type arguments `args`, written at `span`
+  fn generic_type(
+    &mut self,
+    name: Interned<'cx, NfcString>,
+    args: &[Interned<'cx, Type<'cx>>],
+    span: Span,
+  ) -> Interned<'cx, Type<'cx>> {
+    let (count, ty) = match self.generic_alias(name) {
+      Some(found) => found,
+      None => (0, self.resolve_name(name, span)),
+    };
+    if *ty == Type::Error {
+      return ty;
+    }
+    if count != args.len() {
+      let msg = format!(
+        "{} takes {} type arguments, but {} were given",
+        name,
+        count,
+        args.len(),
+      );
+      self.error(span, msg);
+      return self.error_type();
+    }
+    self.context.substitute(ty, args)
+  }
+
+  /*
+    the length of an array type, which is checked and evaluated like the
+    initializer of a `const USize`; `None` if that failed
+  */
+  fn array_length(&mut self, len: &parser::Expression<'cx>) -> Option<u64> {
+    let usize_ty = self.context.add_type_definition(Type::UnsignedInt {
+      size: IntSize::ISize,
+    });
+    let errors = self.errors.len();
+    let len = TypeChecker::new(self, true).initializer(usize_ty, len);
+    if self.errors.len() != errors {
+      return None;
+    }
+    match Evaluator::new(self).constant(&len)? {
+      Value::Integer(len) => Some(len as u64),
+      value => panic!("ICE: array length of {}", value),
+    }
+  }
+
+  fn function(
+    &mut self,
+    name: Interned<'cx, NfcString>,
+  ) -> Option<FunctionDeclaration<'cx>> {
+    if let Some(decl) = self.functions.get(&name) {
+      return Some(decl.clone());
+    }
+    let decl = self.unresolved_functions.remove(&name)?;
+
+    self.in_progress.push(name);
+    let declaration = self.in_module(self.item_modules[&name], |this| {
+      let type_params = this.type_params(&decl.type_params);
+      this.declaration(name, type_params, &decl)
+    });
+    self.in_progress.pop();
+
+    self.functions.insert(name, declaration.clone());
+    Some(declaration)
+  }
+
+  // `decl`, with its types resolved, where `type_params` are in scope
+  fn declaration(
+    &mut self,
+    name: Interned<'cx, NfcString>,
+    type_params: Vec<TypeParam<'cx>>,
+    decl: &parser::FunctionDecl<'cx>,
+  ) -> FunctionDeclaration<'cx> {
+    let (params, ret_ty) = self.with_type_params(type_params.clone(), |this| {
+      let mut params: Vec<Parameter> = vec![];
+      for param in &decl.params {
+        if let Some(name) = param.name {
+          if params.iter().any(|p| p.name == Some(name)) {
+            let msg =
+              format!("multiple parameters with the same name: {}", name);
+            this.error(param.name_span, msg);
+          }
+        }
+        params.push(Parameter {
+          name: param.name,
+          ty: this.resolve_type(&param.ty),
+        });
+      }
+      (params, this.resolve_type(&decl.ret_ty))
+    });
+
+    FunctionDeclaration {
+      name,
+      is_const: decl.is_const,
+      type_params,
+      params,
+      ret_ty,
+    }
+  }
+
+  fn interface(
+    &mut self,
+    name: Interned<'cx, NfcString>,
+  ) -> Option<Rc<Interface<'cx>>> {
+    if let Some(interface) = self.interfaces.get(&name) {
+      return Some(interface.clone());
+    }
+    let interface = self.unresolved_interfaces.remove(&name)?;
+
+    let this_ty = TypeParam {
+      name: self.context.parse_context.get_ident("Self"),
+      bounds: vec![],
+    };
+    let mut functions: Vec<FunctionDeclaration> = vec![];
+    let module = self.item_modules[&name];
+    for decl in &interface.functions {
+      let msg = if !decl.type_params.is_empty() {
+        Some("functions in interfaces can't be generic")
+      } else if decl.owner.is_some() {
+        Some("functions in interfaces can't be methods")
+      } else if functions.iter().any(|f| f.name == decl.name) {
+        Some("multiple functions with the same name")
+      } else {
+        None
+      };
+      if let Some(msg) = msg {
+        self.error(decl.name_span, format!("{}: {}", msg, decl.name));
+        continue;
+      }
+      let this_ty = vec![this_ty.clone()];
+      let decl = self.in_module(module, |this| {
+        this.declaration(decl.name, this_ty, decl)
+      });
+      functions.push(decl);
+    }
+
+    let interface = Rc::new(Interface { name, functions });
+    self.interfaces.insert(name, interface.clone());
+    Some(interface)
+  }
+
+  /*
+    checks each impl against its interface, and adds its functions; the
+    types of the impls are known from here on
+  */
+  fn resolve_impls(&mut self) {
+    for (module, imp) in std::mem::take(&mut self.unresolved_impls) {
+      let interface = self.in_module(module, |this| {
+        this.item_name(imp.interface, imp.interface_span)
+      });
+      let interface = match interface {
+        Some(interface) => interface,
+        None => continue,
+      };
+      let interface = match self.interface(interface) {
+        Some(interface) => interface,
+        None => {
+          let msg = format!("unknown interface: {}", imp.interface);
+          self.error(imp.interface_span, msg);
+          continue;
+        }
+      };
+      let ty = self.in_module(module, |this| {
+        this.with_type_params(vec![], |this| this.resolve_type(&imp.ty))
+      });
+      if *ty == Type::Error {
+        continue;
+      }
+      if !self.context.add_impl(interface.name, ty) {
+        let msg = format!("multiple impls of {} for {}", interface.name, ty);
+        self.error(imp.interface_span, msg);
+        continue;
+      }
+
+      // the functions the impl has, and where they're written
+      let mut names: Vec<(Interned<NfcString>, Span)> = vec![];
+      for func in imp.functions {
+        let name = func.decl.name;
+        let span = func.decl.name_span;
+        let msg = if names.iter().any(|&(n, _)| n == name) {
+          format!("multiple functions with the same name: {}", name)
+        } else if interface.functions.iter().all(|f| f.name != name) {
+          format!(
+            "{} is not a function of the interface {}",
+            name, interface.name
+          )
+        } else if !func.decl.type_params.is_empty() {
+          format!("functions in impls can't be generic: {}", name)
+        } else if func.decl.owner.is_some() {
+          format!("functions in impls can't be methods: {}", name)
+        } else {
+          let mangled =
+            self.context.impl_function_name(interface.name, ty, name);
+          let decl = parser::FunctionDecl {
+            name: mangled,
+            ..func.decl
+          };
+          let body = Some(func.body);
+          if self.insert_function(module, decl, body).is_some() {
+            names.push((name, span));
+            self.impl_functions.push(mangled);
+          }
+          continue;
+        };
+        self.error(span, msg);
+      }
+
+      for required in &interface.functions {
+        let span = match names.iter().find(|&&(n, _)| n == required.name) {
+          Some(&(_, span)) => span,
+          None => {
+            let msg = format!(
+              "the impl of {} for {} is missing the function {}",
+              interface.name, ty, required.name,
+            );
+            self.error(imp.interface_span, msg);
+            continue;
+          }
+        };
+        let name = required.name;
+        let name = self.context.impl_function_name(interface.name, ty, name);
+        let found = self.function(name).unwrap();
+        let expected = required
+          .params
+          .iter()
+          .map(|param| self.context.substitute(param.ty, &[ty]));
+        if !found.params.iter().map(|param| param.ty).eq(expected)
+          || found.ret_ty != self.context.substitute(required.ret_ty, &[ty])
+        {
+          let msg = format!(
+            "{} doesn't match its declaration in the interface {}",
+            name, interface.name,
+          );
+          self.error(span, msg);
+        }
+        if required.is_const && !found.is_const {
+          let msg = format!(
+            "{} must be a `const func`, as it is in the interface {}",
+            name, interface.name,
+          );
+          self.error(span, msg);
+        }
+      }
+    }
+  }
+
+  // adds the methods and associated functions to the types they belong to
+  fn resolve_methods(&mut self) {
+    let methods = std::mem::take(&mut self.unresolved_methods);
+    for (owner, owner_span, name, name_span, method) in methods {
+      let ty = self.with_type_params(vec![], |this| {
+        this.resolve_name(owner, owner_span)
+      });
+      if *ty == Type::Error {
+        continue;
+      }
+      if !self.context.add_method(ty, name, method) {
+        let msg = format!("multiple methods named {} on {}", name, ty);
+        self.error(name_span, msg);
+      }
+    }
+  }
+
+  /*
+    the method or associated function `name` of `ty`; `None` if there's no
+    such thing
+  */
+  fn method(
+    &mut self,
+    ty: Interned<'cx, Type<'cx>>,
+    name: Interned<'cx, NfcString>,
+  ) -> Option<Method<'cx>> {
+    self.resolve_methods();
+    self.context.get_method(ty, name)
+  }
+
+  // the interfaces `ty` implements
+  fn implemented_interfaces(
+    &mut self,
+    ty: Interned<'cx, Type<'cx>>,
+  ) -> Vec<Interned<'cx, NfcString>> {
+    self.resolve_impls();
+    self.context.interfaces_of(ty)
+  }
+
+  fn global(
+    &mut self,
+    name: Interned<'cx, NfcString>,
+  ) -> Option<GlobalDeclaration<'cx>> {
+    if let Some(&decl) = self.globals.get(&name) {
+      return Some(decl);
+    }
+    let (kind, ty) = self.unresolved_globals.remove(&name)?;
+
+    let kind = match kind {
+      parser::GlobalKind::Const => GlobalKind::Const,
+      parser::GlobalKind::Static { mutable: false } => {
+        GlobalKind::Static(Mutability::Immutable)
+      }
+      parser::GlobalKind::Static { mutable: true } => {
+        GlobalKind::Static(Mutability::Mutable)
+      }
+    };
+    self.in_progress.push(name);
+    let ty = self.in_module(self.item_modules[&name], |this| {
+      this.with_type_params(vec![], |this| this.resolve_type(&ty))
+    });
+    self.in_progress.pop();
+
+    let declaration = GlobalDeclaration { name, kind, ty };
+    self.globals.insert(name, declaration);
+    Some(declaration)
+  }
+
+  // the checked body of a function; `None` for `extern` functions
+  fn definition(
+    &mut self,
+    name: Interned<'cx, NfcString>,
+  ) -> Option<Rc<FunctionDefinition<'cx>>> {
+    if let Some(def) = self.definitions.get(&name) {
+      return def.clone();
+    }
+    let body = self.bodies.remove(&name)?;
+    let declaration = self.function(name)?;
+
+    self.in_progress.push(name);
+    let errors = self.errors.len();
+    let is_const = declaration.is_const;
+    let type_params = declaration.type_params.clone();
+    let def = self.in_module(self.item_modules[&name], |this| {
+      this.with_type_params(type_params, |this| {
+        TypeChecker::new(this, is_const).function(declaration, &body)
+      })
+    });
+    self.in_progress.pop();
+
+    let def = if self.errors.len() == errors {
+      Some(Rc::new(def))
+    } else {
+      None
+    };
+    self.definitions.insert(name, def.clone());
+    def
+  }
+
+  fn initializer(
+    &mut self,
+    name: Interned<'cx, NfcString>,
+  ) -> Option<Rc<Expression<'cx>>> {
+    if let Some(init) = self.initializers.get(&name) {
+      return init.clone();
+    }
+    let init = self.unchecked_initializers.remove(&name)?;
+    let declaration = self.global(name)?;
+
+    self.in_progress.push(name);
+    let errors = self.errors.len();
+    let init = self.in_module(self.item_modules[&name], |this| {
+      this.with_type_params(vec![], |this| {
+        TypeChecker::new(this, true).initializer(declaration.ty, &init)
+      })
+    });
+    self.in_progress.pop();
+
+    let init = if self.errors.len() == errors {
+      Some(Rc::new(init))
+    } else {
+      None
+    };
+    self.initializers.insert(name, init.clone());
+    init
   }
 }
 
@@ -68,47 +1464,575 @@ impl<'cx> Context<'cx> {
     Self {
       parse_context,
       types: Interner::new(),
+      struct_fields: Interner::new(),
+      enum_variants: Interner::new(),
+      type_lists: Interner::new(),
+      type_names: UnsafeCell::new(HashMap::new()),
+      impls: UnsafeCell::new(HashMap::new()),
+      methods: UnsafeCell::new(HashMap::new()),
     }
   }
 
-  pub fn add_type_definition(&'cx self, ty: Type<'cx>) -> Interned<'cx, Type<'cx>> {
-    let lt_erased = unsafe {
-      std::mem::transmute::<Type<'cx>, Type<'static>>(ty)
-    };
+  pub fn add_type_definition(
+    &'cx self,
+    ty: Type<'cx>,
+  ) -> Interned<'cx, Type<'cx>> {
+    let lt_erased =
+      unsafe { std::mem::transmute::<Type<'cx>, Type<'static>>(ty) };
 
     self.types.add_element(&lt_erased)
   }
 
-  pub fn add_named_type(&'cx self, name: Interned<'cx, NfcString>, ty: Interned<'cx, Type<'cx>>) {
+  pub fn struct_type(
+    &'cx self,
+    fields: &[StructField<'cx>],
+  ) -> Interned<'cx, Type<'cx>> {
+    let fields = self.struct_fields.add_element(fields);
+    self.add_type_definition(Type::Struct {
+      fields: Interned::as_ref(fields),
+    })
+  }
+
+  pub fn enum_type(
+    &'cx self,
+    variants: &[Variant<'cx>],
+  ) -> Interned<'cx, Type<'cx>> {
+    let variants = self.enum_variants.add_element(variants);
+    self.add_type_definition(Type::Enum {
+      variants: Interned::as_ref(variants),
+    })
+  }
+
+  // `func(params) -> ret`, or `closure(params) -> ret`
+  pub fn function_type(
+    &'cx self,
+    closure: bool,
+    params: &[Interned<'cx, Type<'cx>>],
+    ret: Interned<'cx, Type<'cx>>,
+  ) -> Interned<'cx, Type<'cx>> {
+    let params = params
+      .iter()
+      .map(|&param| Interned::as_ref(param))
+      .collect::<Vec<_>>();
+    let params = Interned::as_ref(self.type_lists.add_element(&params));
+    let ret = Interned::as_ref(ret);
+    if closure {
+      self.add_type_definition(Type::Closure { params, ret })
+    } else {
+      self.add_type_definition(Type::Function { params, ret })
+    }
+  }
+
+  // `(elements)`, or `()` if there are none
+  pub fn tuple_type(
+    &'cx self,
+    elements: &[Interned<'cx, Type<'cx>>],
+  ) -> Interned<'cx, Type<'cx>> {
+    if elements.is_empty() {
+      return self.unit_type();
+    }
+    let elements = elements
+      .iter()
+      .map(|&element| Interned::as_ref(element))
+      .collect::<Vec<_>>();
+    let elements = Interned::as_ref(self.type_lists.add_element(&elements));
+    self.add_type_definition(Type::Tuple { elements })
+  }
+
+  // `()`, the type of blocks without a tail, is the empty struct
+  pub fn unit_type(&'cx self) -> Interned<'cx, Type<'cx>> {
+    self.struct_type(&[])
+  }
+
+  /*
+    `"..."` is a `Str`, and `c"..."` is a `*const CChar`; `b"..."` is a
+    `*const UInt8`, and `u16"..."` and `u32"..."` are `*const UInt16` and
+    `*const UInt32`
+  */
+  pub fn string_type(&'cx self, kind: StringKind) -> Interned<'cx, Type<'cx>> {
+    let element = match kind {
+      StringKind::Normal => return self.add_type_definition(Type::Str),
+      StringKind::CString => Type::CChar,
+      StringKind::Bytes => Type::UnsignedInt { size: IntSize::I8 },
+      StringKind::Utf16 => Type::UnsignedInt { size: IntSize::I16 },
+      StringKind::Utf32 => Type::UnsignedInt { size: IntSize::I32 },
+    };
+    let element = self.add_type_definition(element);
+    self.pointer_type(Mutability::Immutable, element)
+  }
+
+  pub fn array_type(
+    &'cx self,
+    element: Interned<'cx, Type<'cx>>,
+    len: u64,
+  ) -> Interned<'cx, Type<'cx>> {
+    self.add_type_definition(Type::Array {
+      element: Interned::as_ref(element),
+      len,
+    })
+  }
+
+  pub fn pointer_type(
+    &'cx self,
+    mutability: Mutability,
+    pointee: Interned<'cx, Type<'cx>>,
+  ) -> Interned<'cx, Type<'cx>> {
+    self.add_type_definition(Type::Pointer {
+      mutability,
+      pointee: Interned::as_ref(pointee),
+    })
+  }
+
+  /*
+    `ty`, with the types in it replaced by what `f` returns for them, where
+    it returns anything; `f` sees each type before the types inside it
+  */
+  pub fn map_type(
+    &'cx self,
+    ty: Interned<'cx, Type<'cx>>,
+    f: &mut impl FnMut(
+      Interned<'cx, Type<'cx>>,
+    ) -> Option<Interned<'cx, Type<'cx>>>,
+  ) -> Interned<'cx, Type<'cx>> {
+    if let Some(ty) = f(ty) {
+      return ty;
+    }
+    let mut map = |ty: &Type<'cx>| {
+      Interned::as_ref(self.map_type(self.add_type_definition(*ty), f))
+    };
+    match *ty {
+      Type::Pointer {
+        mutability,
+        pointee,
+      } => self.add_type_definition(Type::Pointer {
+        mutability,
+        pointee: map(pointee),
+      }),
+      Type::Array { element, len } => self.add_type_definition(Type::Array {
+        element: map(element),
+        len,
+      }),
+      Type::Struct { fields } => {
+        let fields = fields
+          .iter()
+          .map(|field| StructField {
+            name: field.name,
+            ty: map(field.ty),
+          })
+          .collect::<Vec<_>>();
+        self.struct_type(&fields)
+      }
+      Type::Tuple { elements } => {
+        let elements = elements.iter().map(|&element| map(element));
+        let elements = elements.collect::<Vec<_>>();
+        let elements = Interned::as_ref(self.type_lists.add_element(&elements));
+        self.add_type_definition(Type::Tuple { elements })
+      }
+      Type::Enum { variants } => {
+        let variants = variants
+          .iter()
+          .map(|variant| Variant {
+            name: variant.name,
+            payload: variant.payload.map(&mut map),
+          })
+          .collect::<Vec<_>>();
+        self.enum_type(&variants)
+      }
+      Type::Function { params, ret } | Type::Closure { params, ret } => {
+        let params = params.iter().map(|&param| map(param)).collect::<Vec<_>>();
+        let params = Interned::as_ref(self.type_lists.add_element(&params));
+        let ret = map(ret);
+        match *ty {
+          Type::Function { .. } => {
+            self.add_type_definition(Type::Function { params, ret })
+          }
+          _ => self.add_type_definition(Type::Closure { params, ret }),
+        }
+      }
+      _ => ty,
+    }
+  }
+
+  // `ty`, with its type parameters replaced by the type arguments `args`
+  pub fn substitute(
+    &'cx self,
+    ty: Interned<'cx, Type<'cx>>,
+    args: &[Interned<'cx, Type<'cx>>],
+  ) -> Interned<'cx, Type<'cx>> {
+    if !ty.has_params() {
+      return ty;
+    }
+    self.map_type(ty, &mut |ty| match *ty {
+      Type::Param { idx, .. } => Some(args[idx as usize]),
+      _ => None,
+    })
+  }
+
+  // `false` if there's a type named `name` already
+  pub fn add_named_type(
+    &'cx self,
+    name: Interned<'cx, NfcString>,
+    ty: Interned<'cx, Type<'cx>>,
+  ) -> bool {
     unsafe {
       let type_names = &mut *self.type_names.get();
-      match type_names.insert(name, ty) {
-        Some(_) => panic!("multiple types with the same name"),
-        None => (),
+      if type_names.contains_key(&name) {
+        return false;
       }
+      type_names.insert(name, ty);
+      true
     }
   }
 
-  pub fn get_type(&'cx self, name: Interned<'cx, NfcString>) -> Option<Interned<'cx, Type<'cx>>> {
+  pub fn get_type(
+    &'cx self,
+    name: Interned<'cx, NfcString>,
+  ) -> Option<Interned<'cx, Type<'cx>>> {
+    if let Some(ty) = Type::builtin(name.as_str()) {
+      return Some(self.add_type_definition(ty));
+    }
     unsafe {
-      let type_names = &mut *self.type_names.get();
-      type_names.get(name)
+      let type_names = &*self.type_names.get();
+      type_names.get(&name).cloned()
     }
   }
-}
 
+  // `false` if `ty` implements `interface` already
+  pub fn add_impl(
+    &'cx self,
+    interface: Interned<'cx, NfcString>,
+    ty: Interned<'cx, Type<'cx>>,
+  ) -> bool {
+    unsafe {
+      let impls = &mut *self.impls.get();
+      let interfaces = impls.entry(ty).or_default();
+      if interfaces.contains(&interface) {
+        return false;
+      }
+      interfaces.push(interface);
+      true
+    }
+  }
+
+  pub fn interfaces_of(
+    &'cx self,
+    ty: Interned<'cx, Type<'cx>>,
+  ) -> Vec<Interned<'cx, NfcString>> {
+    unsafe {
+      let impls = &*self.impls.get();
+      impls.get(&ty).cloned().unwrap_or_default()
+    }
+  }
+
+  // `false` if `ty` has a method `name` already
+  pub fn add_method(
+    &'cx self,
+    ty: Interned<'cx, Type<'cx>>,
+    name: Interned<'cx, NfcString>,
+    method: Method<'cx>,
+  ) -> bool {
+    unsafe {
+      let methods = &mut *self.methods.get();
+      if methods.contains_key(&(ty, name)) {
+        return false;
+      }
+      methods.insert((ty, name), method);
+      true
+    }
+  }
+
+  pub fn get_method(
+    &'cx self,
+    ty: Interned<'cx, Type<'cx>>,
+    name: Interned<'cx, NfcString>,
+  ) -> Option<Method<'cx>> {
+    unsafe {
+      let methods = &*self.methods.get();
+      methods.get(&(ty, name)).cloned()
+    }
+  }
+
+  // the name the function `function` of the impl of `interface` for `ty` has
+  pub fn impl_function_name(
+    &'cx self,
+    interface: Interned<'cx, NfcString>,
+    ty: Interned<'cx, Type<'cx>>,
+    function: Interned<'cx, NfcString>,
+  ) -> Interned<'cx, NfcString> {
+    let name = format!("<{} as {}>::{}", ty, interface, function);
+    self.parse_context.get_ident(&name)
+  }
+}
 
 impl<'cx> Module<'cx> {
-  pub fn new(mut parser: Parser<'cx, '_>, context: &'cx Context<'cx>) -> Self {
+  /*
+    the modules of a program, each after the modules it imports; the root
+    module is last
+  */
+  pub fn new(
+    modules: Vec<SourceModule<'cx>>,
+    context: &'cx Context<'cx>,
+  ) -> Self {
     use parser::Item;
+
+    let mut items = Items::new(context, modules.len() - 1);
+    // the names of the items, in the order they're written
+    let mut alias_names = vec![];
+    let mut interface_names = vec![];
+    let mut function_names = vec![];
+    let mut global_names = vec![];
+    for (idx, module) in modules.into_iter().enumerate() {
+      items.add_module(&module);
+      for (_, item) in module.items {
+        match item {
+          Item::Import(_) => panic!("ICE: import left in a module's items"),
+          Item::ExternFunction(decl) => {
+            if let Some(name) = items.add_function(idx, decl, None) {
+              function_names.push(name);
+            }
+          }
+          Item::Function(func) => {
+            let body = Some(func.body);
+            if let Some(name) = items.add_function(idx, func.decl, body) {
+              function_names.push(name);
+            }
+          }
+          Item::TypeAlias(alias) => {
+            let span = alias.name_span;
+            if let Some(name) = items.add_alias(idx, alias) {
+              alias_names.push((name, span));
+            }
+          }
+          Item::Global(global) => {
+            if let Some(name) = items.add_global(idx, global) {
+              global_names.push(name);
+            }
+          }
+          Item::Interface(interface) => {
+            if let Some(name) = items.add_interface(idx, interface) {
+              interface_names.push(name);
+            }
+          }
+          Item::Impl(imp) => items.add_impl(idx, imp),
+        }
+      }
+    }
+
+    // everything is checked, even what isn't used
+    for &(name, span) in &alias_names {
+      if items.generic_alias(name).is_none() {
+        items.resolve_name(name, span);
+      }
+    }
+    for &name in &interface_names {
+      items.interface(name);
+    }
+    items.resolve_impls();
+    items.resolve_methods();
+    function_names.append(&mut items.impl_functions);
+    for &name in &function_names {
+      items.function(name);
+    }
+    items.check_extern_redeclarations();
+    for &name in &global_names {
+      items.global(name);
+    }
+    for &name in &function_names {
+      items.definition(name);
+    }
+    for &name in &global_names {
+      items.initializer(name);
+    }
+    function_names.append(&mut items.closure_functions);
+    for warning in &items.warnings {
+      eprintln!("{}", warning);
+    }
+    if !items.errors.is_empty() {
+      panic!("{}", items.errors.join("\n"));
+    }
+
+    let mut globals = HashMap::new();
+    for &name in &global_names {
+      if let Some(value) = Evaluator::new(&mut items).global(name) {
+        let declaration = items.globals[&name];
+        globals.insert(name, Global { declaration, value });
+      }
+    }
+    if !items.errors.is_empty() {
+      panic!("{}", items.errors.join("\n"));
+    }
+
+    /*
+      generic functions aren't compiled themselves, only their instances;
+      those may be found while monomorphizing other functions, including
+      other instances
+    */
+    let mut names = function_names
+      .into_iter()
+      .filter(|name| items.functions[name].type_params.is_empty())
+      .collect::<Vec<_>>();
     let mut functions = HashMap::new();
-    while let Some(item) = parser.next_item() {
-      match item {
-        Item::ExternFunction(decl) => {},
-        Item::Function(func) => (),
+    while let Some(name) = names.pop() {
+      let func = match items.definitions.remove(&name) {
+        Some(def) => {
+          let def = def.expect("ICE: function failed to type check");
+          let mut def =
+            Rc::try_unwrap(def).unwrap_or_else(|def| (*def).clone());
+          items.monomorphize(&mut def);
+          Function::Definition(def)
+        }
+        None => Function::External(items.functions[&name].clone()),
+      };
+      functions.insert(name, func);
+      names.append(&mut items.pending_instances);
+    }
+    if !items.errors.is_empty() {
+      panic!("{}", items.errors.join("\n"));
+    }
+
+    Module {
+      context,
+      functions,
+      globals,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::parser::Parser;
+  use std::panic::AssertUnwindSafe;
+
+  /*
+    the errors in the module `source`, as they're reported; empty if there
+    are none
+  */
+  fn errors(source: &str) -> String {
+    let parse_ctxt = parser::Context::new();
+    let file = parse_ctxt.add_file("test.nwt".to_owned(), source.to_owned());
+    let result = std::panic::catch_unwind(AssertUnwindSafe(|| {
+      let mut parser = Parser::new(file, &parse_ctxt);
+      let mut items = vec![];
+      while let Some(item) = parser.next_item() {
+        items.push(item);
       }
+      let source = SourceModule {
+        path: vec![parse_ctxt.get_ident("test")],
+        items,
+        imports: vec![],
+      };
+      let context = Context::new(&parse_ctxt);
+      Module::new(vec![source], &context);
+    }));
+    match result {
+      Ok(()) => String::new(),
+      Err(payload) => match payload.downcast::<String>() {
+        Ok(msg) => *msg,
+        Err(_) => panic!("the module panicked without a message"),
+      },
     }
+  }
+
+  #[test]
+  fn forward_references() {
+    let source = r#"
+func get(p: Point) -> Coordinate { p.x }
+type Point = struct { x: Coordinate; y: Coordinate; };
+type Coordinate = Int32;
+type Pairs = Pair[Point];
+type Pair[T] = struct { a: T; b: T; };
+"#;
+    assert_eq!(errors(source), "");
+  }
+
+  #[test]
+  fn cyclic_aliases() {
+    let found = errors("type A = B;\ntype B = struct { x: A; };\n");
+    assert!(found.contains("cyclic type alias: test::A -> test::B -> test::A"));
+    assert!(found.contains("test.nwt:2:22"));
+
+    let found = errors("type C[T] = D[T];\ntype D[T] = C[T];\n");
+    assert!(found.contains("cyclic type alias: test::C -> test::D -> test::C"));
+    assert!(found.contains("test.nwt:2:13"));
+
+    let found = errors("type E = *const E;\n");
+    assert!(found.contains("cyclic type alias: test::E -> test::E"));
+  }
+
+  #[test]
+  fn duplicate_aliases() {
+    let found = errors("type A = Int32;\ntype A = Bool;\n");
+    assert!(found.contains("multiple types with the same name: test::A"));
+    assert!(found.contains("test.nwt:2:6"));
 
-    panic!()
+    let found = errors("type Int32 = Bool;\n");
+    assert!(found.contains("attempted to redefine builtin type: Int32"));
   }
-}
\ No newline at end of file
+
+  #[test]
+  fn integer_literal_ranges() {
+    let source = r#"
+func a() -> UInt8 { 255 }
+func b() -> Int8 { -128 }
+func c() -> UInt64 { 18_446_744_073_709_551_615 }
+func d() -> Int64 { -9_223_372_036_854_775_808 }
+func e() -> UInt16 { let x = 65_535u16; x }
+"#;
+    assert_eq!(errors(source), "");
+
+    let found = errors("func f() -> UInt8 { 256 }\n");
+    assert!(found.contains("integer literal out of range for UInt8: 256"));
+    assert!(found.contains("test.nwt:1:21"));
+    let found = errors("func f() -> Int8 { -129 }\n");
+    assert!(found.contains("integer literal out of range for Int8: -129"));
+    let found = errors("func f() -> Int8 { 128 }\n");
+    assert!(found.contains("integer literal out of range for Int8: 128"));
+    let found = errors("func f() -> Int32 { let x = 300u8; 0 }\n");
+    assert!(found.contains("integer literal out of range for UInt8: 300"));
+    let found = errors("func f() -> Float32 { 1e40 }\n");
+    assert!(found.contains("float literal out of range for Float32: 1e40"));
+  }
+
+  #[test]
+  fn interface_methods_on_type_parameters() {
+    let source = r#"
+interface Show { func show(a: Self) -> Int32; }
+func show[T: Show](x: T) -> Int32 { x.show() }
+func pair[T: Show, U: Show](x: T, y: U) -> Int32 { x.show() + y.show() }
+"#;
+    assert_eq!(errors(source), "");
+
+    let found = errors("func f[T](x: T) -> Int32 { x.show() }\n");
+    assert!(found.contains("no method show on type T"));
+    let source = r#"
+interface Show { func show(a: Self) -> Int32; func make() -> Self; }
+interface Tell { func show(a: Self) -> Int32; }
+func f[T: Show + Tell](x: T) -> Int32 { x.show() }
+func g[T: Show](x: T) -> T { x.make() }
+"#;
+    let found = errors(source);
+    assert!(found.contains("is ambiguous; it's a function of both"));
+    assert!(found.contains("test::Show::make takes no arguments"));
+  }
+
+  #[test]
+  fn interface_methods_on_impls() {
+    let source = r#"
+interface Show { func show(a: Self) -> Int32; }
+impl Show for Int32 { func show(a: Int32) -> Int32 { a } }
+type Point = struct { x: Int32; };
+impl Show for Point { func show(a: Point) -> Int32 { a.x.show() } }
+func (self: *const Point) shown() -> Int32 { self.show() }
+func f(i: Int32, p: Point) -> Int32 { i.show() + p.show() + p.shown() }
+"#;
+    assert_eq!(errors(source), "");
+
+    let source = r#"
+interface Show { func show(a: Self) -> Int32; }
+impl Show for Int32 { func show(a: Int32) -> Int32 { a } }
+func f(b: Bool) -> Int32 { b.show() }
+"#;
+    assert!(errors(source).contains("no method show on type Bool"));
+  }
+}
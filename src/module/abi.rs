@@ -0,0 +1,608 @@
+/*
+  lowering of `extern` function signatures to the C calling convention
+
+  scalars are passed the way LLVM would pass them anyways; it's aggregates
+  that need help. The x86-64 System V ABI splits a struct of at most sixteen
+  bytes into eightbytes, and passes each eightbyte in either a general purpose
+  or an SSE register, depending on what's inside of it; LLVM knows nothing
+  about this, so we pass those structs as the scalars that the registers would
+  hold (`Piece`s). Larger structs are passed on the stack, through `byval`, and
+  returned through a hidden pointer. Windows x64 is simpler - structs of size
+  1, 2, 4, or 8 are passed as integers, and everything else by reference.
+
+  enums are laid out like a C struct of the tag, and a union of the values
+  of the variants; the tag is the smallest unsigned integer that can number
+  every variant.
+*/
+
+use super::types::{FloatSize, IntSize, Type, Variant};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Target {
+  SysV64,
+  Win64,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Layout {
+  pub size: u64,
+  pub align: u64,
+}
+
+/*
+  the tag is at the start; `payload` is the layout of the union, whose size
+  is a multiple of its alignment
+*/
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct EnumLayout {
+  pub tag_size: u64,
+  pub payload_offset: u64,
+  pub payload: Layout,
+}
+
+// what a single register holds, when passing an aggregate in registers
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Piece {
+  Int { bytes: u64 },
+  Float32,
+  Float32x2,
+  Float64,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Extension {
+  None,
+  Sign,
+  Zero,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PassMode {
+  // zero sized types aren't passed at all
+  Ignore,
+  Direct(Extension),
+  // arguments are split into one LLVM argument per piece
+  Pieces(Vec<Piece>),
+  // by a pointer to a copy; for returns, the pointer is the first argument
+  Indirect { byval: bool },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FunctionAbi {
+  pub params: Vec<PassMode>,
+  pub ret: PassMode,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Class {
+  // the eightbyte holds nothing, or only padding
+  None,
+  Integer,
+  Sse,
+}
+
+#[derive(Copy, Clone, Debug)]
+struct Eightbyte {
+  class: Class,
+  has_double: bool,
+  has_high_float: bool,
+}
+
+const SYSV_INTEGER_REGISTERS: u32 = 6;
+const SYSV_SSE_REGISTERS: u32 = 8;
+
+impl Target {
+  pub fn from_triple(triple: &str) -> Option<Self> {
+    if !triple.starts_with("x86_64-") {
+      None
+    } else if triple.contains("-windows") {
+      Some(Target::Win64)
+    } else {
+      Some(Target::SysV64)
+    }
+  }
+}
+
+fn round_up(n: u64, align: u64) -> u64 {
+  n.div_ceil(align) * align
+}
+
+pub fn layout(ty: &Type, pointer_size: u64) -> Layout {
+  let scalar = |size| Layout { size, align: size };
+  match *ty {
+    Type::SignedInt { size } | Type::UnsignedInt { size } => match size {
+      IntSize::I8 => scalar(1),
+      IntSize::I16 => scalar(2),
+      IntSize::I32 => scalar(4),
+      IntSize::I64 => scalar(8),
+      IntSize::ISize => scalar(pointer_size),
+    },
+    Type::Float {
+      size: FloatSize::F32,
+    } => scalar(4),
+    Type::Float {
+      size: FloatSize::F64,
+    } => scalar(8),
+    Type::Bool | Type::CChar => scalar(1),
+    Type::Never => Layout { size: 0, align: 1 },
+    Type::Char => scalar(4),
+    Type::Pointer { .. } | Type::Function { .. } => scalar(pointer_size),
+    // a closure is a pointer to its function, and one to its environment
+    Type::Str | Type::Closure { .. } => Layout {
+      size: pointer_size * 2,
+      align: pointer_size,
+    },
+    Type::Struct { .. } | Type::Tuple { .. } => {
+      let mut size = 0;
+      let mut align = 1;
+      for member in ty.members().unwrap() {
+        let field_layout = layout(member, pointer_size);
+        size = round_up(size, field_layout.align) + field_layout.size;
+        align = std::cmp::max(align, field_layout.align);
+      }
+      Layout {
+        size: round_up(size, align),
+        align,
+      }
+    }
+    Type::Array { element, len } => {
+      let element_layout = layout(element, pointer_size);
+      Layout {
+        size: element_layout.size * len,
+        align: element_layout.align,
+      }
+    }
+    Type::Enum { variants } => {
+      let enum_layout = enum_layout(variants, pointer_size);
+      let align =
+        std::cmp::max(enum_layout.tag_size, enum_layout.payload.align);
+      let size = enum_layout.payload_offset + enum_layout.payload.size;
+      Layout {
+        size: round_up(size, align),
+        align,
+      }
+    }
+    Type::Param { .. } => panic!("ICE: type parameter {} in codegen", ty),
+    Type::Infer(_) | Type::Error => {
+      panic!("ICE: unchecked type in codegen: {}", ty)
+    }
+  }
+}
+
+pub fn enum_layout(variants: &[Variant], pointer_size: u64) -> EnumLayout {
+  let tag_size = match variants.len() {
+    0..=0x100 => 1,
+    0x101..=0x1_0000 => 2,
+    _ => 4,
+  };
+  let mut size = 0;
+  let mut align = 1;
+  for payload in variants.iter().filter_map(|variant| variant.payload) {
+    let payload_layout = layout(payload, pointer_size);
+    size = std::cmp::max(size, payload_layout.size);
+    align = std::cmp::max(align, payload_layout.align);
+  }
+  EnumLayout {
+    tag_size,
+    payload_offset: round_up(tag_size, align),
+    payload: Layout {
+      size: round_up(size, align),
+      align,
+    },
+  }
+}
+
+pub fn field_offsets(members: &[&Type], pointer_size: u64) -> Vec<u64> {
+  let mut offset = 0;
+  let mut offsets = Vec::with_capacity(members.len());
+  for member in members {
+    let field_layout = layout(member, pointer_size);
+    offset = round_up(offset, field_layout.align);
+    offsets.push(offset);
+    offset += field_layout.size;
+  }
+  offsets
+}
+
+fn extension(ty: &Type) -> Extension {
+  match *ty {
+    Type::SignedInt { size: IntSize::I8 }
+    | Type::SignedInt { size: IntSize::I16 } => Extension::Sign,
+    Type::UnsignedInt { size: IntSize::I8 }
+    | Type::UnsignedInt { size: IntSize::I16 }
+    | Type::Bool
+    | Type::CChar => Extension::Zero,
+    _ => Extension::None,
+  }
+}
+
+/*
+  returns `false` if the type has to be passed in memory; that is, if it has
+  a field which isn't aligned
+*/
+fn classify_sysv_eightbytes(
+  ty: &Type,
+  offset: u64,
+  eightbytes: &mut [Eightbyte],
+  pointer_size: u64,
+) -> bool {
+  if let Some(members) = ty.members() {
+    let offsets = field_offsets(&members, pointer_size);
+    return members.iter().zip(offsets).all(|(member, field_offset)| {
+      classify_sysv_eightbytes(
+        member,
+        offset + field_offset,
+        eightbytes,
+        pointer_size,
+      )
+    });
+  }
+  if let Type::Array { element, len } = *ty {
+    let size = layout(element, pointer_size).size;
+    return (0..len).all(|idx| {
+      classify_sysv_eightbytes(
+        element,
+        offset + idx * size,
+        eightbytes,
+        pointer_size,
+      )
+    });
+  }
+  // the tag is an integer, and each variant's value shares the same bytes
+  if let Type::Enum { variants } = *ty {
+    let enum_layout = enum_layout(variants, pointer_size);
+    if !offset.is_multiple_of(enum_layout.tag_size) {
+      return false;
+    }
+    eightbytes[(offset / 8) as usize].class = Class::Integer;
+    let payload_offset = offset + enum_layout.payload_offset;
+    return variants.iter().filter_map(|variant| variant.payload).all(
+      |payload| {
+        classify_sysv_eightbytes(
+          payload,
+          payload_offset,
+          eightbytes,
+          pointer_size,
+        )
+      },
+    );
+  }
+
+  let scalar_layout = layout(ty, pointer_size);
+  if !offset.is_multiple_of(scalar_layout.align) {
+    return false;
+  }
+
+  // a `Str` is a pointer and a length, and a closure two pointers
+  if let Type::Str | Type::Closure { .. } = *ty {
+    let first = (offset / 8) as usize;
+    let last = ((offset + scalar_layout.size - 1) / 8) as usize;
+    for eightbyte in &mut eightbytes[first..=last] {
+      eightbyte.class = Class::Integer;
+    }
+    return true;
+  }
+
+  let eightbyte = &mut eightbytes[(offset / 8) as usize];
+  match *ty {
+    Type::Float { size } => {
+      if eightbyte.class == Class::None {
+        eightbyte.class = Class::Sse;
+      }
+      match size {
+        FloatSize::F64 => eightbyte.has_double = true,
+        FloatSize::F32 if offset % 8 == 4 => eightbyte.has_high_float = true,
+        FloatSize::F32 => (),
+      }
+    }
+    _ => eightbyte.class = Class::Integer,
+  }
+  true
+}
+
+// `None` means that the type is passed in memory
+fn classify_sysv(ty: &Type, pointer_size: u64) -> Option<Vec<Piece>> {
+  let size = layout(ty, pointer_size).size;
+  if size > 16 {
+    return None;
+  }
+
+  let empty = Eightbyte {
+    class: Class::None,
+    has_double: false,
+    has_high_float: false,
+  };
+  let mut eightbytes = vec![empty; round_up(size, 8) as usize / 8];
+  if !classify_sysv_eightbytes(ty, 0, &mut eightbytes, pointer_size) {
+    return None;
+  }
+
+  let pieces = eightbytes.iter().enumerate().map(|(i, eightbyte)| {
+    let bytes = std::cmp::min(8, size - 8 * i as u64);
+    match eightbyte.class {
+      Class::Sse if eightbyte.has_double => Piece::Float64,
+      Class::Sse if eightbyte.has_high_float => Piece::Float32x2,
+      Class::Sse => Piece::Float32,
+      Class::Integer | Class::None => Piece::Int { bytes },
+    }
+  });
+  Some(pieces.collect())
+}
+
+// the number of integer and SSE registers, respectively
+fn sysv_registers(pieces: &[Piece]) -> (u32, u32) {
+  let mut ints = 0;
+  let mut sse = 0;
+  for piece in pieces {
+    match *piece {
+      Piece::Int { .. } => ints += 1,
+      _ => sse += 1,
+    }
+  }
+  (ints, sse)
+}
+
+impl FunctionAbi {
+  pub fn new(
+    target: Option<Target>,
+    params: &[&Type],
+    ret: &Type,
+    pointer_size: u64,
+  ) -> Self {
+    match target {
+      Some(Target::SysV64) => Self::sysv(params, ret, pointer_size),
+      Some(Target::Win64) => Self::win64(params, ret, pointer_size),
+      None => {
+        let direct = |ty: &Type| {
+          if ty.is_aggregate() {
+            panic!(
+              "passing structs to extern functions is only supported on \
+               x86_64 targets"
+            );
+          }
+          PassMode::Direct(extension(ty))
+        };
+        FunctionAbi {
+          params: params.iter().map(|ty| direct(ty)).collect(),
+          ret: direct(ret),
+        }
+      }
+    }
+  }
+
+  fn sysv(params: &[&Type], ret: &Type, pointer_size: u64) -> Self {
+    let mut free_ints = SYSV_INTEGER_REGISTERS;
+    let mut free_sse = SYSV_SSE_REGISTERS;
+
+    let ret = if !ret.is_aggregate() {
+      PassMode::Direct(extension(ret))
+    } else if layout(ret, pointer_size).size == 0 {
+      PassMode::Ignore
+    } else {
+      match classify_sysv(ret, pointer_size) {
+        Some(pieces) => PassMode::Pieces(pieces),
+        None => {
+          // the hidden return pointer takes up the first integer register
+          free_ints -= 1;
+          PassMode::Indirect { byval: false }
+        }
+      }
+    };
+
+    let params = params.iter().map(|ty| {
+      if !ty.is_aggregate() {
+        match **ty {
+          Type::Float { .. } => free_sse = free_sse.saturating_sub(1),
+          _ => free_ints = free_ints.saturating_sub(1),
+        }
+        return PassMode::Direct(extension(ty));
+      }
+      if layout(ty, pointer_size).size == 0 {
+        return PassMode::Ignore;
+      }
+
+      /*
+        an aggregate goes either entirely in registers, or entirely on the
+        stack; later arguments may still use the registers it didn't
+      */
+      match classify_sysv(ty, pointer_size) {
+        Some(pieces) => {
+          let (ints, sse) = sysv_registers(&pieces);
+          if ints <= free_ints && sse <= free_sse {
+            free_ints -= ints;
+            free_sse -= sse;
+            PassMode::Pieces(pieces)
+          } else {
+            PassMode::Indirect { byval: true }
+          }
+        }
+        None => PassMode::Indirect { byval: true },
+      }
+    });
+
+    FunctionAbi {
+      params: params.collect(),
+      ret,
+    }
+  }
+
+  fn win64(params: &[&Type], ret: &Type, pointer_size: u64) -> Self {
+    let lower = |ty: &Type| {
+      if !ty.is_aggregate() {
+        return PassMode::Direct(Extension::None);
+      }
+      match layout(ty, pointer_size).size {
+        0 => PassMode::Ignore,
+        bytes @ 1 | bytes @ 2 | bytes @ 4 | bytes @ 8 => {
+          PassMode::Pieces(vec![Piece::Int { bytes }])
+        }
+        _ => PassMode::Indirect { byval: false },
+      }
+    };
+
+    FunctionAbi {
+      params: params.iter().map(|ty| lower(ty)).collect(),
+      ret: lower(ret),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::interner::Interned;
+  use crate::module::types::StructField;
+  use crate::module::{self, Module};
+  use crate::parser::{self, Parser};
+  use crate::session::SourceModule;
+  use crate::{llvm, string::NfcString};
+
+  fn name<'cx>(
+    ctxt: &'cx parser::Context,
+    s: &str,
+  ) -> Interned<'cx, NfcString> {
+    ctxt.get_ident(s)
+  }
+
+  #[test]
+  fn sysv_classification() {
+    let parse_ctxt = parser::Context::new();
+    let ctxt = module::Context::new(&parse_ctxt);
+    let ty = |s: &str| ctxt.get_type(name(&parse_ctxt, s)).unwrap();
+    let struct_of = |fields: &[(&str, &str)]| {
+      let fields = fields
+        .iter()
+        .map(|&(n, t)| StructField {
+          name: name(&parse_ctxt, n),
+          ty: Interned::as_ref(ty(t)),
+        })
+        .collect::<Vec<_>>();
+      ctxt.struct_type(&fields)
+    };
+
+    let complex = struct_of(&[("x", "Float32"), ("y", "Float32")]);
+    let mixed =
+      struct_of(&[("a", "Int32"), ("b", "Float32"), ("c", "Float64")]);
+    let small = struct_of(&[("a", "Int8"), ("b", "Int16")]);
+    let big = struct_of(&[("a", "Int64"), ("b", "Int64"), ("c", "Int64")]);
+    let pair = struct_of(&[("a", "Int64"), ("b", "Int64")]);
+
+    assert_eq!(classify_sysv(&complex, 8), Some(vec![Piece::Float32x2]));
+    assert_eq!(
+      classify_sysv(&mixed, 8),
+      Some(vec![Piece::Int { bytes: 8 }, Piece::Float64]),
+    );
+    assert_eq!(
+      classify_sysv(&small, 8),
+      Some(vec![Piece::Int { bytes: 4 }])
+    );
+    assert_eq!(classify_sysv(&big, 8), None);
+
+    let abi = FunctionAbi::new(
+      Some(Target::SysV64),
+      &[&pair, &pair, &pair, &pair, &ty("Int8")],
+      &big,
+      8,
+    );
+    let pieces = PassMode::Pieces(vec![Piece::Int { bytes: 8 }; 2]);
+    assert_eq!(abi.ret, PassMode::Indirect { byval: false });
+    assert_eq!(
+      abi.params,
+      vec![
+        pieces.clone(),
+        pieces.clone(),
+        PassMode::Indirect { byval: true },
+        PassMode::Indirect { byval: true },
+        PassMode::Direct(Extension::Sign),
+      ],
+    );
+
+    let abi =
+      FunctionAbi::new(Some(Target::Win64), &[&complex, &mixed], &small, 8);
+    assert_eq!(abi.ret, PassMode::Pieces(vec![Piece::Int { bytes: 4 }]));
+    assert_eq!(
+      abi.params,
+      vec![
+        PassMode::Pieces(vec![Piece::Int { bytes: 8 }]),
+        PassMode::Indirect { byval: false },
+      ],
+    );
+  }
+
+  const PROGRAM: &str = r#"
+type Complex = struct { x: Float32; y: Float32; };
+type Mixed = struct { a: Int32; b: Float32; c: Float64; };
+type Big = struct { a: Int64; b: Int64; c: Int64; };
+type Pair = struct { a: Int64; b: Int64; };
+
+extern func make_complex(x: Float32, y: Float32) -> Complex;
+extern func check_complex(c: Complex) -> Int32;
+extern func make_mixed() -> Mixed;
+extern func check_mixed(m: Mixed) -> Int32;
+extern func make_big() -> Big;
+extern func check_big(b: Big) -> Int32;
+extern func make_pair(a: Int64) -> Pair;
+extern func check_pairs(a: Pair, b: Pair, c: Pair, d: Pair, e: Int8) -> Int32;
+
+func main() -> Int32 {
+  check_complex(make_complex(1.5, 2.5)) + check_mixed(make_mixed())
+    + check_big(make_big())
+    + check_pairs(make_pair(1), make_pair(2), make_pair(3), make_pair(4), -5)
+}
+"#;
+
+  /*
+    compiles `PROGRAM`, links it against tests/c/abi.c with the system C
+    compiler, and checks that every struct made it across intact
+  */
+  #[test]
+  fn sysv_calls_into_c() {
+    use std::process::Command;
+
+    let parse_ctxt = parser::Context::new();
+    let file = parse_ctxt.add_file("abi.nwt".to_owned(), PROGRAM.to_owned());
+    let mut parser = Parser::new(file, &parse_ctxt);
+    let mut items = vec![];
+    while let Some(item) = parser.next_item() {
+      items.push(item);
+    }
+    let source = SourceModule {
+      path: vec![name(&parse_ctxt, "abi")],
+      items,
+      imports: vec![],
+    };
+    let module_ctxt = module::Context::new(&parse_ctxt);
+    let module = Module::new(vec![source], &module_ctxt);
+
+    let llctxt = llvm::Context::new();
+    if Target::from_triple(llctxt.target_triple()) != Some(Target::SysV64) {
+      return;
+    }
+    module.build(&llctxt);
+
+    // per process, so that test runs at the same time don't collide
+    let dir = std::env::temp_dir()
+      .join(format!("epsilon-abi-test-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let obj = dir.join("abi.o");
+    let exe = dir.join("abi");
+    let c_file =
+      std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/c/abi.c");
+    llctxt
+      .write_obj_file(&mut std::fs::File::create(&obj).unwrap())
+      .unwrap();
+
+    let status = Command::new("cc")
+      .arg(&obj)
+      .arg(&c_file)
+      .arg("-o")
+      .arg(&exe)
+      .status()
+      .unwrap();
+    assert!(status.success());
+
+    let status = Command::new(&exe).status().unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(status.code(), Some(42));
+  }
+}
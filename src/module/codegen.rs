@@ -0,0 +1,1756 @@
+use std::collections::HashMap;
+
+use super::abi::{self, Extension, FunctionAbi, PassMode, Piece};
+use super::eval::Value;
+use super::types::{FloatSize, IntSize, Mutability, Type};
+use super::{
+  Context, Expression, ExpressionKind, Function, FunctionDeclaration,
+  FunctionDefinition, GlobalKind, LoopId, MatchArm, Module, Pattern,
+  PatternKind, Statement,
+};
+
+use crate::interner::Interned;
+use crate::llvm;
+use crate::parser::{BinaryOp, StringKind, UnaryOp};
+use crate::string::NfcString;
+
+struct FunctionInfo<'cx, 'll> {
+  decl: FunctionDeclaration<'cx>,
+  llfunc: llvm::Function<'ll>,
+  // only `extern` functions use the C calling convention
+  signature: Option<CSignature<'ll>>,
+}
+
+/*
+  how a function with the C calling convention is passed its arguments; the
+  attributes are by the index of the LLVM parameter they're on, along with
+  the type that `sret` and `byval` point to
+*/
+#[derive(Clone)]
+struct CSignature<'ll> {
+  abi: FunctionAbi,
+  fn_ty: llvm::FunctionType<'ll>,
+  param_attributes: Vec<(usize, &'static str, Option<llvm::Type<'ll>>)>,
+  ret_attribute: Option<&'static str>,
+}
+
+// a `const` is built where it's used; a `static` is an LLVM global
+enum GlobalInfo<'cx, 'll> {
+  Const(Value<'cx>),
+  Static(llvm::ConstValue<'ll>),
+}
+
+struct LoopTarget<'cx, 'll> {
+  continue_bb: llvm::BasicBlock<'ll>,
+  break_bb: llvm::BasicBlock<'ll>,
+  // the value of each `break`, and the block it's from
+  breaks: Vec<(llvm::Value<'ll>, llvm::BasicBlock<'ll>)>,
+  ty: Interned<'cx, Type<'cx>>,
+  // the number of `defer`s which had been reached when the loop began
+  defers: usize,
+}
+
+/*
+  an immutable local is just its value; a `let mut` local lives in an
+  `alloca` in the entry block, which LLVM's mem2reg turns back into values.
+  So does an array, so that it can be indexed without copying it
+*/
+#[derive(Copy, Clone)]
+enum LocalValue<'ll> {
+  Value(llvm::Value<'ll>),
+  Slot(llvm::Value<'ll>),
+}
+
+struct Codegen<'cx, 'll> {
+  context: &'cx Context<'cx>,
+  llctxt: &'ll llvm::Context,
+  builder: llvm::Builder<'ll>,
+  target: Option<abi::Target>,
+  functions: HashMap<Interned<'cx, NfcString>, FunctionInfo<'cx, 'll>>,
+  globals: HashMap<Interned<'cx, NfcString>, GlobalInfo<'cx, 'll>>,
+  // identical literals share a global
+  strings: HashMap<(StringKind, &'cx str), llvm::ConstValue<'ll>>,
+  // indexed by `LocalId`; `None` until the local's `let` has been built
+  locals: Vec<Option<LocalValue<'ll>>>,
+  local_types: Vec<Interned<'cx, Type<'cx>>>,
+  loops: HashMap<LoopId, LoopTarget<'cx, 'll>>,
+  /*
+    the `defer`s which have been reached in the blocks around the current
+    expression, innermost last; each way out of a block builds them again
+  */
+  defers: Vec<Expression<'cx>>,
+  // `llvm.trap`, for indexing out of bounds; declared when it's first needed
+  trap: Option<llvm::Function<'ll>>,
+  /*
+    the functions which `func`s point to, for the functions which don't use
+    the C calling convention themselves, and the functions which call a
+    `func` as a `closure`, by its type; both are built when they're first
+    needed
+  */
+  adapters: HashMap<Interned<'cx, NfcString>, llvm::Function<'ll>>,
+  thunks: HashMap<Interned<'cx, Type<'cx>>, llvm::Function<'ll>>,
+}
+
+impl<'cx> Module<'cx> {
+  pub fn build(&self, llctxt: &llvm::Context) {
+    let mut cg = Codegen {
+      context: self.context,
+      llctxt,
+      builder: llvm::Builder::new(llctxt),
+      target: abi::Target::from_triple(llctxt.target_triple()),
+      functions: HashMap::new(),
+      globals: HashMap::new(),
+      strings: HashMap::new(),
+      locals: vec![],
+      local_types: vec![],
+      loops: HashMap::new(),
+      defers: vec![],
+      trap: None,
+      adapters: HashMap::new(),
+      thunks: HashMap::new(),
+    };
+
+    // by name, so that a program is always built into the same IR
+    let mut globals = self.globals.iter().collect::<Vec<_>>();
+    globals.sort_by_key(|&(name, _)| name.as_str());
+    let mut functions = self.functions.iter().collect::<Vec<_>>();
+    functions.sort_by_key(|&(name, _)| name.as_str());
+
+    for (&name, global) in globals {
+      let decl = &global.declaration;
+      let info = match decl.kind {
+        GlobalKind::Const => GlobalInfo::Const(global.value.clone()),
+        GlobalKind::Static(mutability) => {
+          let init = cg.const_value(&global.value, decl.ty);
+          let constant = mutability == Mutability::Immutable;
+          let llglobal =
+            llvm::ConstValue::global(llctxt, Some(name), init, constant);
+          let llty = cg.llvm_type(&decl.ty);
+          if init.type_of() == llty {
+            GlobalInfo::Static(llglobal)
+          } else {
+            let ptr_ty = llvm::Type::ptr(llty);
+            GlobalInfo::Static(llvm::ConstValue::bitcast(llglobal, ptr_ty))
+          }
+        }
+      };
+      cg.globals.insert(name, info);
+    }
+
+    for &(&name, func) in &functions {
+      let decl = func.declaration();
+      let info = match *func {
+        Function::External(_) => cg.declare_extern_function(decl),
+        Function::Definition(_) => cg.declare_function(decl),
+      };
+      cg.functions.insert(name, info);
+    }
+
+    for (_, func) in functions {
+      if let Function::Definition(def) = func {
+        cg.build_function(def);
+      }
+    }
+  }
+}
+
+impl<'cx, 'll> Codegen<'cx, 'll> {
+  fn int_bits(&self, size: IntSize) -> libc::c_uint {
+    match size {
+      IntSize::I8 => 8,
+      IntSize::I16 => 16,
+      IntSize::I32 => 32,
+      IntSize::I64 => 64,
+      IntSize::ISize => self.llctxt.pointer_size() * 8,
+    }
+  }
+
+  fn pointer_size(&self) -> u64 {
+    u64::from(self.llctxt.pointer_size())
+  }
+
+  fn enum_layout(&self, ty: &Type<'cx>) -> abi::EnumLayout {
+    match *ty {
+      Type::Enum { variants } => {
+        abi::enum_layout(variants, self.pointer_size())
+      }
+      _ => panic!("ICE: enum layout of {}", ty),
+    }
+  }
+
+  /*
+    an enum is its tag, and then the union of the values of its variants,
+    which is an array of integers as aligned as the most aligned of them
+  */
+  fn llvm_type(&self, ty: &Type<'cx>) -> llvm::Type<'ll> {
+    match *ty {
+      Type::SignedInt { size } | Type::UnsignedInt { size } => {
+        llvm::Type::int(self.llctxt, self.int_bits(size))
+      }
+      Type::Float {
+        size: FloatSize::F32,
+      } => llvm::Type::float(self.llctxt),
+      Type::Float {
+        size: FloatSize::F64,
+      } => llvm::Type::double(self.llctxt),
+      Type::Bool => llvm::Type::int1(self.llctxt),
+      Type::Char => llvm::Type::int(self.llctxt, 32),
+      Type::CChar => llvm::Type::int(self.llctxt, 8),
+      Type::Str => {
+        let ptr = llvm::Type::ptr(llvm::Type::int(self.llctxt, 8));
+        let len = llvm::Type::size_type(self.llctxt);
+        llvm::Type::structure(self.llctxt, &[ptr, len])
+      }
+      Type::Pointer { pointee, .. } => llvm::Type::ptr(self.llvm_type(pointee)),
+      // the function of a `func` is cast to its type where it's called
+      Type::Function { .. } => self.byte_pointer_type(),
+      Type::Closure { .. } => {
+        let ptr = self.byte_pointer_type();
+        llvm::Type::structure(self.llctxt, &[ptr, ptr])
+      }
+      // a tuple is an anonymous struct
+      Type::Struct { .. } | Type::Tuple { .. } => {
+        let fields = ty
+          .members()
+          .unwrap()
+          .into_iter()
+          .map(|member| self.llvm_type(member))
+          .collect::<Vec<_>>();
+        llvm::Type::structure(self.llctxt, &fields)
+      }
+      Type::Array { element, len } => {
+        llvm::Type::array(self.llvm_type(element), len)
+      }
+      Type::Enum { .. } => {
+        let layout = self.enum_layout(ty);
+        let tag = llvm::Type::int(self.llctxt, layout.tag_size as u32 * 8);
+        if layout.payload.size == 0 {
+          return llvm::Type::structure(self.llctxt, &[tag]);
+        }
+        let align = layout.payload.align;
+        let unit = llvm::Type::int(self.llctxt, align as u32 * 8);
+        let payload = llvm::Type::array(unit, layout.payload.size / align);
+        llvm::Type::structure(self.llctxt, &[tag, payload])
+      }
+      // a `!` is never created, so it needs no space
+      Type::Never => llvm::Type::structure(self.llctxt, &[]),
+      Type::Param { .. } => panic!("ICE: type parameter {} in codegen", ty),
+      Type::Infer(_) | Type::Error => {
+        panic!("ICE: unchecked type in codegen: {}", ty)
+      }
+    }
+  }
+
+  fn piece_type(&self, piece: Piece) -> llvm::Type<'ll> {
+    match piece {
+      Piece::Int { bytes } => {
+        llvm::Type::int(self.llctxt, bytes as libc::c_uint * 8)
+      }
+      Piece::Float32 => llvm::Type::float(self.llctxt),
+      Piece::Float32x2 => llvm::Type::vector(llvm::Type::float(self.llctxt), 2),
+      Piece::Float64 => llvm::Type::double(self.llctxt),
+    }
+  }
+
+  // the type which holds all of the pieces, laid out like the original type
+  fn pieces_type(&self, pieces: &[Piece]) -> llvm::Type<'ll> {
+    let pieces = pieces
+      .iter()
+      .map(|&piece| self.piece_type(piece))
+      .collect::<Vec<_>>();
+    llvm::Type::structure(self.llctxt, &pieces)
+  }
+
+  fn declare_function(
+    &self,
+    decl: &FunctionDeclaration<'cx>,
+  ) -> FunctionInfo<'cx, 'll> {
+    let ret_ty = self.llvm_type(&decl.ret_ty);
+    let params = decl
+      .params
+      .iter()
+      .map(|param| self.llvm_type(&param.ty))
+      .collect::<Vec<_>>();
+    let fn_ty = llvm::FunctionType::new(ret_ty, &params);
+    FunctionInfo {
+      decl: decl.clone(),
+      llfunc: llvm::Function::new(decl.name, fn_ty, self.llctxt),
+      signature: None,
+    }
+  }
+
+  fn declare_extern_function(
+    &self,
+    decl: &FunctionDeclaration<'cx>,
+  ) -> FunctionInfo<'cx, 'll> {
+    let param_tys = decl
+      .params
+      .iter()
+      .map(|param| Interned::as_ref(param.ty))
+      .collect::<Vec<_>>();
+    let signature = self.c_signature(&param_tys, &decl.ret_ty);
+    let llfunc = self.declare_c_function(decl.name, &signature);
+    FunctionInfo {
+      decl: decl.clone(),
+      llfunc,
+      signature: Some(signature),
+    }
+  }
+
+  fn declare_c_function(
+    &self,
+    name: Interned<'cx, NfcString>,
+    signature: &CSignature<'ll>,
+  ) -> llvm::Function<'ll> {
+    let llfunc = llvm::Function::new(name, signature.fn_ty, self.llctxt);
+    for &(idx, attr, ty) in &signature.param_attributes {
+      llfunc.add_param_attribute(idx as libc::c_uint, attr, ty);
+    }
+    if let Some(attr) = signature.ret_attribute {
+      llfunc.add_return_attribute(attr);
+    }
+    llfunc
+  }
+
+  fn c_signature(
+    &self,
+    param_tys: &[&Type<'cx>],
+    ret_ty: &Type<'cx>,
+  ) -> CSignature<'ll> {
+    let pointer_size = self.llctxt.pointer_size() as u64;
+    let abi = FunctionAbi::new(self.target, param_tys, ret_ty, pointer_size);
+
+    let extension_attribute = |ext| match ext {
+      Extension::None => None,
+      Extension::Sign => Some("signext"),
+      Extension::Zero => Some("zeroext"),
+    };
+
+    let mut params = vec![];
+    let mut attributes = vec![];
+    let llret_ty = match abi.ret {
+      PassMode::Ignore => llvm::Type::void(self.llctxt),
+      PassMode::Direct(_) => self.llvm_type(ret_ty),
+      PassMode::Pieces(ref pieces) if pieces.len() == 1 => {
+        self.piece_type(pieces[0])
+      }
+      PassMode::Pieces(ref pieces) => self.pieces_type(pieces),
+      PassMode::Indirect { .. } => {
+        let llret_ty = self.llvm_type(ret_ty);
+        attributes.push((0, "sret", Some(llret_ty)));
+        params.push(llvm::Type::ptr(llret_ty));
+        llvm::Type::void(self.llctxt)
+      }
+    };
+
+    for (&param_ty, mode) in param_tys.iter().zip(&abi.params) {
+      match *mode {
+        PassMode::Ignore => (),
+        PassMode::Direct(ext) => {
+          if let Some(attr) = extension_attribute(ext) {
+            attributes.push((params.len(), attr, None));
+          }
+          params.push(self.llvm_type(param_ty));
+        }
+        PassMode::Pieces(ref pieces) => {
+          for &piece in pieces {
+            params.push(self.piece_type(piece));
+          }
+        }
+        PassMode::Indirect { byval } => {
+          let llparam_ty = self.llvm_type(param_ty);
+          if byval {
+            attributes.push((params.len(), "byval", Some(llparam_ty)));
+          }
+          params.push(llvm::Type::ptr(llparam_ty));
+        }
+      }
+    }
+
+    let ret_attribute = match abi.ret {
+      PassMode::Direct(ext) => extension_attribute(ext),
+      _ => None,
+    };
+    CSignature {
+      abi,
+      fn_ty: llvm::FunctionType::new(llret_ty, &params),
+      param_attributes: attributes,
+      ret_attribute,
+    }
+  }
+
+  fn build_function(&mut self, def: &FunctionDefinition<'cx>) {
+    let decl = &def.declaration;
+    let llfunc = self.functions[&decl.name].llfunc;
+
+    self.locals = vec![None; def.locals.len()];
+    self.local_types = def.locals.iter().map(|local| local.ty).collect();
+    for idx in 0..decl.params.len() {
+      let param = llfunc.param(idx as libc::c_uint);
+      self.locals[idx] = Some(LocalValue::Value(param));
+    }
+
+    let bb = llfunc.append_bb();
+    self.builder.attach_to_bb(bb);
+    for (idx, local) in def.locals.iter().enumerate() {
+      let is_array = matches!(*local.ty, Type::Array { .. });
+      if local.mutability == Mutability::Mutable || is_array {
+        let slot = self.builder.build_alloca(self.llvm_type(&local.ty));
+        if let Some(LocalValue::Value(param)) = self.locals[idx] {
+          self.builder.build_store(param, slot);
+        }
+        self.locals[idx] = Some(LocalValue::Slot(slot));
+      }
+    }
+    let ret = self.build_expression(&def.body);
+    match *def.body.ty {
+      Type::Never => self.builder.build_unreachable(),
+      _ => self.builder.build_ret(ret),
+    }
+  }
+
+  /*
+    a call to `callee`, which is a function with the C calling convention,
+    or a pointer to one; an argument which is passed in pieces is spilled to
+    the stack, and then read back as the pieces that the registers hold
+  */
+  fn build_c_call(
+    &mut self,
+    callee: llvm::Value<'ll>,
+    signature: &CSignature<'ll>,
+    ret_ty: Interned<'cx, Type<'cx>>,
+    args: Vec<(llvm::Value<'ll>, Interned<'cx, Type<'cx>>)>,
+  ) -> llvm::Value<'ll> {
+    let abi = &signature.abi;
+
+    let mut llargs = vec![];
+    let ret_slot = match abi.ret {
+      PassMode::Indirect { .. } => {
+        let slot = self.builder.build_entry_alloca(self.llvm_type(&ret_ty));
+        llargs.push(slot);
+        Some(slot)
+      }
+      _ => None,
+    };
+
+    for ((arg, ty), mode) in args.into_iter().zip(&abi.params) {
+      match *mode {
+        PassMode::Ignore => (),
+        PassMode::Direct(_) => llargs.push(arg),
+        PassMode::Pieces(ref pieces) => {
+          let pieces_ty = self.pieces_type(pieces);
+          let slot = self.builder.build_entry_alloca(pieces_ty);
+          let arg_ptr_ty = llvm::Type::ptr(self.llvm_type(&ty));
+          let arg_slot = self.builder.build_bitcast(slot, arg_ptr_ty);
+          self.builder.build_store(arg, arg_slot);
+          for idx in 0..pieces.len() {
+            let piece_ptr =
+              self.builder.build_struct_gep(slot, idx as libc::c_uint);
+            llargs.push(self.builder.build_load(piece_ptr));
+          }
+        }
+        PassMode::Indirect { .. } => {
+          let slot = self.builder.build_entry_alloca(self.llvm_type(&ty));
+          self.builder.build_store(arg, slot);
+          llargs.push(slot);
+        }
+      }
+    }
+
+    let ret = self.builder.build_call(callee, &llargs);
+    for &(idx, attr, ty) in &signature.param_attributes {
+      ret.add_call_param_attribute(idx as libc::c_uint, attr, ty);
+    }
+    if let Some(attr) = signature.ret_attribute {
+      ret.add_call_return_attribute(attr);
+    }
+    match abi.ret {
+      PassMode::Ignore => {
+        let ret_ty = self.llvm_type(&ret_ty);
+        llvm::Value::from(llvm::ConstValue::null(ret_ty))
+      }
+      PassMode::Direct(_) => ret,
+      PassMode::Pieces(ref pieces) => {
+        // a single piece is returned as itself, not wrapped in a struct
+        let returned_ty = if pieces.len() == 1 {
+          self.piece_type(pieces[0])
+        } else {
+          self.pieces_type(pieces)
+        };
+        let slot = self.builder.build_entry_alloca(self.pieces_type(pieces));
+        let ret_ptr_ty = llvm::Type::ptr(returned_ty);
+        let ret_slot = self.builder.build_bitcast(slot, ret_ptr_ty);
+        self.builder.build_store(ret, ret_slot);
+        let value_ptr_ty = llvm::Type::ptr(self.llvm_type(&ret_ty));
+        let value_slot = self.builder.build_bitcast(slot, value_ptr_ty);
+        self.builder.build_load(value_slot)
+      }
+      PassMode::Indirect { .. } => self.builder.build_load(ret_slot.unwrap()),
+    }
+  }
+
+  fn build_call(
+    &mut self,
+    callee: Interned<'cx, NfcString>,
+    args: &[Expression<'cx>],
+  ) -> llvm::Value<'ll> {
+    let info = &self.functions[&callee];
+    let (signature, llfunc) = (info.signature.clone(), info.llfunc);
+    let ret_ty = info.decl.ret_ty;
+    let params = info.decl.params.iter().map(|param| param.ty);
+    let params = params.collect::<Vec<_>>();
+
+    let args = args
+      .iter()
+      .zip(params)
+      .map(|(arg, ty)| (self.build_value(arg, ty), ty))
+      .collect::<Vec<_>>();
+
+    match signature {
+      Some(signature) => {
+        let llfunc = llvm::Value::from(llfunc);
+        self.build_c_call(llfunc, &signature, ret_ty, args)
+      }
+      None => {
+        let args = args.into_iter().map(|(arg, _)| arg).collect::<Vec<_>>();
+        self.builder.build_call(llfunc, &args)
+      }
+    }
+  }
+
+  /*
+    a call to a `func` or a `closure`; a closure's function is called with
+    the arguments, and then its environment
+  */
+  fn build_call_indirect(
+    &mut self,
+    callee: &Expression<'cx>,
+    args: &[Expression<'cx>],
+  ) -> llvm::Value<'ll> {
+    let (params, ret_ty) = match *callee.ty {
+      Type::Function { params, ret } | Type::Closure { params, ret } => {
+        (params, self.context.add_type_definition(*ret))
+      }
+      _ => panic!("ICE: call to a value of type {}", callee.ty),
+    };
+    let value = self.build_expression(callee);
+    let args = args
+      .iter()
+      .zip(params)
+      .map(|(arg, &ty)| {
+        let ty = self.context.add_type_definition(*ty);
+        (self.build_value(arg, ty), ty)
+      })
+      .collect::<Vec<_>>();
+
+    match *callee.ty {
+      Type::Function { .. } => {
+        let signature = self.c_signature(params, &ret_ty);
+        let fn_ptr_ty = llvm::Type::ptr(signature.fn_ty.into());
+        let fn_ptr = self.builder.build_bitcast(value, fn_ptr_ty);
+        self.build_c_call(fn_ptr, &signature, ret_ty, args)
+      }
+      _ => {
+        let fn_ty = self.closure_function_type(params, &ret_ty);
+        let fn_ptr = self.builder.build_extract_value(value, 0);
+        let fn_ptr_ty = llvm::Type::ptr(fn_ty.into());
+        let fn_ptr = self.builder.build_bitcast(fn_ptr, fn_ptr_ty);
+        let env = self.builder.build_extract_value(value, 1);
+        let mut args = args.into_iter().map(|(arg, _)| arg).collect::<Vec<_>>();
+        args.push(env);
+        self.builder.build_call(fn_ptr, &args)
+      }
+    }
+  }
+
+  /*
+    the function a closure calls; it takes its environment as a byte
+    pointer, although the function a closure was lifted into takes a pointer
+    to its own environment type
+  */
+  fn closure_function_type(
+    &self,
+    param_tys: &[&Type<'cx>],
+    ret_ty: &Type<'cx>,
+  ) -> llvm::FunctionType<'ll> {
+    let mut params = param_tys
+      .iter()
+      .map(|ty| self.llvm_type(ty))
+      .collect::<Vec<_>>();
+    params.push(self.byte_pointer_type());
+    llvm::FunctionType::new(self.llvm_type(ret_ty), &params)
+  }
+
+  // a `closure` of the type `ty`, from its function and its environment
+  fn build_closure(
+    &mut self,
+    ty: Interned<'cx, Type<'cx>>,
+    fn_ptr: llvm::Value<'ll>,
+    env: llvm::Value<'ll>,
+  ) -> llvm::Value<'ll> {
+    let fn_ptr = self.builder.build_bitcast(fn_ptr, self.byte_pointer_type());
+    let env = self.builder.build_bitcast(env, self.byte_pointer_type());
+    let closure = self.undef(ty);
+    let closure = self.builder.build_insert_value(closure, fn_ptr, 0);
+    self.builder.build_insert_value(closure, env, 1)
+  }
+
+  fn byte_pointer_type(&self) -> llvm::Type<'ll> {
+    llvm::Type::ptr(llvm::Type::int8(self.llctxt))
+  }
+
+  /*
+    a `func` pointing to the function `name`; an `extern` function is
+    pointed to itself, and any other function gets an adapter, which is
+    called with the C calling convention, and calls it
+  */
+  fn build_function_pointer(
+    &mut self,
+    name: Interned<'cx, NfcString>,
+  ) -> llvm::Value<'ll> {
+    let info = &self.functions[&name];
+    let llfunc = match info.signature {
+      Some(_) => info.llfunc,
+      None => self.c_adapter(name),
+    };
+    let llfunc = llvm::Value::from(llfunc);
+    self.builder.build_bitcast(llfunc, self.byte_pointer_type())
+  }
+
+  fn c_adapter(
+    &mut self,
+    name: Interned<'cx, NfcString>,
+  ) -> llvm::Function<'ll> {
+    if let Some(&adapter) = self.adapters.get(&name) {
+      return adapter;
+    }
+    let info = &self.functions[&name];
+    let (decl, llfunc) = (info.decl.clone(), info.llfunc);
+    let param_tys = decl
+      .params
+      .iter()
+      .map(|param| Interned::as_ref(param.ty))
+      .collect::<Vec<_>>();
+    let signature = self.c_signature(&param_tys, &decl.ret_ty);
+    let adapter_name = format!("{}::{{func}}", name);
+    let adapter_name = self.context.parse_context.get_ident(&adapter_name);
+    let adapter = self.declare_c_function(adapter_name, &signature);
+    self.adapters.insert(name, adapter);
+
+    let current_bb = self.builder.current_bb();
+    self.builder.attach_to_bb(adapter.append_bb());
+    let mut llparams = (0..).map(|idx| adapter.param(idx));
+    let ret_slot = match signature.abi.ret {
+      PassMode::Indirect { .. } => llparams.next(),
+      _ => None,
+    };
+    let mut args = vec![];
+    for (param, mode) in decl.params.iter().zip(&signature.abi.params) {
+      let arg = match *mode {
+        PassMode::Ignore => self.undef(param.ty),
+        PassMode::Direct(_) => llparams.next().unwrap(),
+        PassMode::Pieces(ref pieces) => {
+          let slot = self.builder.build_alloca(self.pieces_type(pieces));
+          for idx in 0..pieces.len() {
+            let piece_ptr =
+              self.builder.build_struct_gep(slot, idx as libc::c_uint);
+            self
+              .builder
+              .build_store(llparams.next().unwrap(), piece_ptr);
+          }
+          let arg_ptr_ty = llvm::Type::ptr(self.llvm_type(&param.ty));
+          let arg_slot = self.builder.build_bitcast(slot, arg_ptr_ty);
+          self.builder.build_load(arg_slot)
+        }
+        PassMode::Indirect { .. } => {
+          self.builder.build_load(llparams.next().unwrap())
+        }
+      };
+      args.push(arg);
+    }
+
+    let ret = self.builder.build_call(llfunc, &args);
+    match signature.abi.ret {
+      _ if *decl.ret_ty == Type::Never => self.builder.build_unreachable(),
+      PassMode::Ignore => self.builder.build_ret_void(),
+      PassMode::Direct(_) => self.builder.build_ret(ret),
+      PassMode::Pieces(ref pieces) => {
+        let returned_ty = if pieces.len() == 1 {
+          self.piece_type(pieces[0])
+        } else {
+          self.pieces_type(pieces)
+        };
+        let slot = self.builder.build_alloca(self.pieces_type(pieces));
+        let value_ptr_ty = llvm::Type::ptr(self.llvm_type(&decl.ret_ty));
+        let value_slot = self.builder.build_bitcast(slot, value_ptr_ty);
+        self.builder.build_store(ret, value_slot);
+        let ret_slot = self
+          .builder
+          .build_bitcast(slot, llvm::Type::ptr(returned_ty));
+        let ret = self.builder.build_load(ret_slot);
+        self.builder.build_ret(ret);
+      }
+      PassMode::Indirect { .. } => {
+        self.builder.build_store(ret, ret_slot.unwrap());
+        self.builder.build_ret_void();
+      }
+    }
+    self.builder.attach_to_bb(current_bb);
+    adapter
+  }
+
+  /*
+    the function of a `closure` made from a `func`, whose environment is
+    the `func` itself; there's one for each type of closure
+  */
+  fn closure_thunk(
+    &mut self,
+    ty: Interned<'cx, Type<'cx>>,
+  ) -> llvm::Function<'ll> {
+    if let Some(&thunk) = self.thunks.get(&ty) {
+      return thunk;
+    }
+    let (params, ret_ty) = match *ty {
+      Type::Closure { params, ret } => {
+        (params, self.context.add_type_definition(*ret))
+      }
+      _ => panic!("ICE: closure thunk for a {}", ty),
+    };
+    let fn_ty = self.closure_function_type(params, &ret_ty);
+    let thunk_name = format!("{{{} from a func}}", ty);
+    let thunk_name = self.context.parse_context.get_ident(&thunk_name);
+    let thunk = llvm::Function::new(thunk_name, fn_ty, self.llctxt);
+    self.thunks.insert(ty, thunk);
+
+    let current_bb = self.builder.current_bb();
+    self.builder.attach_to_bb(thunk.append_bb());
+    let signature = self.c_signature(params, &ret_ty);
+    let fn_ptr_ty = llvm::Type::ptr(signature.fn_ty.into());
+    let env = thunk.param(params.len() as libc::c_uint);
+    let fn_ptr = self.builder.build_bitcast(env, fn_ptr_ty);
+    let args = params
+      .iter()
+      .enumerate()
+      .map(|(idx, &param_ty)| {
+        let ty = self.context.add_type_definition(*param_ty);
+        (thunk.param(idx as libc::c_uint), ty)
+      })
+      .collect::<Vec<_>>();
+    let ret = self.build_c_call(fn_ptr, &signature, ret_ty, args);
+    match *ret_ty {
+      Type::Never => self.builder.build_unreachable(),
+      _ => self.builder.build_ret(ret),
+    }
+    self.builder.attach_to_bb(current_bb);
+    thunk
+  }
+
+  /*
+    the value of `expr`, used as a `ty`
+
+    an expression which diverges, like `return`, has no value, and the code
+    after it is never run; but that code still has to be well-typed, so it
+    gets an undefined value of the type it expects
+  */
+  fn build_value(
+    &mut self,
+    expr: &Expression<'cx>,
+    ty: Interned<'cx, Type<'cx>>,
+  ) -> llvm::Value<'ll> {
+    let value = self.build_expression(expr);
+    match *expr.ty {
+      Type::Never => self.undef(ty),
+      _ => value,
+    }
+  }
+
+  fn undef(&self, ty: Interned<'cx, Type<'cx>>) -> llvm::Value<'ll> {
+    llvm::Value::from(llvm::ConstValue::undef(self.llvm_type(&ty)))
+  }
+
+  fn build_expression(&mut self, expr: &Expression<'cx>) -> llvm::Value<'ll> {
+    let ty = expr.ty;
+    match expr.kind {
+      ExpressionKind::IntegerLiteral(i) => {
+        let llty = self.llvm_type(&ty);
+        llvm::Value::from(llvm::ConstValue::int(llty, i, false))
+      }
+      ExpressionKind::FloatLiteral(f) => {
+        let llty = self.llvm_type(&ty);
+        llvm::Value::from(llvm::ConstValue::real(llty, f))
+      }
+      ExpressionKind::CharLiteral(ch) => {
+        let llty = self.llvm_type(&ty);
+        llvm::Value::from(llvm::ConstValue::int(llty, ch as u32, false))
+      }
+      ExpressionKind::BoolLiteral(b) => {
+        let llty = self.llvm_type(&ty);
+        llvm::Value::from(llvm::ConstValue::int(llty, b as u32, false))
+      }
+      ExpressionKind::Local(id) => match self.locals[id].unwrap() {
+        LocalValue::Value(value) => value,
+        LocalValue::Slot(slot) => self.builder.build_load(slot),
+      },
+      ExpressionKind::Global(name) => match self.globals[&name] {
+        GlobalInfo::Const(ref value) => {
+          let value = value.clone();
+          self.build_const(&value, ty)
+        }
+        GlobalInfo::Static(global) => {
+          self.builder.build_load(llvm::Value::from(global))
+        }
+      },
+      ExpressionKind::Call(callee, _, ref args) => {
+        self.build_call(callee, args)
+      }
+      ExpressionKind::InterfaceCall(..) => {
+        panic!("ICE: call through a type parameter in codegen")
+      }
+      ExpressionKind::Function(name, _) => self.build_function_pointer(name),
+      /*
+        each closure expression gets an environment of its own, in the entry
+        block, which lives until the function that created it returns; it's
+        filled in again each time the expression is reached, so a loop
+        doesn't grow the stack
+      */
+      ExpressionKind::Closure(name, _, ref captures) => {
+        let llfunc = self.functions[&name].llfunc;
+        let env_ty = match self.functions[&name].decl.params.last() {
+          Some(param) => param.ty,
+          None => panic!("ICE: closure {} without an environment", name),
+        };
+        let env_ty = match *env_ty {
+          Type::Pointer { pointee, .. } => self.llvm_type(pointee),
+          _ => panic!("ICE: closure environment of type {}", env_ty),
+        };
+        let mut env = llvm::Value::from(llvm::ConstValue::undef(env_ty));
+        for (idx, capture) in captures.iter().enumerate() {
+          let capture = self.build_value(capture, capture.ty);
+          env =
+            self
+              .builder
+              .build_insert_value(env, capture, idx as libc::c_uint);
+        }
+        let env_slot = self.builder.build_entry_alloca(env_ty);
+        self.builder.build_store(env, env_slot);
+        let fn_ptr = llvm::Value::from(llfunc);
+        self.build_closure(ty, fn_ptr, env_slot)
+      }
+      // the environment is the `func`, which the thunk calls
+      ExpressionKind::ToClosure(ref operand) => {
+        let fn_ptr = self.build_value(operand, operand.ty);
+        let thunk = llvm::Value::from(self.closure_thunk(ty));
+        self.build_closure(ty, thunk, fn_ptr)
+      }
+      ExpressionKind::CallIndirect(ref callee, ref args) => {
+        self.build_call_indirect(callee, args)
+      }
+      // a value which isn't in memory is spilled, to point to it
+      ExpressionKind::AddressOf(ref operand) => {
+        if let Some(ptr) = self.build_place(operand) {
+          return ptr;
+        }
+        let value = self.build_value(operand, operand.ty);
+        let slot = self.builder.build_entry_alloca(self.llvm_type(&operand.ty));
+        self.builder.build_store(value, slot);
+        slot
+      }
+      ExpressionKind::Deref(ref ptr) => {
+        let ptr = self.build_value(ptr, ptr.ty);
+        self.builder.build_load(ptr)
+      }
+      ExpressionKind::Array(ref elements) => {
+        let element_ty = match *ty {
+          Type::Array { element, .. } => {
+            self.context.add_type_definition(*element)
+          }
+          _ => panic!("ICE: array literal of type {}", ty),
+        };
+        let mut array = self.undef(ty);
+        for (idx, element) in elements.iter().enumerate() {
+          let element = self.build_value(element, element_ty);
+          array = self.builder.build_insert_value(
+            array,
+            element,
+            idx as libc::c_uint,
+          );
+        }
+        array
+      }
+      ExpressionKind::ArrayRepeat(ref value, len) => {
+        let value = self.build_value(value, value.ty);
+        let mut array = self.undef(ty);
+        for idx in 0..len {
+          array =
+            self
+              .builder
+              .build_insert_value(array, value, idx as libc::c_uint);
+        }
+        array
+      }
+      ExpressionKind::Index(ref base, ref index) => {
+        if let Some(ptr) = self.build_place(expr) {
+          return self.builder.build_load(ptr);
+        }
+        // an array which isn't in memory is spilled, to index it dynamically
+        let array = self.build_value(base, base.ty);
+        let slot = self.builder.build_entry_alloca(self.llvm_type(&base.ty));
+        self.builder.build_store(array, slot);
+        let ptr = self.build_element_ptr(slot, base.ty, index);
+        self.builder.build_load(ptr)
+      }
+      ExpressionKind::Field(ref base, idx) => {
+        if let Some(ptr) = self.build_place(expr) {
+          return self.builder.build_load(ptr);
+        }
+        let value = self.build_value(base, base.ty);
+        self.builder.build_extract_value(value, idx as libc::c_uint)
+      }
+      ExpressionKind::Struct(ref values) => {
+        let members = match ty.members() {
+          Some(members) => members,
+          None => panic!("ICE: struct literal of type {}", ty),
+        };
+        let mut value = self.undef(ty);
+        for &(idx, ref field) in values {
+          let field_ty = self.context.add_type_definition(*members[idx]);
+          let field = self.build_value(field, field_ty);
+          value =
+            self
+              .builder
+              .build_insert_value(value, field, idx as libc::c_uint);
+        }
+        value
+      }
+      ExpressionKind::Variant(idx, ref value) => {
+        self.build_variant(ty, idx, value.as_ref().map(|value| &**value))
+      }
+      ExpressionKind::StringLiteral(kind, s) => {
+        llvm::Value::from(self.build_string_literal(kind, s))
+      }
+      ExpressionKind::UnaryOp(UnaryOp::Negate, ref operand) => {
+        if let ExpressionKind::IntegerLiteral(i) = operand.kind {
+          let llty = self.llvm_type(&ty);
+          let value = llvm::ConstValue::int(llty, i.wrapping_neg(), false);
+          return llvm::Value::from(value);
+        }
+        let operand = self.build_expression(operand);
+        match *ty {
+          Type::Float { .. } => self.builder.build_fneg(operand),
+          Type::Never => operand,
+          _ => self.builder.build_neg(operand),
+        }
+      }
+      ExpressionKind::UnaryOp(UnaryOp::Not, ref operand) => {
+        let operand = self.build_expression(operand);
+        match *ty {
+          Type::Never => operand,
+          _ => self.builder.build_not(operand),
+        }
+      }
+      ExpressionKind::BinaryOp(op, ref lhs, ref rhs) if op.is_logical() => {
+        self.build_logical(op, lhs, rhs)
+      }
+      ExpressionKind::BinaryOp(op, ref lhs, ref rhs) => {
+        let operand_ty = match *lhs.ty {
+          Type::Never => rhs.ty,
+          _ => lhs.ty,
+        };
+        if *operand_ty == Type::Never {
+          self.build_expression(lhs);
+          self.build_expression(rhs);
+          return self.undef(ty);
+        }
+        let lhs = self.build_value(lhs, operand_ty);
+        let rhs = self.build_value(rhs, operand_ty);
+        if op.is_comparison() {
+          self.build_comparison(op, operand_ty, lhs, rhs)
+        } else {
+          self.build_arithmetic(op, ty, lhs, rhs)
+        }
+      }
+      ExpressionKind::If(ref cond, ref then, ref els) => {
+        self.build_if(cond, then, els.as_ref().map(|els| &**els), ty)
+      }
+      ExpressionKind::While(id, ref cond, ref body) => {
+        self.build_loop(id, Some(cond), body, ty)
+      }
+      ExpressionKind::Loop(id, ref body) => self.build_loop(id, None, body, ty),
+      ExpressionKind::Match(ref scrutinee, ref arms) => {
+        self.build_match(scrutinee, arms, ty)
+      }
+      ExpressionKind::Break(id, ref value) => {
+        let value = match *value {
+          Some(ref value) => self.build_value(value, self.loops[&id].ty),
+          None => self.unit_value(),
+        };
+        self.build_defers(self.loops[&id].defers);
+        let bb = self.builder.current_bb();
+        let target = self.loops.get_mut(&id).unwrap();
+        target.breaks.push((value, bb));
+        self.builder.build_br(target.break_bb);
+        self.start_unreachable_bb();
+        self.unit_value()
+      }
+      ExpressionKind::Continue(id) => {
+        self.build_defers(self.loops[&id].defers);
+        self.builder.build_br(self.loops[&id].continue_bb);
+        self.start_unreachable_bb();
+        self.unit_value()
+      }
+      ExpressionKind::Return(ref value) => {
+        match *value {
+          Some(ref value) if *value.ty == Type::Never => {
+            self.build_expression(value);
+          }
+          Some(ref value) => {
+            let value = self.build_expression(value);
+            self.build_defers(0);
+            self.builder.build_ret(value);
+          }
+          None => {
+            self.build_defers(0);
+            let unit = self.unit_value();
+            self.builder.build_ret(unit);
+          }
+        }
+        self.start_unreachable_bb();
+        self.unit_value()
+      }
+      ExpressionKind::Assign(op, ref place, ref value) => {
+        let value = self.build_value(value, place.ty);
+        let slot = match self.build_place(place) {
+          Some(slot) => slot,
+          None => panic!("ICE: assignment to something other than a place"),
+        };
+        let value = match op {
+          Some(op) => {
+            let current = self.builder.build_load(slot);
+            self.build_arithmetic(op, place.ty, current, value)
+          }
+          None => value,
+        };
+        self.builder.build_store(value, slot);
+        self.unit_value()
+      }
+      ExpressionKind::Error => panic!("ICE: unchecked expression in codegen"),
+      ExpressionKind::Block(ref statements, ref tail) => {
+        let defers = self.defers.len();
+        for stmt in statements {
+          match *stmt {
+            Statement::Let(id, ref init) => {
+              let value = self.build_value(init, self.local_types[id]);
+              match self.locals[id] {
+                Some(LocalValue::Slot(slot)) => {
+                  self.builder.build_store(value, slot)
+                }
+                _ => self.locals[id] = Some(LocalValue::Value(value)),
+              }
+            }
+            Statement::Expression(ref expr) => {
+              self.build_expression(expr);
+            }
+            Statement::Defer(ref expr) => self.defers.push(expr.clone()),
+          }
+        }
+        let value = match *tail {
+          Some(ref tail) => self.build_expression(tail),
+          None => self.unit_value(),
+        };
+        self.build_defers(defers);
+        self.defers.truncate(defers);
+        value
+      }
+    }
+  }
+
+  /*
+    a pointer to the place that `expr` names, if it's in memory: a mutable
+    or array local, a `static`, what a pointer points to, or an element or
+    field of one of those
+  */
+  fn build_place(
+    &mut self,
+    expr: &Expression<'cx>,
+  ) -> Option<llvm::Value<'ll>> {
+    match expr.kind {
+      ExpressionKind::Local(id) => match self.locals[id] {
+        Some(LocalValue::Slot(slot)) => Some(slot),
+        _ => None,
+      },
+      ExpressionKind::Global(name) => match self.globals[&name] {
+        GlobalInfo::Static(global) => Some(llvm::Value::from(global)),
+        GlobalInfo::Const(_) => None,
+      },
+      ExpressionKind::Index(ref base, ref index) => {
+        let ptr = self.build_place(base)?;
+        Some(self.build_element_ptr(ptr, base.ty, index))
+      }
+      ExpressionKind::Field(ref base, idx) => {
+        let ptr = self.build_place(base)?;
+        Some(self.builder.build_struct_gep(ptr, idx as libc::c_uint))
+      }
+      ExpressionKind::Deref(ref ptr) => Some(self.build_value(ptr, ptr.ty)),
+      _ => None,
+    }
+  }
+
+  /*
+    a pointer to an element of the array that `ptr` points to; an index out
+    of bounds traps
+  */
+  fn build_element_ptr(
+    &mut self,
+    ptr: llvm::Value<'ll>,
+    array_ty: Interned<'cx, Type<'cx>>,
+    index: &Expression<'cx>,
+  ) -> llvm::Value<'ll> {
+    let len = match *array_ty {
+      Type::Array { len, .. } => len,
+      _ => panic!("ICE: indexing into a {}", array_ty),
+    };
+    let index = self.build_value(index, index.ty);
+    let size_ty = llvm::Type::size_type(self.llctxt);
+    let llen = llvm::Value::from(llvm::ConstValue::int(size_ty, len, false));
+    let in_bounds =
+      self
+        .builder
+        .build_icmp(llvm::IntPredicate::LLVMIntULT, index, llen);
+
+    let llfunc = self.builder.current_function();
+    let ok_bb = llfunc.append_bb();
+    let trap_bb = llfunc.append_bb();
+    self.builder.build_cond_br(in_bounds, ok_bb, trap_bb);
+    self.builder.attach_to_bb(trap_bb);
+    let trap = self.trap();
+    self.builder.build_call(trap, &[]);
+    self.builder.build_unreachable();
+
+    self.builder.attach_to_bb(ok_bb);
+    let zero = llvm::Value::from(llvm::ConstValue::int(size_ty, 0u64, false));
+    self.builder.build_gep(ptr, &[zero, index])
+  }
+
+  fn tag_value(
+    &self,
+    ty: Interned<'cx, Type<'cx>>,
+    idx: usize,
+  ) -> llvm::ConstValue<'ll> {
+    let tag_size = self.enum_layout(&ty).tag_size;
+    let tag_ty = llvm::Type::int(self.llctxt, tag_size as u32 * 8);
+    llvm::ConstValue::int(tag_ty, idx as u64, false)
+  }
+
+  /*
+    a pointer to the value held by the enum that `ptr` points to, as a
+    `payload_ty`; if every variant's value is zero sized, the enum has no
+    union, and the pointer is to the enum itself
+  */
+  fn build_payload_ptr(
+    &mut self,
+    ptr: llvm::Value<'ll>,
+    enum_ty: Interned<'cx, Type<'cx>>,
+    payload_ty: Interned<'cx, Type<'cx>>,
+  ) -> llvm::Value<'ll> {
+    let ptr = if self.enum_layout(&enum_ty).payload.size == 0 {
+      ptr
+    } else {
+      self.builder.build_struct_gep(ptr, 1)
+    };
+    let payload_ptr_ty = llvm::Type::ptr(self.llvm_type(&payload_ty));
+    self.builder.build_bitcast(ptr, payload_ptr_ty)
+  }
+
+  // a variant which holds a value is built in memory, through its union
+  fn build_variant(
+    &mut self,
+    ty: Interned<'cx, Type<'cx>>,
+    idx: usize,
+    value: Option<&Expression<'cx>>,
+  ) -> llvm::Value<'ll> {
+    let tag = llvm::Value::from(self.tag_value(ty, idx));
+    let value = match value {
+      Some(value) => value,
+      None => {
+        let undef = self.undef(ty);
+        return self.builder.build_insert_value(undef, tag, 0);
+      }
+    };
+    let payload_ty = match *ty {
+      Type::Enum { variants } => variants[idx].payload.unwrap(),
+      _ => panic!("ICE: variant of type {}", ty),
+    };
+    let payload_ty = self.context.add_type_definition(*payload_ty);
+    let value = self.build_value(value, payload_ty);
+
+    let slot = self.builder.build_entry_alloca(self.llvm_type(&ty));
+    let tag_ptr = self.builder.build_struct_gep(slot, 0);
+    self.builder.build_store(tag, tag_ptr);
+    let payload_ptr = self.build_payload_ptr(slot, ty, payload_ty);
+    self.builder.build_store(value, payload_ptr);
+    self.builder.build_load(slot)
+  }
+
+  /*
+    each arm's pattern is tested in turn, against the scrutinee in memory;
+    a test which fails goes on to the next arm. The match is exhaustive, so
+    the end of the last test is never reached
+  */
+  fn build_match(
+    &mut self,
+    scrutinee: &Expression<'cx>,
+    arms: &[MatchArm<'cx>],
+    ty: Interned<'cx, Type<'cx>>,
+  ) -> llvm::Value<'ll> {
+    let ptr = match self.build_place(scrutinee) {
+      Some(ptr) => ptr,
+      None => {
+        let value = self.build_expression(scrutinee);
+        if *scrutinee.ty == Type::Never {
+          return self.undef(ty);
+        }
+        let llty = self.llvm_type(&scrutinee.ty);
+        let slot = self.builder.build_entry_alloca(llty);
+        self.builder.build_store(value, slot);
+        slot
+      }
+    };
+
+    let llfunc = self.builder.current_function();
+    let end_bb = llfunc.append_bb();
+    let mut incoming = vec![];
+    for arm in arms {
+      let next_bb = llfunc.append_bb();
+      self.build_pattern(&arm.pattern, ptr, next_bb);
+      if *arm.body.ty == Type::Never {
+        self.build_expression(&arm.body);
+        self.builder.build_unreachable();
+      } else {
+        let value = self.build_expression(&arm.body);
+        incoming.push((value, self.builder.current_bb()));
+        self.builder.build_br(end_bb);
+      }
+      self.builder.attach_to_bb(next_bb);
+    }
+    self.builder.build_unreachable();
+
+    self.builder.attach_to_bb(end_bb);
+    if incoming.is_empty() {
+      self.undef(ty)
+    } else {
+      self.builder.build_phi(self.llvm_type(&ty), &incoming)
+    }
+  }
+
+  /*
+    tests whether the value that `ptr` points to matches `pattern`, going to
+    `fail_bb` if it doesn't, and setting its bindings if it does
+  */
+  fn build_pattern(
+    &mut self,
+    pattern: &Pattern<'cx>,
+    ptr: llvm::Value<'ll>,
+    fail_bb: llvm::BasicBlock<'ll>,
+  ) {
+    let ty = pattern.ty;
+    let llty = self.llvm_type(&ty);
+    let expected = match pattern.kind {
+      PatternKind::Wildcard => return,
+      PatternKind::Binding(id) => {
+        let value = self.builder.build_load(ptr);
+        match self.locals[id] {
+          Some(LocalValue::Slot(slot)) => self.builder.build_store(value, slot),
+          _ => self.locals[id] = Some(LocalValue::Value(value)),
+        }
+        return;
+      }
+      PatternKind::Integer(i) => llvm::ConstValue::int(llty, i as u64, false),
+      PatternKind::Char(ch) => llvm::ConstValue::int(llty, ch as u32, false),
+      PatternKind::Bool(b) => llvm::ConstValue::int(llty, b as u32, false),
+      PatternKind::Variant(idx, ref payload) => {
+        let tag_ptr = self.builder.build_struct_gep(ptr, 0);
+        let tag = self.builder.build_load(tag_ptr);
+        let expected = self.tag_value(ty, idx);
+        self.build_test(tag, expected, fail_bb);
+        if let Some(payload) = payload {
+          let payload_ptr = self.build_payload_ptr(ptr, ty, payload.ty);
+          self.build_pattern(payload, payload_ptr, fail_bb);
+        }
+        return;
+      }
+      PatternKind::Error => panic!("ICE: unchecked pattern in codegen"),
+    };
+    let value = self.builder.build_load(ptr);
+    self.build_test(value, expected, fail_bb);
+  }
+
+  // continues in a new block if `value` is `expected`, or goes to `fail_bb`
+  fn build_test(
+    &mut self,
+    value: llvm::Value<'ll>,
+    expected: llvm::ConstValue<'ll>,
+    fail_bb: llvm::BasicBlock<'ll>,
+  ) {
+    let expected = llvm::Value::from(expected);
+    let is_equal =
+      self
+        .builder
+        .build_icmp(llvm::IntPredicate::LLVMIntEQ, value, expected);
+    let ok_bb = self.builder.current_function().append_bb();
+    self.builder.build_cond_br(is_equal, ok_bb, fail_bb);
+    self.builder.attach_to_bb(ok_bb);
+  }
+
+  fn trap(&mut self) -> llvm::Function<'ll> {
+    if let Some(trap) = self.trap {
+      return trap;
+    }
+    let name = self.context.parse_context.get_ident("llvm.trap");
+    let void = llvm::Type::void(self.llctxt);
+    let fn_ty = llvm::FunctionType::new(void, &[]);
+    let trap = llvm::Function::new(name, fn_ty, self.llctxt);
+    self.trap = Some(trap);
+    trap
+  }
+
+  /*
+    the code units of a string literal; all but `"..."` and `b"..."` are
+    NUL-terminated, since byte strings are often binary data
+  */
+  fn string_units(
+    &self,
+    kind: StringKind,
+    s: &str,
+  ) -> (libc::c_uint, Vec<u32>) {
+    let mut units = match kind {
+      StringKind::Normal | StringKind::CString => {
+        s.bytes().map(u32::from).collect::<Vec<_>>()
+      }
+      StringKind::Bytes => s.chars().map(u32::from).collect(),
+      StringKind::Utf16 => s.encode_utf16().map(u32::from).collect(),
+      StringKind::Utf32 => s.chars().map(u32::from).collect(),
+    };
+    let bits = match kind {
+      StringKind::Normal | StringKind::CString | StringKind::Bytes => 8,
+      StringKind::Utf16 => 16,
+      StringKind::Utf32 => 32,
+    };
+    match kind {
+      StringKind::Normal | StringKind::Bytes => (),
+      _ => units.push(0),
+    }
+    (bits, units)
+  }
+
+  /*
+    the code units of a literal go in a private global; a literal is a
+    pointer to the first of them, except for `"..."`, which is that pointer,
+    and the number of bytes
+  */
+  fn build_string_literal(
+    &mut self,
+    kind: StringKind,
+    s: &'cx str,
+  ) -> llvm::ConstValue<'ll> {
+    if let Some(&value) = self.strings.get(&(kind, s)) {
+      return value;
+    }
+
+    let (bits, units) = self.string_units(kind, s);
+    let unit_ty = llvm::Type::int(self.llctxt, bits);
+    let units = units
+      .into_iter()
+      .map(|unit| llvm::ConstValue::int(unit_ty, unit, false))
+      .collect::<Vec<_>>();
+    let array = llvm::ConstValue::array(unit_ty, &units);
+    let global = llvm::ConstValue::global(self.llctxt, None, array, true);
+
+    let size_ty = llvm::Type::size_type(self.llctxt);
+    let zero = llvm::ConstValue::int(size_ty, 0u64, false);
+    let ptr = llvm::ConstValue::gep(global, &[zero, zero]);
+    let value = match kind {
+      StringKind::Normal => {
+        let len = llvm::ConstValue::int(size_ty, s.len() as u64, false);
+        llvm::ConstValue::structure(self.llctxt, &[ptr, len])
+      }
+      _ => ptr,
+    };
+
+    self.strings.insert((kind, s), value);
+    value
+  }
+
+  /*
+    a constant, used as a value; if its type isn't the LLVM type of `ty`, as
+    for constants with enums in them, it's loaded from a global
+  */
+  fn build_const(
+    &mut self,
+    value: &Value<'cx>,
+    ty: Interned<'cx, Type<'cx>>,
+  ) -> llvm::Value<'ll> {
+    let constant = self.const_value(value, ty);
+    let llty = self.llvm_type(&ty);
+    if constant.type_of() == llty {
+      return llvm::Value::from(constant);
+    }
+    let global = llvm::ConstValue::global(self.llctxt, None, constant, true);
+    let ptr = llvm::ConstValue::bitcast(global, llvm::Type::ptr(llty));
+    self.builder.build_load(llvm::Value::from(ptr))
+  }
+
+  /*
+    the value of a constant, as a `ty`
+
+    an enum's union can't be written as a constant, since the value in it
+    may not be an integer; so a constant enum is a struct with the same
+    layout instead, and so is an array of them whose elements differ in
+    type. The empty array at the start gives it the enum's alignment
+  */
+  fn const_value(
+    &mut self,
+    value: &Value<'cx>,
+    ty: Interned<'cx, Type<'cx>>,
+  ) -> llvm::ConstValue<'ll> {
+    let llty = self.llvm_type(&ty);
+    match *value {
+      // `LLVMConstInt` takes the low bits, which are the two's complement
+      Value::Integer(i) => llvm::ConstValue::int(llty, i as u64, false),
+      Value::Float(x) => llvm::ConstValue::real(llty, x),
+      Value::Bool(b) => llvm::ConstValue::int(llty, b as u32, false),
+      Value::Char(ch) => llvm::ConstValue::int(llty, ch as u32, false),
+      Value::String(kind, s) => self.build_string_literal(kind, s),
+      Value::Array(ref elements) => {
+        let (element, llelement) = match *ty {
+          Type::Array { element, .. } => (
+            self.context.add_type_definition(*element),
+            self.llvm_type(element),
+          ),
+          _ => panic!("ICE: array constant of type {}", ty),
+        };
+        let elements = elements
+          .iter()
+          .map(|value| self.const_value(value, element))
+          .collect::<Vec<_>>();
+        if elements.iter().all(|value| value.type_of() == llelement) {
+          llvm::ConstValue::array(llelement, &elements)
+        } else {
+          llvm::ConstValue::structure(self.llctxt, &elements)
+        }
+      }
+      Value::Struct(ref values) => {
+        let members = match ty.members() {
+          Some(members) => members,
+          None => panic!("ICE: struct constant of type {}", ty),
+        };
+        let values = values
+          .iter()
+          .zip(members)
+          .map(|(value, member)| {
+            let field_ty = self.context.add_type_definition(*member);
+            self.const_value(value, field_ty)
+          })
+          .collect::<Vec<_>>();
+        llvm::ConstValue::structure(self.llctxt, &values)
+      }
+      Value::Variant(idx, ref value) => {
+        let layout = self.enum_layout(&ty);
+        let pointer_size = self.pointer_size();
+        let enum_layout = abi::layout(&ty, pointer_size);
+        let byte = llvm::Type::int8(self.llctxt);
+        let padding =
+          |len| llvm::ConstValue::null(llvm::Type::array(byte, len));
+        let align_ty =
+          llvm::Type::int(self.llctxt, enum_layout.align as u32 * 8);
+
+        let mut fields = vec![
+          llvm::ConstValue::null(llvm::Type::array(align_ty, 0)),
+          self.tag_value(ty, idx),
+        ];
+        let mut end = layout.tag_size;
+        if let Some(ref value) = *value {
+          let payload_ty = match *ty {
+            Type::Enum { variants } => variants[idx].payload.unwrap(),
+            _ => unreachable!(),
+          };
+          let payload_ty = self.context.add_type_definition(*payload_ty);
+          if layout.payload_offset > end {
+            fields.push(padding(layout.payload_offset - end));
+          }
+          fields.push(self.const_value(value, payload_ty));
+          end =
+            layout.payload_offset + abi::layout(&payload_ty, pointer_size).size;
+        }
+        if enum_layout.size > end {
+          fields.push(padding(enum_layout.size - end));
+        }
+        llvm::ConstValue::structure(self.llctxt, &fields)
+      }
+    }
+  }
+
+  fn unit_value(&self) -> llvm::Value<'ll> {
+    let unit_ty = llvm::Type::structure(self.llctxt, &[]);
+    llvm::Value::from(llvm::ConstValue::null(unit_ty))
+  }
+
+  /*
+    the `defer`s reached since the first `outer` of them, latest first, for a
+    way out of the blocks they're in. They stay where they are, since the
+    code after this is in those blocks too, if it's reached at all
+  */
+  fn build_defers(&mut self, outer: usize) {
+    for idx in (outer..self.defers.len()).rev() {
+      let expr = self.defers[idx].clone();
+      self.build_expression(&expr);
+    }
+  }
+
+  /*
+    code after a `break` or `continue` is never run, but it still needs a
+    block to go in
+  */
+  fn start_unreachable_bb(&mut self) {
+    let bb = self.builder.current_function().append_bb();
+    self.builder.attach_to_bb(bb);
+  }
+
+  /*
+    a `while` loop checks its condition at the top of each iteration, and
+    that's where `continue` goes; a `loop` ends only by `break`, so its value
+    comes from those
+  */
+  fn build_loop(
+    &mut self,
+    id: LoopId,
+    cond: Option<&Expression<'cx>>,
+    body: &Expression<'cx>,
+    ty: Interned<'cx, Type<'cx>>,
+  ) -> llvm::Value<'ll> {
+    let llfunc = self.builder.current_function();
+    let body_bb = llfunc.append_bb();
+    let continue_bb = match cond {
+      Some(_) => llfunc.append_bb(),
+      None => body_bb,
+    };
+    let end_bb = llfunc.append_bb();
+    self.loops.insert(
+      id,
+      LoopTarget {
+        continue_bb,
+        break_bb: end_bb,
+        breaks: vec![],
+        ty,
+        defers: self.defers.len(),
+      },
+    );
+
+    self.builder.build_br(continue_bb);
+    if let Some(cond) = cond {
+      self.builder.attach_to_bb(continue_bb);
+      let bool_ty = self.context.add_type_definition(Type::Bool);
+      let cond = self.build_value(cond, bool_ty);
+      self.builder.build_cond_br(cond, body_bb, end_bb);
+    }
+
+    self.builder.attach_to_bb(body_bb);
+    self.build_expression(body);
+    self.builder.build_br(continue_bb);
+
+    self.builder.attach_to_bb(end_bb);
+    let target = self.loops.remove(&id).unwrap();
+    if cond.is_some() {
+      self.unit_value()
+    } else if target.breaks.is_empty() {
+      self.undef(ty)
+    } else {
+      self.builder.build_phi(self.llvm_type(&ty), &target.breaks)
+    }
+  }
+
+  fn build_if(
+    &mut self,
+    cond: &Expression<'cx>,
+    then: &Expression<'cx>,
+    els: Option<&Expression<'cx>>,
+    ty: Interned<'cx, Type<'cx>>,
+  ) -> llvm::Value<'ll> {
+    let llfunc = self.builder.current_function();
+    let then_bb = llfunc.append_bb();
+    let else_bb = llfunc.append_bb();
+    let end_bb = llfunc.append_bb();
+
+    let bool_ty = self.context.add_type_definition(Type::Bool);
+    let cond = self.build_value(cond, bool_ty);
+    self.builder.build_cond_br(cond, then_bb, else_bb);
+
+    /*
+      the branches may have added blocks; the phi is from where they end,
+      unless they diverge
+    */
+    let mut incoming = vec![];
+    for &(bb, branch) in &[(then_bb, Some(then)), (else_bb, els)] {
+      self.builder.attach_to_bb(bb);
+      match branch {
+        Some(branch) if *branch.ty == Type::Never => {
+          self.build_expression(branch);
+          self.builder.build_unreachable();
+        }
+        Some(branch) => {
+          let value = self.build_expression(branch);
+          incoming.push((value, self.builder.current_bb()));
+          self.builder.build_br(end_bb);
+        }
+        None => {
+          incoming.push((self.unit_value(), self.builder.current_bb()));
+          self.builder.build_br(end_bb);
+        }
+      }
+    }
+
+    self.builder.attach_to_bb(end_bb);
+    if incoming.is_empty() {
+      self.undef(ty)
+    } else {
+      self.builder.build_phi(self.llvm_type(&ty), &incoming)
+    }
+  }
+
+  /*
+    `a && b` is `if a { b } else { false }`, and `a || b` is
+    `if a { true } else { b }`
+  */
+  fn build_logical(
+    &mut self,
+    op: BinaryOp,
+    lhs: &Expression<'cx>,
+    rhs: &Expression<'cx>,
+  ) -> llvm::Value<'ll> {
+    let llfunc = self.builder.current_function();
+    let rhs_bb = llfunc.append_bb();
+    let end_bb = llfunc.append_bb();
+
+    let bool_ty = self.context.add_type_definition(Type::Bool);
+    let lhs = self.build_value(lhs, bool_ty);
+    let lhs_end = self.builder.current_bb();
+    let short_circuit = match op {
+      BinaryOp::And => {
+        self.builder.build_cond_br(lhs, rhs_bb, end_bb);
+        false
+      }
+      _ => {
+        self.builder.build_cond_br(lhs, end_bb, rhs_bb);
+        true
+      }
+    };
+
+    self.builder.attach_to_bb(rhs_bb);
+    let rhs = self.build_value(rhs, bool_ty);
+    let rhs_end = self.builder.current_bb();
+    self.builder.build_br(end_bb);
+
+    self.builder.attach_to_bb(end_bb);
+    let bool_ty = llvm::Type::int1(self.llctxt);
+    let short_circuit =
+      llvm::ConstValue::int(bool_ty, short_circuit as u32, false);
+    let incoming =
+      [(llvm::Value::from(short_circuit), lhs_end), (rhs, rhs_end)];
+    self.builder.build_phi(bool_ty, &incoming)
+  }
+
+  fn build_arithmetic(
+    &mut self,
+    op: BinaryOp,
+    ty: Interned<'cx, Type<'cx>>,
+    lhs: llvm::Value<'ll>,
+    rhs: llvm::Value<'ll>,
+  ) -> llvm::Value<'ll> {
+    let b = &mut self.builder;
+    match (*ty, op) {
+      (Type::SignedInt { .. }, BinaryOp::Add)
+      | (Type::UnsignedInt { .. }, BinaryOp::Add) => b.build_add(lhs, rhs),
+      (Type::SignedInt { .. }, BinaryOp::Sub)
+      | (Type::UnsignedInt { .. }, BinaryOp::Sub) => b.build_sub(lhs, rhs),
+      (Type::SignedInt { .. }, BinaryOp::Mul)
+      | (Type::UnsignedInt { .. }, BinaryOp::Mul) => b.build_mul(lhs, rhs),
+      (Type::SignedInt { .. }, BinaryOp::Div) => b.build_sdiv(lhs, rhs),
+      (Type::UnsignedInt { .. }, BinaryOp::Div) => b.build_udiv(lhs, rhs),
+      (Type::SignedInt { .. }, BinaryOp::Rem) => b.build_srem(lhs, rhs),
+      (Type::UnsignedInt { .. }, BinaryOp::Rem) => b.build_urem(lhs, rhs),
+      (Type::Float { .. }, BinaryOp::Add) => b.build_fadd(lhs, rhs),
+      (Type::Float { .. }, BinaryOp::Sub) => b.build_fsub(lhs, rhs),
+      (Type::Float { .. }, BinaryOp::Mul) => b.build_fmul(lhs, rhs),
+      (Type::Float { .. }, BinaryOp::Div) => b.build_fdiv(lhs, rhs),
+      (Type::Float { .. }, BinaryOp::Rem) => b.build_frem(lhs, rhs),
+      _ => panic!("cannot use {:?} on values of type {}", op, ty),
+    }
+  }
+
+  fn build_comparison(
+    &mut self,
+    op: BinaryOp,
+    ty: Interned<'cx, Type<'cx>>,
+    lhs: llvm::Value<'ll>,
+    rhs: llvm::Value<'ll>,
+  ) -> llvm::Value<'ll> {
+    use llvm::{IntPredicate as I, RealPredicate as R};
+
+    let int_pred = |signed| match (op, signed) {
+      (BinaryOp::Equal, _) => I::LLVMIntEQ,
+      (BinaryOp::NotEqual, _) => I::LLVMIntNE,
+      (BinaryOp::Less, true) => I::LLVMIntSLT,
+      (BinaryOp::Less, false) => I::LLVMIntULT,
+      (BinaryOp::LessEqual, true) => I::LLVMIntSLE,
+      (BinaryOp::LessEqual, false) => I::LLVMIntULE,
+      (BinaryOp::Greater, true) => I::LLVMIntSGT,
+      (BinaryOp::Greater, false) => I::LLVMIntUGT,
+      (BinaryOp::GreaterEqual, true) => I::LLVMIntSGE,
+      (BinaryOp::GreaterEqual, false) => I::LLVMIntUGE,
+      _ => unreachable!(),
+    };
+
+    match *ty {
+      Type::SignedInt { .. } => {
+        self.builder.build_icmp(int_pred(true), lhs, rhs)
+      }
+      Type::UnsignedInt { .. } | Type::Pointer { .. } => {
+        self.builder.build_icmp(int_pred(false), lhs, rhs)
+      }
+      Type::Char | Type::CChar => {
+        self.builder.build_icmp(int_pred(false), lhs, rhs)
+      }
+      Type::Bool => match op {
+        BinaryOp::Equal | BinaryOp::NotEqual => {
+          self.builder.build_icmp(int_pred(false), lhs, rhs)
+        }
+        _ => panic!("cannot use {:?} on values of type {}", op, ty),
+      },
+      Type::Float { .. } => {
+        // ordered comparisons, except `!=`, which is true for NaNs
+        let pred = match op {
+          BinaryOp::Equal => R::LLVMRealOEQ,
+          BinaryOp::NotEqual => R::LLVMRealUNE,
+          BinaryOp::Less => R::LLVMRealOLT,
+          BinaryOp::LessEqual => R::LLVMRealOLE,
+          BinaryOp::Greater => R::LLVMRealOGT,
+          BinaryOp::GreaterEqual => R::LLVMRealOGE,
+          _ => unreachable!(),
+        };
+        self.builder.build_fcmp(pred, lhs, rhs)
+      }
+      Type::Struct { .. }
+      | Type::Tuple { .. }
+      | Type::Array { .. }
+      | Type::Enum { .. }
+      | Type::Function { .. }
+      | Type::Closure { .. }
+      | Type::Str
+      | Type::Never
+      | Type::Param { .. }
+      | Type::Infer(_)
+      | Type::Error => {
+        panic!("cannot use {:?} on values of type {}", op, ty)
+      }
+    }
+  }
+}
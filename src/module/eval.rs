@@ -0,0 +1,752 @@
+/*
+  compile-time evaluation, of the initializers of `const`s and `static`s,
+  the lengths of arrays, and the `const func`s that they call
+
+  the evaluator walks the typed IR, after type checking has succeeded, so it
+  doesn't need to check types again; it only rejects what can't be done at
+  compile time, arithmetic which overflows or divides by zero, and indexing
+  out of bounds. Integers are held as their mathematical value, and checked
+  against the range of their type after every operation; as in type
+  checking, the target isn't known yet, so `ISize` and `USize` are taken to
+  be 64 bits.
+
+  a `const` may use other `const`s, in any order; each is evaluated once,
+  the first time it's needed, and a `const` which is used while it's being
+  evaluated is a cycle. A call gets a new frame of locals, and `break`,
+  `continue`, and `return` unwind to their target as an `Err(Stop)`.
+  Evaluation which runs for too long, or recurses too deeply, is stopped,
+  since it might never end.
+*/
+
+use super::types::{FloatSize, IntSize, Type};
+use super::{Expression, ExpressionKind, GlobalKind, Items, LocalId, LoopId};
+use super::{Pattern, PatternKind, Statement};
+
+use crate::interner::Interned;
+use crate::parser::{BinaryOp, Span, StringKind, UnaryOp};
+use crate::string::NfcString;
+
+// the number of expressions which may be evaluated for a single constant
+const STEP_LIMIT: u64 = 1_000_000;
+const CALL_DEPTH_LIMIT: usize = 128;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value<'cx> {
+  Integer(i128),
+  Float(f64),
+  Bool(bool),
+  Char(char),
+  String(StringKind, &'cx str),
+  Array(Vec<Value<'cx>>),
+  // the fields, in order; `()` is the empty struct
+  Struct(Vec<Value<'cx>>),
+  // the index of the variant, and the value it holds
+  Variant(usize, Option<Box<Value<'cx>>>),
+}
+
+// why evaluation stopped early
+enum Stop<'cx> {
+  // an error, which has already been reported
+  Error,
+  Break(LoopId, Value<'cx>),
+  Continue(LoopId),
+  Return(Value<'cx>),
+}
+
+type Eval<'cx> = Result<Value<'cx>, Stop<'cx>>;
+
+pub(super) struct Evaluator<'cx, 'a> {
+  items: &'a mut Items<'cx>,
+  // the locals of the function or initializer being evaluated
+  locals: Vec<Option<Value<'cx>>>,
+  depth: usize,
+  steps: u64,
+}
+
+impl<'cx> Value<'cx> {
+  pub fn unit() -> Self {
+    Value::Struct(vec![])
+  }
+}
+
+impl<'cx> std::fmt::Display for Value<'cx> {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    let (open, values, close) = match *self {
+      Value::Integer(i) => return write!(f, "{}", i),
+      Value::Float(x) => return write!(f, "{:?}", x),
+      Value::Bool(b) => return write!(f, "{}", b),
+      Value::Char(ch) => return write!(f, "{:?}", ch),
+      Value::String(_, s) => return write!(f, "{:?}", s),
+      Value::Variant(idx, None) => return write!(f, "variant {}", idx),
+      Value::Variant(idx, Some(ref value)) => {
+        return write!(f, "variant {}({})", idx, value)
+      }
+      Value::Array(ref elements) => ("[", elements, "]"),
+      Value::Struct(ref fields) => ("(", fields, ")"),
+    };
+    f.write_str(open)?;
+    for (idx, value) in values.iter().enumerate() {
+      if idx != 0 {
+        f.write_str(", ")?;
+      }
+      write!(f, "{}", value)?;
+    }
+    f.write_str(close)
+  }
+}
+
+impl<'cx, 'a> Evaluator<'cx, 'a> {
+  pub(super) fn new(items: &'a mut Items<'cx>) -> Self {
+    Evaluator {
+      items,
+      locals: vec![],
+      depth: 0,
+      steps: 0,
+    }
+  }
+
+  // the value of a global's initializer, or `None` if there was an error
+  pub(super) fn global(
+    &mut self,
+    name: Interned<'cx, NfcString>,
+  ) -> Option<Value<'cx>> {
+    if let Some(value) = self.items.values.get(&name) {
+      return value.clone();
+    }
+
+    let init = self.items.initializer(name)?;
+    self.items.in_progress.push(name);
+    let value = self.constant(&init);
+    self.items.in_progress.pop();
+
+    self.items.values.insert(name, value.clone());
+    value
+  }
+
+  // a checked expression in a const context, which has no locals around it
+  pub(super) fn constant(
+    &mut self,
+    expr: &Expression<'cx>,
+  ) -> Option<Value<'cx>> {
+    let locals = std::mem::take(&mut self.locals);
+    let value = match self.expression(expr) {
+      Ok(value) => Some(value),
+      Err(Stop::Error) => None,
+      Err(_) => panic!("ICE: `break` or `return` out of a constant"),
+    };
+    self.locals = locals;
+    value
+  }
+
+  fn error(&mut self, span: Span, msg: impl std::fmt::Display) {
+    let msg = self.items.context.parse_context.render(span, msg);
+    self.items.errors.push(msg);
+  }
+
+  fn unsupported(&mut self, span: Span, what: &str) -> Eval<'cx> {
+    let msg = format!("{} can't be evaluated at compile time", what);
+    self.error(span, msg);
+    Err(Stop::Error)
+  }
+
+  // counts `steps` towards the limit
+  fn step(&mut self, steps: u64, span: Span) -> Result<(), Stop<'cx>> {
+    self.steps += steps;
+    if self.steps > STEP_LIMIT {
+      let msg = format!(
+        "compile-time evaluation took more than {} steps; it may never end",
+        STEP_LIMIT,
+      );
+      self.error(span, msg);
+      Err(Stop::Error)
+    } else {
+      Ok(())
+    }
+  }
+
+  fn set_local(&mut self, id: LocalId, value: Value<'cx>) {
+    if self.locals.len() <= id {
+      self.locals.resize(id + 1, None);
+    }
+    self.locals[id] = Some(value);
+  }
+
+  fn const_value(
+    &mut self,
+    name: Interned<'cx, NfcString>,
+    span: Span,
+  ) -> Eval<'cx> {
+    if let Some(cycle) = self.items.cycle(name) {
+      self.error(span, format!("cyclic constant: {}", cycle));
+      return Err(Stop::Error);
+    }
+    self.global(name).ok_or(Stop::Error)
+  }
+
+  // a call to a generic function is to its instance for `type_args`
+  fn call(
+    &mut self,
+    name: Interned<'cx, NfcString>,
+    type_args: &[Interned<'cx, Type<'cx>>],
+    args: Vec<Value<'cx>>,
+    span: Span,
+  ) -> Eval<'cx> {
+    let def = if type_args.is_empty() {
+      self.items.definition(name)
+    } else {
+      let instance = self.items.instance(name, type_args);
+      instance.and_then(|instance| self.items.definition(instance))
+    };
+    // without a definition, it failed to type check, or it's a cycle
+    let def = match def {
+      Some(def) => def,
+      None => {
+        if let Some(cycle) = self.items.cycle(name) {
+          self.error(span, format!("cyclic dependency: {}", cycle));
+        }
+        return Err(Stop::Error);
+      }
+    };
+    if self.depth == CALL_DEPTH_LIMIT {
+      let msg = format!(
+        "compile-time evaluation recursed more than {} calls deep",
+        CALL_DEPTH_LIMIT,
+      );
+      self.error(span, msg);
+      return Err(Stop::Error);
+    }
+
+    let mut frame = vec![None; def.locals.len()];
+    for (local, arg) in frame.iter_mut().zip(args) {
+      *local = Some(arg);
+    }
+    let locals = std::mem::replace(&mut self.locals, frame);
+    self.depth += 1;
+    let value = self.expression(&def.body);
+    self.depth -= 1;
+    self.locals = locals;
+    match value {
+      Ok(value) | Err(Stop::Return(value)) => Ok(value),
+      Err(stop) => Err(stop),
+    }
+  }
+
+  // the index of an element of an array of type `ty`, which must be in bounds
+  fn index(
+    &mut self,
+    ty: Interned<'cx, Type<'cx>>,
+    index: &Expression<'cx>,
+  ) -> Result<usize, Stop<'cx>> {
+    let len = match *ty {
+      Type::Array { len, .. } => len,
+      _ => panic!("ICE: indexing into a {} in a constant", ty),
+    };
+    match self.expression(index)? {
+      Value::Integer(i) if i < i128::from(len) => Ok(i as usize),
+      Value::Integer(i) => {
+        let msg = format!(
+          "index {} is out of bounds for an array of length {}",
+          i, len,
+        );
+        self.error(index.span, msg);
+        Err(Stop::Error)
+      }
+      value => panic!("ICE: index of {} in a constant", value),
+    }
+  }
+
+  fn is_place(expr: &Expression<'cx>) -> bool {
+    match expr.kind {
+      ExpressionKind::Local(_) => true,
+      ExpressionKind::Index(ref base, _)
+      | ExpressionKind::Field(ref base, _) => Self::is_place(base),
+      _ => false,
+    }
+  }
+
+  /*
+    a place, as a local, and the path of elements and fields into it; only
+    locals may be assigned to at compile time, since `static`s can't be used
+  */
+  fn place(
+    &mut self,
+    expr: &Expression<'cx>,
+  ) -> Result<(LocalId, Vec<usize>), Stop<'cx>> {
+    match expr.kind {
+      ExpressionKind::Local(id) => Ok((id, vec![])),
+      ExpressionKind::Index(ref base, ref index) => {
+        let (id, mut path) = self.place(base)?;
+        path.push(self.index(base.ty, index)?);
+        Ok((id, path))
+      }
+      ExpressionKind::Field(ref base, idx) => {
+        let (id, mut path) = self.place(base)?;
+        path.push(idx);
+        Ok((id, path))
+      }
+      _ => panic!("ICE: assignment to something other than a place"),
+    }
+  }
+
+  fn place_mut(&mut self, id: LocalId, path: &[usize]) -> &mut Value<'cx> {
+    let mut value = match self.locals.get_mut(id) {
+      Some(Some(value)) => value,
+      _ => panic!("ICE: local used before its `let`"),
+    };
+    for &idx in path {
+      value = match *value {
+        Value::Array(ref mut values) | Value::Struct(ref mut values) => {
+          &mut values[idx]
+        }
+        ref value => panic!("ICE: projection out of {} in a constant", value),
+      };
+    }
+    value
+  }
+
+  /*
+    the evaluation of each kind of expression is split out into its own
+    function, since the evaluator recurses deeply, and a single function
+    would need the stack space of every kind at once
+  */
+  fn expression(&mut self, expr: &Expression<'cx>) -> Eval<'cx> {
+    let ty = expr.ty;
+    let span = expr.span;
+    self.step(1, span)?;
+    match expr.kind {
+      ExpressionKind::IntegerLiteral(i) => Ok(Value::Integer(i128::from(i))),
+      ExpressionKind::FloatLiteral(x) => Ok(Value::Float(Self::round(ty, x))),
+      ExpressionKind::CharLiteral(ch) => Ok(Value::Char(ch)),
+      ExpressionKind::BoolLiteral(b) => Ok(Value::Bool(b)),
+      ExpressionKind::StringLiteral(kind, s) => Ok(Value::String(kind, s)),
+      ExpressionKind::Local(id) => Ok(self.place_mut(id, &[]).clone()),
+      ExpressionKind::Global(name) => match self.items.globals[&name].kind {
+        GlobalKind::Const => self.const_value(name, span),
+        GlobalKind::Static(_) => {
+          panic!("ICE: the value of a `static` in a constant")
+        }
+      },
+      ExpressionKind::UnaryOp(..) | ExpressionKind::BinaryOp(..) => {
+        self.operator(expr)
+      }
+      ExpressionKind::Call(name, ref type_args, ref args) => {
+        let args = args
+          .iter()
+          .map(|arg| self.expression(arg))
+          .collect::<Result<Vec<_>, _>>()?;
+        self.call(name, type_args, args, span)
+      }
+      ExpressionKind::InterfaceCall(..) => {
+        panic!("ICE: call through a type parameter in a constant")
+      }
+      ExpressionKind::AddressOf(_) | ExpressionKind::Deref(_) => {
+        panic!("ICE: pointer to a place in a constant")
+      }
+      ExpressionKind::Function(..)
+      | ExpressionKind::Closure(..)
+      | ExpressionKind::ToClosure(_)
+      | ExpressionKind::CallIndirect(..) => {
+        panic!("ICE: function value in a constant")
+      }
+      ExpressionKind::Array(_)
+      | ExpressionKind::ArrayRepeat(..)
+      | ExpressionKind::Index(..)
+      | ExpressionKind::Field(..)
+      | ExpressionKind::Struct(_)
+      | ExpressionKind::Variant(..) => self.aggregate(expr),
+      ExpressionKind::Block(..)
+      | ExpressionKind::If(..)
+      | ExpressionKind::While(..)
+      | ExpressionKind::Loop(..)
+      | ExpressionKind::Break(..)
+      | ExpressionKind::Continue(_)
+      | ExpressionKind::Return(_)
+      | ExpressionKind::Match(..)
+      | ExpressionKind::Assign(..) => self.control_flow(expr),
+      ExpressionKind::Error => {
+        panic!("ICE: unchecked expression in a constant")
+      }
+    }
+  }
+
+  // unary and binary operators
+  fn operator(&mut self, expr: &Expression<'cx>) -> Eval<'cx> {
+    let ty = expr.ty;
+    let span = expr.span;
+    match expr.kind {
+      ExpressionKind::UnaryOp(op, ref operand) => {
+        let operand = self.expression(operand)?;
+        match (op, operand) {
+          (UnaryOp::Negate, Value::Integer(i)) => {
+            self.check_range(ty, -i, span, || format!("-{}", i))
+          }
+          (UnaryOp::Negate, Value::Float(x)) => Ok(Value::Float(-x)),
+          (UnaryOp::Not, Value::Bool(b)) => Ok(Value::Bool(!b)),
+          (op, operand) => {
+            panic!("ICE: cannot use {:?} on {} in a constant", op, operand)
+          }
+        }
+      }
+      ExpressionKind::BinaryOp(op, ref lhs, ref rhs) => {
+        let lhs_value = self.expression(lhs)?;
+        // `&&` and `||` only evaluate their right side if they need to
+        match (op, lhs_value) {
+          (BinaryOp::And, Value::Bool(false)) => Ok(Value::Bool(false)),
+          (BinaryOp::Or, Value::Bool(true)) => Ok(Value::Bool(true)),
+          (BinaryOp::And, _) | (BinaryOp::Or, _) => self.expression(rhs),
+          (op, lhs_value) => {
+            let rhs_value = self.expression(rhs)?;
+            if op.is_comparison() {
+              self.comparison(op, lhs_value, rhs_value, span)
+            } else {
+              self.arithmetic(op, ty, lhs_value, rhs_value, span)
+            }
+          }
+        }
+      }
+      _ => unreachable!(),
+    }
+  }
+
+  // arrays, structs, and enums, and their elements and fields
+  fn aggregate(&mut self, expr: &Expression<'cx>) -> Eval<'cx> {
+    let ty = expr.ty;
+    let span = expr.span;
+    match expr.kind {
+      ExpressionKind::Array(ref elements) => {
+        let elements = elements
+          .iter()
+          .map(|element| self.expression(element))
+          .collect::<Result<Vec<_>, _>>()?;
+        Ok(Value::Array(elements))
+      }
+      ExpressionKind::ArrayRepeat(ref value, len) => {
+        let value = self.expression(value)?;
+        self.step(len, span)?;
+        Ok(Value::Array(vec![value; len as usize]))
+      }
+      // the length of a `Str` is known, but not where its bytes will be
+      ExpressionKind::Field(ref base, idx) if *base.ty == Type::Str => {
+        match self.expression(base)? {
+          Value::String(_, s) if idx == 1 => {
+            Ok(Value::Integer(s.len() as i128))
+          }
+          Value::String(..) => self.unsupported(span, "the pointer of a Str"),
+          value => panic!("ICE: field of {} in a constant", value),
+        }
+      }
+      ExpressionKind::Index(ref base, _)
+      | ExpressionKind::Field(ref base, _)
+        if Self::is_place(base) =>
+      {
+        let (id, path) = self.place(expr)?;
+        Ok(self.place_mut(id, &path).clone())
+      }
+      ExpressionKind::Index(ref base, ref index) => {
+        let value = self.expression(base)?;
+        let idx = self.index(base.ty, index)?;
+        match value {
+          Value::Array(mut elements) => Ok(elements.swap_remove(idx)),
+          value => panic!("ICE: indexing into {} in a constant", value),
+        }
+      }
+      ExpressionKind::Field(ref base, idx) => match self.expression(base)? {
+        Value::Struct(mut fields) => Ok(fields.swap_remove(idx)),
+        value => panic!("ICE: field of {} in a constant", value),
+      },
+      ExpressionKind::Struct(ref values) => {
+        let len = match ty.members() {
+          Some(members) => members.len(),
+          None => panic!("ICE: struct literal of type {}", ty),
+        };
+        let mut fields = vec![None; len];
+        for (idx, value) in values {
+          fields[*idx] = Some(self.expression(value)?);
+        }
+        Ok(Value::Struct(
+          fields.into_iter().map(Option::unwrap).collect(),
+        ))
+      }
+      ExpressionKind::Variant(idx, ref value) => {
+        let value = match *value {
+          Some(ref value) => Some(Box::new(self.expression(value)?)),
+          None => None,
+        };
+        Ok(Value::Variant(idx, value))
+      }
+      _ => unreachable!(),
+    }
+  }
+
+  // blocks, branches, loops, and assignment
+  fn control_flow(&mut self, expr: &Expression<'cx>) -> Eval<'cx> {
+    let span = expr.span;
+    match expr.kind {
+      ExpressionKind::Block(ref statements, ref tail) => {
+        let mut deferred = vec![];
+        let value = self.block(statements, tail, &mut deferred);
+        // however the block was left, unless it was by an error
+        if let Err(Stop::Error) = value {
+          return value;
+        }
+        for expr in deferred.into_iter().rev() {
+          self.expression(expr)?;
+        }
+        value
+      }
+      ExpressionKind::If(ref cond, ref then, ref els) => {
+        match (self.expression(cond)?, els) {
+          (Value::Bool(true), _) => self.expression(then),
+          (Value::Bool(false), Some(els)) => self.expression(els),
+          (Value::Bool(false), None) => Ok(Value::unit()),
+          (cond, _) => panic!("ICE: `if` on {} in a constant", cond),
+        }
+      }
+      ExpressionKind::While(id, ref cond, ref body) => {
+        loop {
+          match self.expression(cond)? {
+            Value::Bool(true) => (),
+            Value::Bool(false) => break,
+            cond => panic!("ICE: `while` on {} in a constant", cond),
+          }
+          match self.expression(body) {
+            Ok(_) => (),
+            Err(Stop::Continue(target)) if target == id => (),
+            Err(Stop::Break(target, _)) if target == id => break,
+            Err(stop) => return Err(stop),
+          }
+        }
+        Ok(Value::unit())
+      }
+      ExpressionKind::Loop(id, ref body) => loop {
+        match self.expression(body) {
+          Ok(_) => (),
+          Err(Stop::Continue(target)) if target == id => (),
+          Err(Stop::Break(target, value)) if target == id => return Ok(value),
+          Err(stop) => return Err(stop),
+        }
+      },
+      ExpressionKind::Break(id, ref value) => {
+        let value = match *value {
+          Some(ref value) => self.expression(value)?,
+          None => Value::unit(),
+        };
+        Err(Stop::Break(id, value))
+      }
+      ExpressionKind::Continue(id) => Err(Stop::Continue(id)),
+      ExpressionKind::Match(ref scrutinee, ref arms) => {
+        let value = self.expression(scrutinee)?;
+        match arms.iter().find(|arm| self.matches(&arm.pattern, &value)) {
+          Some(arm) => self.expression(&arm.body),
+          None => panic!("ICE: no arm matches {} in a constant", value),
+        }
+      }
+      ExpressionKind::Return(ref value) => {
+        let value = match *value {
+          Some(ref value) => self.expression(value)?,
+          None => Value::unit(),
+        };
+        Err(Stop::Return(value))
+      }
+      ExpressionKind::Assign(op, ref place, ref value) => {
+        let value = self.expression(value)?;
+        let (id, path) = self.place(place)?;
+        let value = match op {
+          Some(op) => {
+            let current = self.place_mut(id, &path).clone();
+            self.arithmetic(op, place.ty, current, value, span)?
+          }
+          None => value,
+        };
+        *self.place_mut(id, &path) = value;
+        Ok(Value::unit())
+      }
+      _ => unreachable!(),
+    }
+  }
+
+  /*
+    the statements and tail of a block; the `defer`s which are reached are
+    pushed onto `deferred`, to be run by the caller however the block is left
+  */
+  fn block<'e>(
+    &mut self,
+    statements: &'e [Statement<'cx>],
+    tail: &'e Option<Box<Expression<'cx>>>,
+    deferred: &mut Vec<&'e Expression<'cx>>,
+  ) -> Eval<'cx> {
+    for stmt in statements {
+      match *stmt {
+        Statement::Let(id, ref init) => {
+          let value = self.expression(init)?;
+          self.set_local(id, value);
+        }
+        Statement::Expression(ref expr) => {
+          self.expression(expr)?;
+        }
+        Statement::Defer(ref expr) => deferred.push(expr),
+      }
+    }
+    match *tail {
+      Some(ref tail) => self.expression(tail),
+      None => Ok(Value::unit()),
+    }
+  }
+
+  // whether `pattern` matches `value`; if it does, its bindings are set
+  fn matches(&mut self, pattern: &Pattern<'cx>, value: &Value<'cx>) -> bool {
+    match (&pattern.kind, value) {
+      (PatternKind::Wildcard, _) => true,
+      (&PatternKind::Binding(id), value) => {
+        self.set_local(id, value.clone());
+        true
+      }
+      (&PatternKind::Integer(lhs), &Value::Integer(rhs)) => lhs == rhs,
+      (&PatternKind::Char(lhs), &Value::Char(rhs)) => lhs == rhs,
+      (&PatternKind::Bool(lhs), &Value::Bool(rhs)) => lhs == rhs,
+      (&PatternKind::Variant(idx, _), &Value::Variant(value_idx, _))
+        if idx != value_idx =>
+      {
+        false
+      }
+      (PatternKind::Variant(_, None), Value::Variant(..)) => true,
+      (
+        PatternKind::Variant(_, Some(payload)),
+        Value::Variant(_, Some(value)),
+      ) => self.matches(payload, value),
+      _ => panic!(
+        "ICE: matching {:?} against {} in a constant",
+        pattern, value
+      ),
+    }
+  }
+
+  // a `Float32` is computed as a `Float64`, and rounded after each operation
+  fn round(ty: Interned<'cx, Type<'cx>>, x: f64) -> f64 {
+    match *ty {
+      Type::Float {
+        size: FloatSize::F32,
+      } => f64::from(x as f32),
+      _ => x,
+    }
+  }
+
+  fn check_range(
+    &mut self,
+    ty: Interned<'cx, Type<'cx>>,
+    value: i128,
+    span: Span,
+    describe: impl FnOnce() -> String,
+  ) -> Eval<'cx> {
+    let bits = |size| match size {
+      IntSize::I8 => 8,
+      IntSize::I16 => 16,
+      IntSize::I32 => 32,
+      IntSize::I64 | IntSize::ISize => 64,
+    };
+    let (min, max) = match *ty {
+      Type::SignedInt { size } => {
+        let max = (1i128 << (bits(size) - 1)) - 1;
+        (-max - 1, max)
+      }
+      Type::UnsignedInt { size } => (0, (1i128 << bits(size)) - 1),
+      _ => panic!("ICE: integer constant of type {}", ty),
+    };
+    if value < min || value > max {
+      let msg = format!("{} doesn't fit in {}", describe(), ty);
+      self.error(span, msg);
+      Err(Stop::Error)
+    } else {
+      Ok(Value::Integer(value))
+    }
+  }
+
+  fn arithmetic(
+    &mut self,
+    op: BinaryOp,
+    ty: Interned<'cx, Type<'cx>>,
+    lhs: Value<'cx>,
+    rhs: Value<'cx>,
+    span: Span,
+  ) -> Eval<'cx> {
+    match (lhs, rhs) {
+      (Value::Integer(lhs), Value::Integer(rhs)) => {
+        let value = match op {
+          BinaryOp::Add => lhs.checked_add(rhs),
+          BinaryOp::Sub => lhs.checked_sub(rhs),
+          BinaryOp::Mul => lhs.checked_mul(rhs),
+          BinaryOp::Div | BinaryOp::Rem if rhs == 0 => {
+            self.error(span, "division by zero in a constant");
+            return Err(Stop::Error);
+          }
+          // `i128` division truncates, like LLVM's `sdiv` and `udiv`
+          BinaryOp::Div => Some(lhs / rhs),
+          BinaryOp::Rem => Some(lhs % rhs),
+          _ => panic!("ICE: cannot use {:?} on integers in a constant", op),
+        };
+        let describe =
+          || format!("the result of `{}` on {} and {}", op, lhs, rhs);
+        match value {
+          Some(value) => self.check_range(ty, value, span, describe),
+          None => {
+            let msg = format!("{} doesn't fit in {}", describe(), ty);
+            self.error(span, msg);
+            Err(Stop::Error)
+          }
+        }
+      }
+      (Value::Float(lhs), Value::Float(rhs)) => {
+        let value = match op {
+          BinaryOp::Add => lhs + rhs,
+          BinaryOp::Sub => lhs - rhs,
+          BinaryOp::Mul => lhs * rhs,
+          BinaryOp::Div => lhs / rhs,
+          BinaryOp::Rem => lhs % rhs,
+          _ => panic!("ICE: cannot use {:?} on floats in a constant", op),
+        };
+        Ok(Value::Float(Self::round(ty, value)))
+      }
+      (lhs, rhs) => {
+        panic!(
+          "ICE: cannot use {:?} on {} and {} in a constant",
+          op, lhs, rhs
+        )
+      }
+    }
+  }
+
+  fn comparison(
+    &mut self,
+    op: BinaryOp,
+    lhs: Value<'cx>,
+    rhs: Value<'cx>,
+    span: Span,
+  ) -> Eval<'cx> {
+    use std::cmp::Ordering;
+
+    let ordering = match (&lhs, &rhs) {
+      (Value::Integer(lhs), Value::Integer(rhs)) => lhs.partial_cmp(rhs),
+      (Value::Float(lhs), Value::Float(rhs)) => lhs.partial_cmp(rhs),
+      (Value::Bool(lhs), Value::Bool(rhs)) => lhs.partial_cmp(rhs),
+      (Value::Char(lhs), Value::Char(rhs)) => lhs.partial_cmp(rhs),
+      // the addresses of strings aren't known until link time
+      (Value::String(..), Value::String(..)) => {
+        return self.unsupported(span, "a comparison of pointers");
+      }
+      _ => panic!("ICE: cannot compare {} and {} in a constant", lhs, rhs),
+    };
+    // only `!=` is true for NaNs, which are unordered
+    let result = match (op, ordering) {
+      (BinaryOp::NotEqual, None) => true,
+      (_, None) => false,
+      (BinaryOp::Equal, Some(ord)) => ord == Ordering::Equal,
+      (BinaryOp::NotEqual, Some(ord)) => ord != Ordering::Equal,
+      (BinaryOp::Less, Some(ord)) => ord == Ordering::Less,
+      (BinaryOp::LessEqual, Some(ord)) => ord != Ordering::Greater,
+      (BinaryOp::Greater, Some(ord)) => ord == Ordering::Greater,
+      (BinaryOp::GreaterEqual, Some(ord)) => ord != Ordering::Less,
+      _ => unreachable!(),
+    };
+    Ok(Value::Bool(result))
+  }
+}
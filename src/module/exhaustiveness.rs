@@ -0,0 +1,365 @@
+/*
+  exhaustiveness checking for `match`, and finding unreachable arms
+
+  this is the usefulness algorithm from Maranget's "Warnings for pattern
+  matching": a pattern is useful after some rows of patterns if there's a
+  value which it matches, and none of the rows do. An arm is unreachable if
+  its pattern isn't useful after the arms above it, and a match is
+  exhaustive if `_` isn't useful after all of its arms. Where a pattern is
+  useful, the algorithm also finds a value that shows it - for a match which
+  isn't exhaustive, that's the value which isn't covered.
+
+  patterns are looked at one column at a time, so that the value held by a
+  variant becomes a column of its own once the variant has been matched.
+  Only enums and `Bool`s can have every constructor listed; integers and
+  characters are only ever covered by a pattern which matches anything.
+*/
+
+use super::types::Type;
+use super::{Pattern, PatternKind};
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Constructor {
+  Variant(usize),
+  Integer(i128),
+  Char(char),
+  Bool(bool),
+}
+
+// a pattern, without what the checker doesn't care about, like bindings
+#[derive(Clone, Debug)]
+enum Pat {
+  Wildcard,
+  Constructor(Constructor, Vec<Pat>),
+}
+
+type Row<'a, 'cx> = Vec<(Pat, &'a Type<'cx>)>;
+
+impl Pat {
+  fn from_pattern(pattern: &Pattern) -> Self {
+    let ctor = |ctor| Pat::Constructor(ctor, vec![]);
+    match pattern.kind {
+      PatternKind::Wildcard | PatternKind::Binding(_) | PatternKind::Error => {
+        Pat::Wildcard
+      }
+      PatternKind::Integer(i) => ctor(Constructor::Integer(i)),
+      PatternKind::Char(ch) => ctor(Constructor::Char(ch)),
+      PatternKind::Bool(b) => ctor(Constructor::Bool(b)),
+      PatternKind::Variant(idx, ref payload) => {
+        let payload = payload.iter().map(|p| Pat::from_pattern(p)).collect();
+        Pat::Constructor(Constructor::Variant(idx), payload)
+      }
+    }
+  }
+
+  // for the message of a match which isn't exhaustive
+  fn describe(&self, ty: &Type) -> String {
+    let (ctor, args) = match *self {
+      Pat::Wildcard => return "_".to_string(),
+      Pat::Constructor(ctor, ref args) => (ctor, args),
+    };
+    match (ctor, ty) {
+      (Constructor::Variant(idx), Type::Enum { variants }) => {
+        let variant = variants[idx];
+        match (variant.payload, args.first()) {
+          (Some(payload), Some(arg)) => {
+            format!("{}({})", variant.name, arg.describe(payload))
+          }
+          _ => variant.name.to_string(),
+        }
+      }
+      (Constructor::Integer(i), _) => i.to_string(),
+      (Constructor::Char(ch), _) => format!("{:?}", ch),
+      (Constructor::Bool(b), _) => b.to_string(),
+      (ctor, ty) => panic!("ICE: {:?} is not a constructor of {}", ctor, ty),
+    }
+  }
+}
+
+// the types of the fields of `ctor`, in a value of type `ty`
+fn fields<'cx>(ctor: Constructor, ty: &Type<'cx>) -> Vec<&'cx Type<'cx>> {
+  match (ctor, ty) {
+    (Constructor::Variant(idx), Type::Enum { variants }) => {
+      variants[idx].payload.into_iter().collect()
+    }
+    _ => vec![],
+  }
+}
+
+// every constructor of `ty`, if there's a finite number of them
+fn all_constructors(ty: &Type) -> Option<Vec<Constructor>> {
+  match *ty {
+    Type::Enum { variants } => {
+      Some((0..variants.len()).map(Constructor::Variant).collect())
+    }
+    Type::Bool => Some(vec![Constructor::Bool(false), Constructor::Bool(true)]),
+    _ => None,
+  }
+}
+
+/*
+  the rows which can match a value built with `ctor`, with the fields of
+  that value in place of the first column
+*/
+fn specialize<'a, 'cx>(
+  rows: &[Row<'a, 'cx>],
+  ctor: Constructor,
+  fields: &[&'a Type<'cx>],
+) -> Vec<Row<'a, 'cx>> {
+  let mut specialized = vec![];
+  for row in rows {
+    let mut new_row: Row = match row[0].0 {
+      Pat::Wildcard => fields.iter().map(|&ty| (Pat::Wildcard, ty)).collect(),
+      Pat::Constructor(c, ref args) if c == ctor => {
+        args.iter().cloned().zip(fields.iter().cloned()).collect()
+      }
+      Pat::Constructor(..) => continue,
+    };
+    new_row.extend(row[1..].iter().cloned());
+    specialized.push(new_row);
+  }
+  specialized
+}
+
+/*
+  if `row` is useful after `rows`, a value which shows it, as one pattern
+  for each column; every row has the same number of columns
+*/
+fn useful<'a, 'cx>(
+  rows: &[Row<'a, 'cx>],
+  row: &[(Pat, &'a Type<'cx>)],
+) -> Option<Vec<Pat>> {
+  let (head, ty) = match row.first() {
+    Some(&(ref head, ty)) => (head, ty),
+    None if rows.is_empty() => return Some(vec![]),
+    None => return None,
+  };
+
+  // the arguments of a constructor are its first fields in the witness
+  let rebuild = |ctor, witness: Vec<Pat>| {
+    let arity = fields(ctor, ty).len();
+    let mut rest = witness;
+    let args = rest.drain(..arity).collect();
+    let mut witness = vec![Pat::Constructor(ctor, args)];
+    witness.extend(rest);
+    witness
+  };
+  let specialized_row = |ctor, args: &[Pat]| {
+    let fields = fields(ctor, ty);
+    let mut new_row = args
+      .iter()
+      .cloned()
+      .zip(fields.iter().cloned())
+      .collect::<Vec<_>>();
+    new_row.extend(row[1..].iter().cloned());
+    (fields, new_row)
+  };
+
+  match *head {
+    Pat::Constructor(ctor, ref args) => {
+      let (fields, new_row) = specialized_row(ctor, args);
+      let witness = useful(&specialize(rows, ctor, &fields), &new_row)?;
+      Some(rebuild(ctor, witness))
+    }
+    Pat::Wildcard => {
+      let mut used = vec![];
+      for row in rows {
+        if let Pat::Constructor(ctor, _) = row[0].0 {
+          if !used.contains(&ctor) {
+            used.push(ctor);
+          }
+        }
+      }
+
+      let all = all_constructors(ty);
+      let missing = match all {
+        Some(ref all) => all.iter().find(|ctor| !used.contains(ctor)).cloned(),
+        None => None,
+      };
+      if let (Some(all), None) = (all, missing) {
+        // every constructor is used, so the wildcard is tried as each of them
+        return all.into_iter().find_map(|ctor| {
+          let wildcards = vec![Pat::Wildcard; fields(ctor, ty).len()];
+          let (fields, new_row) = specialized_row(ctor, &wildcards);
+          let witness = useful(&specialize(rows, ctor, &fields), &new_row)?;
+          Some(rebuild(ctor, witness))
+        });
+      }
+
+      // otherwise, only the rows which start with a wildcard matter
+      let default = rows
+        .iter()
+        .filter(|row| match row[0].0 {
+          Pat::Wildcard => true,
+          Pat::Constructor(..) => false,
+        })
+        .map(|row| row[1..].to_vec())
+        .collect::<Vec<_>>();
+      let rest = useful(&default, &row[1..])?;
+      let head = match missing {
+        Some(ctor) => {
+          let wildcards = vec![Pat::Wildcard; fields(ctor, ty).len()];
+          Pat::Constructor(ctor, wildcards)
+        }
+        None => Pat::Wildcard,
+      };
+      let mut witness = vec![head];
+      witness.extend(rest);
+      Some(witness)
+    }
+  }
+}
+
+pub(super) struct MatchCheck {
+  // a value which no arm matches, if there is one
+  pub missing: Option<String>,
+  // the indices of the arms which can never be reached
+  pub unreachable: Vec<usize>,
+}
+
+pub(super) fn check_match(ty: &Type, patterns: &[&Pattern]) -> MatchCheck {
+  let mut rows: Vec<Row> = vec![];
+  let mut unreachable = vec![];
+  for (idx, pattern) in patterns.iter().enumerate() {
+    let row = vec![(Pat::from_pattern(pattern), ty)];
+    if useful(&rows, &row).is_none() {
+      unreachable.push(idx);
+    }
+    rows.push(row);
+  }
+
+  let missing = useful(&rows, &[(Pat::Wildcard, ty)])
+    .map(|witness| witness[0].describe(ty));
+  MatchCheck {
+    missing,
+    unreachable,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::interner::Interned;
+  use crate::module::types::Variant;
+  use crate::module::{self, Pattern, PatternKind};
+  use crate::parser::{self, Span};
+
+  fn pattern<'cx>(
+    kind: PatternKind<'cx>,
+    ty: Interned<'cx, Type<'cx>>,
+  ) -> Pattern<'cx> {
+    let span = Span { start: 0, end: 0 };
+    Pattern { kind, ty, span }
+  }
+
+  fn variant<'cx>(
+    ctxt: &'cx parser::Context,
+    name: &str,
+    payload: Option<Interned<'cx, Type<'cx>>>,
+  ) -> Variant<'cx> {
+    Variant {
+      name: ctxt.get_ident(name),
+      payload: payload.map(Interned::as_ref),
+    }
+  }
+
+  fn check(ty: &Type, patterns: &[Pattern]) -> MatchCheck {
+    check_match(ty, &patterns.iter().collect::<Vec<_>>())
+  }
+
+  #[test]
+  fn nested_variants() {
+    let parse_ctxt = parser::Context::new();
+    let ctxt = module::Context::new(&parse_ctxt);
+    let bool_ty = ctxt.get_type(parse_ctxt.get_ident("Bool")).unwrap();
+    // `enum { None; Some: Bool; }`, and `enum { A: Option; B; }`
+    let option = ctxt.enum_type(&[
+      variant(&parse_ctxt, "None", None),
+      variant(&parse_ctxt, "Some", Some(bool_ty)),
+    ]);
+    let outer = ctxt.enum_type(&[
+      variant(&parse_ctxt, "A", Some(option)),
+      variant(&parse_ctxt, "B", None),
+    ]);
+
+    let some = |b| {
+      let b = pattern(PatternKind::Bool(b), bool_ty);
+      pattern(PatternKind::Variant(1, Some(Box::new(b))), option)
+    };
+    let none = || pattern(PatternKind::Variant(0, None), option);
+    let a = |payload| {
+      pattern(PatternKind::Variant(0, Some(Box::new(payload))), outer)
+    };
+    let b = || pattern(PatternKind::Variant(1, None), outer);
+
+    let result = check(&outer, &[a(some(true)), a(none()), b()]);
+    assert_eq!(result.missing.as_deref(), Some("A(Some(false))"));
+    assert!(result.unreachable.is_empty());
+
+    let arms = [a(some(true)), a(none()), a(some(false)), b()];
+    let result = check(&outer, &arms);
+    assert_eq!(result.missing, None);
+    assert!(result.unreachable.is_empty());
+
+    let wildcard = pattern(PatternKind::Wildcard, option);
+    let result = check(&outer, &[a(wildcard), a(some(true)), b()]);
+    assert_eq!(result.missing, None);
+    assert_eq!(result.unreachable, vec![1]);
+
+    let result = check(&outer, &[b()]);
+    assert_eq!(result.missing.as_deref(), Some("A(_)"));
+  }
+
+  #[test]
+  fn bools() {
+    let parse_ctxt = parser::Context::new();
+    let ctxt = module::Context::new(&parse_ctxt);
+    let ty = ctxt.get_type(parse_ctxt.get_ident("Bool")).unwrap();
+    let b = |b| pattern(PatternKind::Bool(b), ty);
+    let wildcard = || pattern(PatternKind::Wildcard, ty);
+
+    let result = check(&ty, &[b(true), b(false)]);
+    assert_eq!(result.missing, None);
+    assert!(result.unreachable.is_empty());
+
+    let result = check(&ty, &[b(true)]);
+    assert_eq!(result.missing.as_deref(), Some("false"));
+
+    let result = check(&ty, &[b(true), wildcard()]);
+    assert_eq!(result.missing, None);
+    assert!(result.unreachable.is_empty());
+
+    let result = check(&ty, &[b(true), b(false), wildcard()]);
+    assert_eq!(result.unreachable, vec![2]);
+
+    let result = check(&ty, &[wildcard(), b(true)]);
+    assert_eq!(result.unreachable, vec![1]);
+  }
+
+  // integers and characters are only covered by a wildcard, or a binding
+  #[test]
+  fn integers_and_chars() {
+    let parse_ctxt = parser::Context::new();
+    let ctxt = module::Context::new(&parse_ctxt);
+    let int_ty = ctxt.get_type(parse_ctxt.get_ident("Int32")).unwrap();
+    let char_ty = ctxt.get_type(parse_ctxt.get_ident("Char")).unwrap();
+    let int = |i| pattern(PatternKind::Integer(i), int_ty);
+    let ch = |c| pattern(PatternKind::Char(c), char_ty);
+
+    let result = check(&int_ty, &[int(0), int(1)]);
+    assert_eq!(result.missing.as_deref(), Some("_"));
+
+    let binding = pattern(PatternKind::Binding(0), int_ty);
+    let result = check(&int_ty, &[int(0), int(0), binding]);
+    assert_eq!(result.missing, None);
+    assert_eq!(result.unreachable, vec![1]);
+
+    let result = check(&char_ty, &[ch('a'), ch('b')]);
+    assert_eq!(result.missing.as_deref(), Some("_"));
+
+    let wildcard = pattern(PatternKind::Wildcard, char_ty);
+    let result = check(&char_ty, &[wildcard, ch('a')]);
+    assert_eq!(result.missing, None);
+    assert_eq!(result.unreachable, vec![1]);
+  }
+}
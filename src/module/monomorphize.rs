@@ -0,0 +1,256 @@
+/*
+  monomorphization, which turns a generic function into an instance for
+  each list of type arguments it's called with
+
+  an instance is the checked body of the generic function, with the type
+  arguments substituted for its parameters wherever a type appears; its
+  name is mangled from the function's name and the type arguments, as in
+  `max[Int32]`, which can't clash with a name written in the source. A call
+  is only pointed at its instance once the function it's in is compiled,
+  since a call in a generic function doesn't know its type arguments until
+  that function is instantiated in turn. Each instance is made once, and
+  kept by its type arguments, so that every call with the same ones shares
+  it.
+
+  a closure in a generic function takes the function's type parameters, and
+  is instantiated with the same type arguments as the function it's in.
+
+  a call to a function of an interface, through a type parameter, becomes a
+  call to the implementation for the type argument; the bounds of the
+  parameter, which were checked where the generic function was called,
+  guarantee that there is one.
+
+  a generic function which calls itself with bigger type arguments, like
+  `T` calling `*const T`, would need an endless chain of instances; the
+  chain is cut off at `INSTANCE_DEPTH_LIMIT`.
+*/
+
+use std::rc::Rc;
+
+use super::types::Type;
+use super::{
+  Expression, ExpressionKind, FunctionDefinition, Items, Pattern, PatternKind,
+  Statement,
+};
+
+use crate::interner::Interned;
+use crate::string::NfcString;
+
+// how many instantiations there may be between a function and its instance
+const INSTANCE_DEPTH_LIMIT: usize = 64;
+
+/*
+  calls `on_expression` on an expression and every expression inside it,
+  outer expressions first, and `on_pattern` on every pattern inside it
+*/
+struct Walker<E, P> {
+  on_expression: E,
+  on_pattern: P,
+}
+
+impl<'cx, E, P> Walker<E, P>
+where
+  E: FnMut(&mut Expression<'cx>),
+  P: FnMut(&mut Pattern<'cx>),
+{
+  fn expression(&mut self, expr: &mut Expression<'cx>) {
+    (self.on_expression)(expr);
+    match expr.kind {
+      ExpressionKind::IntegerLiteral(_)
+      | ExpressionKind::FloatLiteral(_)
+      | ExpressionKind::CharLiteral(_)
+      | ExpressionKind::BoolLiteral(_)
+      | ExpressionKind::StringLiteral(..)
+      | ExpressionKind::Local(_)
+      | ExpressionKind::Global(_)
+      | ExpressionKind::Function(..)
+      | ExpressionKind::Continue(_)
+      | ExpressionKind::Error => (),
+      ExpressionKind::UnaryOp(_, ref mut operand)
+      | ExpressionKind::AddressOf(ref mut operand)
+      | ExpressionKind::ToClosure(ref mut operand)
+      | ExpressionKind::Deref(ref mut operand)
+      | ExpressionKind::ArrayRepeat(ref mut operand, _)
+      | ExpressionKind::Field(ref mut operand, _)
+      | ExpressionKind::Loop(_, ref mut operand) => self.expression(operand),
+      ExpressionKind::BinaryOp(_, ref mut lhs, ref mut rhs)
+      | ExpressionKind::Index(ref mut lhs, ref mut rhs)
+      | ExpressionKind::While(_, ref mut lhs, ref mut rhs)
+      | ExpressionKind::Assign(_, ref mut lhs, ref mut rhs) => {
+        self.expression(lhs);
+        self.expression(rhs);
+      }
+      ExpressionKind::Call(_, _, ref mut values)
+      | ExpressionKind::InterfaceCall(_, _, _, ref mut values)
+      | ExpressionKind::Closure(_, _, ref mut values)
+      | ExpressionKind::Array(ref mut values) => {
+        for value in values {
+          self.expression(value);
+        }
+      }
+      ExpressionKind::CallIndirect(ref mut callee, ref mut args) => {
+        self.expression(callee);
+        for arg in args {
+          self.expression(arg);
+        }
+      }
+      ExpressionKind::Struct(ref mut values) => {
+        for (_, value) in values {
+          self.expression(value);
+        }
+      }
+      ExpressionKind::Variant(_, ref mut value)
+      | ExpressionKind::Break(_, ref mut value)
+      | ExpressionKind::Return(ref mut value) => {
+        if let Some(value) = value {
+          self.expression(value);
+        }
+      }
+      ExpressionKind::Block(ref mut statements, ref mut tail) => {
+        for stmt in statements {
+          match *stmt {
+            Statement::Let(_, ref mut expr)
+            | Statement::Expression(ref mut expr)
+            | Statement::Defer(ref mut expr) => self.expression(expr),
+          }
+        }
+        if let Some(tail) = tail {
+          self.expression(tail);
+        }
+      }
+      ExpressionKind::If(ref mut cond, ref mut then, ref mut els) => {
+        self.expression(cond);
+        self.expression(then);
+        if let Some(els) = els {
+          self.expression(els);
+        }
+      }
+      ExpressionKind::Match(ref mut scrutinee, ref mut arms) => {
+        self.expression(scrutinee);
+        for arm in arms {
+          self.pattern(&mut arm.pattern);
+          self.expression(&mut arm.body);
+        }
+      }
+    }
+  }
+
+  fn pattern(&mut self, pattern: &mut Pattern<'cx>) {
+    (self.on_pattern)(pattern);
+    if let PatternKind::Variant(_, Some(ref mut payload)) = pattern.kind {
+      self.pattern(payload);
+    }
+  }
+}
+
+impl<'cx> Items<'cx> {
+  /*
+    the name of the instance of the generic function `name` for
+    `type_args`; `None` if the function failed to type check, or is being
+    checked
+  */
+  pub(super) fn instance(
+    &mut self,
+    name: Interned<'cx, NfcString>,
+    type_args: &[Interned<'cx, Type<'cx>>],
+  ) -> Option<Interned<'cx, NfcString>> {
+    let key = (name, type_args.to_vec());
+    if let Some(&instance) = self.instances.get(&key) {
+      return Some(instance);
+    }
+    let generic = self.definition(name)?;
+
+    let args = type_args
+      .iter()
+      .map(|arg| arg.to_string())
+      .collect::<Vec<_>>();
+    let mangled = format!("{}[{}]", name, args.join(", "));
+    let instance = self.context.parse_context.get_ident(&mangled);
+
+    let context = self.context;
+    let substitute = |ty| context.substitute(ty, type_args);
+    let mut def = FunctionDefinition::clone(&generic);
+    let declaration = &mut def.declaration;
+    declaration.name = instance;
+    declaration.type_params.clear();
+    for param in &mut declaration.params {
+      param.ty = substitute(param.ty);
+    }
+    declaration.ret_ty = substitute(declaration.ret_ty);
+    for local in &mut def.locals {
+      local.ty = substitute(local.ty);
+    }
+    let mut walker = Walker {
+      on_expression: |expr: &mut Expression<'cx>| {
+        expr.ty = substitute(expr.ty);
+        match expr.kind {
+          ExpressionKind::Call(_, ref mut type_args, _)
+          | ExpressionKind::Function(_, ref mut type_args)
+          | ExpressionKind::Closure(_, ref mut type_args, _) => {
+            for ty in type_args {
+              *ty = substitute(*ty);
+            }
+          }
+          ExpressionKind::InterfaceCall(interface, ty, name, ref mut args) => {
+            let ty = substitute(ty);
+            let name = context.impl_function_name(interface, ty, name);
+            let args = std::mem::take(args);
+            expr.kind = ExpressionKind::Call(name, vec![], args);
+          }
+          _ => (),
+        }
+      },
+      on_pattern: |pattern: &mut Pattern<'cx>| {
+        pattern.ty = substitute(pattern.ty)
+      },
+    };
+    walker.expression(&mut def.body);
+
+    self.instances.insert(key, instance);
+    self.functions.insert(instance, def.declaration.clone());
+    self.definitions.insert(instance, Some(Rc::new(def)));
+    self.pending_instances.push(instance);
+    Some(instance)
+  }
+
+  /*
+    points each call to a generic function in `def` at its instance, and
+    each use of one as a value, or of a closure in one
+  */
+  pub(super) fn monomorphize(&mut self, def: &mut FunctionDefinition<'cx>) {
+    let name = def.declaration.name;
+    let depth = self.instance_depths.get(&name).map_or(0, |&depth| depth);
+    let mut walker = Walker {
+      on_expression: |expr: &mut Expression<'cx>| {
+        let span = expr.span;
+        let (name, type_args) = match expr.kind {
+          ExpressionKind::Call(ref mut name, ref mut type_args, _)
+          | ExpressionKind::Function(ref mut name, ref mut type_args)
+          | ExpressionKind::Closure(ref mut name, ref mut type_args, _)
+            if !type_args.is_empty() =>
+          {
+            (name, type_args)
+          }
+          _ => return,
+        };
+        if depth == INSTANCE_DEPTH_LIMIT {
+          let msg = format!(
+            "instantiating {} recursed more than {} instances deep",
+            name, INSTANCE_DEPTH_LIMIT,
+          );
+          let msg = self.context.parse_context.render(span, msg);
+          self.errors.push(msg);
+          return;
+        }
+        let instance = self
+          .instance(*name, type_args)
+          .expect("ICE: generic function failed to type check");
+        self.instance_depths.entry(instance).or_insert(depth + 1);
+        *name = instance;
+        type_args.clear();
+      },
+      on_pattern: |_: &mut Pattern<'cx>| (),
+    };
+    walker.expression(&mut def.body);
+  }
+}
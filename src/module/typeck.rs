@@ -0,0 +1,2855 @@
+/*
+  type checking for function bodies, which lowers them to the typed IR
+
+  every expression gets a type as it's lowered from the parse tree; where
+  the type isn't known yet, as for `let x = 0;`, the expression gets a fresh
+  inference variable (`Type::Infer`), which is later bound by unifying it
+  with the types it's used as. Variables remember what kind of type they may
+  be bound to - an integer literal's variable only unifies with integer
+  types, for example. Once the whole body has been seen, the variables are
+  replaced by what they were bound to, and the literals which were never
+  constrained get their default types: `Int32`, `Float64`, and `Char`.
+
+  there are no implicit conversions between numeric types; the only ones are
+  from `*mut T` to `*const T`, from a `func` to a `closure`, and from `!` to
+  anything, which happen where a value goes somewhere of a known type:
+  arguments, annotated `let`s, and the result of a function. Where two
+  values meet, as in the branches of an `if`, the type is that of whichever
+  doesn't diverge.
+
+  the bodies of `const func`s, the initializers of globals, and the lengths
+  of arrays are checked in a const context, since they may be evaluated at
+  compile time; there, only `const func`s may be called, and `static`s may
+  not be used.
+
+  a generic function is checked once, with its type parameters as types
+  of their own, which only unify with themselves; nothing is known about
+  them, so a value of a parameter's type can only be moved around. A call
+  to a generic function gets a new variable for each type argument that
+  isn't written out, and a generic type named in an expression, as in
+  `Option::Some(1)`, is instantiated with new variables in the same way;
+  types are unified structurally, since the variables may be anywhere
+  inside them.
+
+  `T::f(...)` calls the function `f` of an interface `T` implements; for a
+  type parameter, those are the interfaces its bounds name, and the call is
+  resolved once the function is instantiated. The bounds are checked at
+  each call to a generic function, once its type arguments are known.
+
+  `value.name(...)` calls a method of the type of `value`, or of the type
+  it points to, and `Type.name(...)` an associated function of `Type`.
+  Without such a method, it calls the interface function `name`, as
+  `T::name(value, ...)` would. A receiver is referenced or dereferenced to
+  match what the method takes, and fields are reached through pointers in
+  the same way; neither can be done at compile time.
+
+  a function may be used as a value, which is a `func`, and so may a
+  closure, `func(x: T) -> R { ... }`, which doesn't use the locals around
+  it. A closure which does captures them, by copying them into its
+  environment when it's created, and is a `closure`; what it captured can't
+  be assigned to. A closure is checked along with the function it's in,
+  with locals of its own, and lifted out into a function of its own once
+  that function is finished. None of these can be used at compile time.
+
+  the environment of a closure is in the frame of the function which made
+  it, so a `closure` may only be passed down: no function, or closure, may
+  return a value which holds one, and since a `static` can't be initialized
+  with one, it can't be put in a global either. A closure expression has a
+  single environment, which is filled in again each time it's reached; so
+  in a loop, each of the closures it makes sees the latest captures.
+
+  a tuple is built like a struct, with its elements as fields, and `.0`
+  names its first element. `let (a, b) = value;` puts the tuple in a local
+  with no name, and binds each name to an element of that local.
+
+  the arms of a `match` are checked against each other once the types of
+  their patterns are known: together they must match every value, and an
+  arm which can't be reached, since the arms above it match everything it
+  would, gets a warning.
+
+  errors don't stop the checker; they're collected, and the expression gets
+  `Type::Error`, which unifies with anything, so that one mistake isn't
+  reported again by everything that uses its result.
+*/
+
+use std::mem;
+use std::rc::Rc;
+
+use super::exhaustiveness;
+use super::types::{FloatSize, IntSize, Mutability, StructField, Type};
+use super::{
+  Context, Expression, ExpressionKind, FunctionDeclaration, FunctionDefinition,
+  GlobalKind, Items, Local, LocalId, LoopId, MatchArm, Parameter, Pattern,
+  PatternKind, Statement,
+};
+
+use crate::interner::Interned;
+use crate::parser::{self, BinaryOp, Span, StringKind, UnaryOp};
+use crate::string::NfcString;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum VariableKind {
+  Integer,
+  Float,
+  Char,
+  // the element type of `[]`, which may be anything
+  General,
+}
+
+#[derive(Copy, Clone, Debug)]
+enum Variable<'cx> {
+  Unbound(VariableKind),
+  Bound(Interned<'cx, Type<'cx>>),
+}
+
+struct LoopScope<'cx> {
+  id: LoopId,
+  label: Option<Interned<'cx, NfcString>>,
+  // `while` loops can't `break` with a value, since they may end without one
+  is_while: bool,
+  // the type of the `break`s seen so far, which is the type of the loop
+  break_ty: Option<Interned<'cx, Type<'cx>>>,
+}
+
+// a local of a function around a closure, which the closure uses
+struct Capture<'cx> {
+  name: Interned<'cx, NfcString>,
+  // the index of the function in `TypeChecker::enclosing`, and the local
+  origin: (usize, LocalId),
+  // the local's value, in the function the closure is written in
+  value: Expression<'cx>,
+}
+
+struct ClosureScope<'cx> {
+  // the parameter which points to the environment, which holds the captures
+  env: LocalId,
+  // a variable, which is bound once every capture has been seen
+  env_ty: Interned<'cx, Type<'cx>>,
+  captures: Vec<Capture<'cx>>,
+}
+
+// the state of a function, while a closure inside it is being checked
+struct Frame<'cx> {
+  ret_ty: Option<Interned<'cx, Type<'cx>>>,
+  locals: Vec<Local<'cx>>,
+  scope: Vec<(Interned<'cx, NfcString>, LocalId)>,
+  loops: Vec<LoopScope<'cx>>,
+  loop_count: usize,
+  deferring: Option<usize>,
+  closure: Option<ClosureScope<'cx>>,
+}
+
+pub(super) struct TypeChecker<'cx, 'a> {
+  context: &'cx Context<'cx>,
+  // the other items of the module, which collect the diagnostics
+  items: &'a mut Items<'cx>,
+  is_const: bool,
+  // the return type of the function being checked; `None` outside of one
+  ret_ty: Option<Interned<'cx, Type<'cx>>>,
+  variables: Vec<Variable<'cx>>,
+  locals: Vec<Local<'cx>>,
+  // the locals which are currently visible; later ones shadow earlier ones
+  scope: Vec<(Interned<'cx, NfcString>, LocalId)>,
+  // the loops around the current expression, innermost last
+  loops: Vec<LoopScope<'cx>>,
+  loop_count: usize,
+  /*
+    the number of loops around the `defer` being checked, if there is one;
+    a deferred expression can't `break` to those loops, or `return`
+  */
+  deferring: Option<usize>,
+  // the function being checked, which its closures are named after
+  name: Option<Interned<'cx, NfcString>>,
+  // the closure being checked, and the functions around it, outermost first
+  closure: Option<ClosureScope<'cx>>,
+  enclosing: Vec<Frame<'cx>>,
+  // the closures lifted out of the function, which are finished with it
+  closures: Vec<FunctionDefinition<'cx>>,
+}
+
+impl VariableKind {
+  fn accepts(self, ty: &Type) -> bool {
+    matches!(
+      (self, ty),
+      (VariableKind::Integer, Type::SignedInt { .. })
+        | (VariableKind::Integer, Type::UnsignedInt { .. })
+        | (VariableKind::Float, Type::Float { .. })
+        | (VariableKind::Char, Type::Char)
+        | (VariableKind::Char, Type::CChar)
+        | (VariableKind::General, _)
+    )
+  }
+}
+
+impl<'cx, 'a> TypeChecker<'cx, 'a> {
+  // `is_const` is whether this is a const context
+  pub(super) fn new(items: &'a mut Items<'cx>, is_const: bool) -> Self {
+    TypeChecker {
+      context: items.context,
+      items,
+      is_const,
+      ret_ty: None,
+      variables: vec![],
+      locals: vec![],
+      scope: vec![],
+      loops: vec![],
+      loop_count: 0,
+      deferring: None,
+      name: None,
+      closure: None,
+      enclosing: vec![],
+      closures: vec![],
+    }
+  }
+
+  pub(super) fn function(
+    mut self,
+    declaration: FunctionDeclaration<'cx>,
+    body: &parser::Expression<'cx>,
+  ) -> FunctionDefinition<'cx> {
+    self.name = Some(declaration.name);
+    self.ret_ty = Some(declaration.ret_ty);
+    self.parameters(&declaration.params);
+    let mut body = self.body(declaration.name, declaration.ret_ty, body);
+
+    self.finish(&mut body, false);
+    let locals = self.finish_locals(&self.locals);
+
+    for mut closure in mem::take(&mut self.closures) {
+      self.finish(&mut closure.body, false);
+      closure.locals = self.finish_locals(&closure.locals);
+      for param in &mut closure.declaration.params {
+        param.ty = self.resolve(param.ty);
+      }
+      let name = closure.declaration.name;
+      let items = &mut *self.items;
+      items.functions.insert(name, closure.declaration.clone());
+      items.definitions.insert(name, Some(Rc::new(closure)));
+      items.closure_functions.push(name);
+    }
+
+    FunctionDefinition {
+      declaration,
+      locals,
+      body,
+    }
+  }
+
+  // adds the parameters of the function being checked to its locals
+  fn parameters(&mut self, params: &[Parameter<'cx>]) {
+    for param in params {
+      let id = self.locals.len();
+      self.locals.push(Local {
+        name: param.name,
+        ty: param.ty,
+        mutability: Mutability::Immutable,
+      });
+      if let Some(name) = param.name {
+        self.scope.push((name, id));
+      }
+    }
+  }
+
+  /*
+    the body of a function, or of a closure, which returns `ret_ty`
+
+    a body without a tail is `()`, unless it diverges, as is an `if`
+    without an `else`; if that isn't what the function returns, there's a
+    path which falls off the end
+  */
+  fn body(
+    &mut self,
+    name: impl std::fmt::Display,
+    ret_ty: Interned<'cx, Type<'cx>>,
+    body: &parser::Expression<'cx>,
+  ) -> Expression<'cx> {
+    let body = self.expression(body);
+    let ret_span = Self::result_span(&body);
+    if Self::holds_closure(&ret_ty) {
+      let msg = format!(
+        "{} can't return a value of type {}, since a closure can't outlive \
+         the function which made it",
+        name, ret_ty,
+      );
+      self.error(ret_span, msg);
+    }
+    let unit = self.context.unit_type();
+    let falls_off = match &body.kind {
+      ExpressionKind::Block(_, None) => true,
+      ExpressionKind::Block(_, Some(tail)) => {
+        matches!(tail.kind, ExpressionKind::If(_, _, None))
+      }
+      _ => false,
+    };
+    if falls_off && body.ty == unit && ret_ty != unit {
+      let msg = format!(
+        "not every path through {} returns a value of type {}",
+        name, ret_ty,
+      );
+      self.error(ret_span, msg);
+      body
+    } else {
+      self.coerce_value(ret_ty, body, ret_span)
+    }
+  }
+
+  fn finish_locals(&self, locals: &[Local<'cx>]) -> Vec<Local<'cx>> {
+    locals
+      .iter()
+      .map(|local| Local {
+        ty: self.resolve(local.ty),
+        ..*local
+      })
+      .collect()
+  }
+
+  // the initializer of a `const` or `static`, which has the type `ty`
+  pub(super) fn initializer(
+    mut self,
+    ty: Interned<'cx, Type<'cx>>,
+    init: &parser::Expression<'cx>,
+  ) -> Expression<'cx> {
+    let mut init = self.expression(init);
+    self.coerce(ty, init.ty, Self::result_span(&init));
+    self.finish(&mut init, false);
+    init
+  }
+
+  // whether a value of type `ty` holds a `closure`, rather than points to one
+  fn holds_closure(ty: &Type<'cx>) -> bool {
+    match *ty {
+      Type::Closure { .. } => true,
+      Type::Array { element, .. } => Self::holds_closure(element),
+      Type::Struct { fields } => {
+        fields.iter().any(|field| Self::holds_closure(field.ty))
+      }
+      Type::Tuple { elements } => {
+        elements.iter().any(|element| Self::holds_closure(element))
+      }
+      Type::Enum { variants } => variants
+        .iter()
+        .filter_map(|variant| variant.payload)
+        .any(Self::holds_closure),
+      _ => false,
+    }
+  }
+
+  // where to point when the type of `expr` is wrong
+  fn result_span(expr: &Expression<'cx>) -> Span {
+    match expr.kind {
+      ExpressionKind::Block(_, Some(ref tail)) => Self::result_span(tail),
+      // the closing brace
+      ExpressionKind::Block(_, None) => Span {
+        start: expr.span.end - 1,
+        end: expr.span.end,
+      },
+      _ => expr.span,
+    }
+  }
+
+  fn error(&mut self, span: Span, msg: impl std::fmt::Display) {
+    let msg = self.context.parse_context.render(span, msg);
+    self.items.errors.push(msg);
+  }
+
+  fn warning(&mut self, span: Span, msg: impl std::fmt::Display) {
+    let msg = self.context.parse_context.render_warning(span, msg);
+    self.items.warnings.push(msg);
+  }
+
+  fn error_type(&self) -> Interned<'cx, Type<'cx>> {
+    self.type_of(Type::Error)
+  }
+
+  fn type_of(&self, ty: Type<'cx>) -> Interned<'cx, Type<'cx>> {
+    self.context.add_type_definition(ty)
+  }
+
+  fn new_variable(&mut self, kind: VariableKind) -> Interned<'cx, Type<'cx>> {
+    let idx = self.variables.len() as u32;
+    self.variables.push(Variable::Unbound(kind));
+    self.type_of(Type::Infer(idx))
+  }
+
+  // follows bound variables, until reaching a type or an unbound variable
+  fn shallow_resolve(
+    &self,
+    ty: Interned<'cx, Type<'cx>>,
+  ) -> Interned<'cx, Type<'cx>> {
+    match *ty {
+      Type::Infer(idx) => match self.variables[idx as usize] {
+        Variable::Bound(bound) => self.shallow_resolve(bound),
+        Variable::Unbound(_) => ty,
+      },
+      _ => ty,
+    }
+  }
+
+  /*
+    `ty`, with unbound variables given their default types; a `General`
+    variable has no default, and is left as it is
+  */
+  fn resolve(&self, ty: Interned<'cx, Type<'cx>>) -> Interned<'cx, Type<'cx>> {
+    if !Self::has_variables(&ty) {
+      return ty;
+    }
+    self.context.map_type(ty, &mut |ty| {
+      let idx = match *ty {
+        Type::Infer(idx) => idx,
+        _ => return None,
+      };
+      let resolved = match self.variables[idx as usize] {
+        Variable::Bound(bound) => self.resolve(bound),
+        Variable::Unbound(VariableKind::Integer) => {
+          self.type_of(Type::SignedInt { size: IntSize::I32 })
+        }
+        Variable::Unbound(VariableKind::Float) => self.type_of(Type::Float {
+          size: FloatSize::F64,
+        }),
+        Variable::Unbound(VariableKind::Char) => self.type_of(Type::Char),
+        Variable::Unbound(VariableKind::General) => ty,
+      };
+      Some(resolved)
+    })
+  }
+
+  // `ty`, with the variables in it which are bound replaced
+  fn resolve_bound(
+    &self,
+    ty: Interned<'cx, Type<'cx>>,
+  ) -> Interned<'cx, Type<'cx>> {
+    self.context.map_type(ty, &mut |ty| match *ty {
+      Type::Infer(idx) => match self.variables[idx as usize] {
+        Variable::Bound(bound) => Some(self.resolve_bound(bound)),
+        Variable::Unbound(_) => None,
+      },
+      _ => None,
+    })
+  }
+
+  // for error messages, where the type may not be known yet
+  fn describe(&self, ty: Interned<'cx, Type<'cx>>) -> String {
+    let ty = self.shallow_resolve(ty);
+    match *ty {
+      Type::Infer(idx) => match self.variables[idx as usize] {
+        Variable::Unbound(VariableKind::Integer) => "{integer}".to_string(),
+        Variable::Unbound(VariableKind::Float) => "{float}".to_string(),
+        Variable::Unbound(VariableKind::Char) => "{char}".to_string(),
+        Variable::Unbound(VariableKind::General) => "_".to_string(),
+        Variable::Bound(_) => unreachable!(),
+      },
+      Type::Array { element, len } => {
+        format!("[{}]{}", len, self.describe(self.type_of(*element)))
+      }
+      Type::Tuple { elements } => {
+        let elements = elements
+          .iter()
+          .map(|&element| self.describe(self.type_of(*element)))
+          .collect::<Vec<_>>();
+        match elements.len() {
+          1 => format!("({},)", elements[0]),
+          _ => format!("({})", elements.join(", ")),
+        }
+      }
+      _ => self.resolve_bound(ty).to_string(),
+    }
+  }
+
+  fn mismatch(
+    &mut self,
+    expected: Interned<'cx, Type<'cx>>,
+    found: Interned<'cx, Type<'cx>>,
+    span: Span,
+  ) {
+    let msg = format!(
+      "mismatched types: expected {}, found {}",
+      self.describe(expected),
+      self.describe(found),
+    );
+    self.error(span, msg)
+  }
+
+  fn unify(
+    &mut self,
+    expected: Interned<'cx, Type<'cx>>,
+    found: Interned<'cx, Type<'cx>>,
+    span: Span,
+  ) {
+    if !self.try_unify(expected, found) {
+      self.mismatch(expected, found, span);
+    }
+  }
+
+  /*
+    binds variables so that `expected` and `found` are the same type, if
+    they can be; `false` if they can't, in which case some variables may
+    have been bound anyway
+  */
+  fn try_unify(
+    &mut self,
+    expected: Interned<'cx, Type<'cx>>,
+    found: Interned<'cx, Type<'cx>>,
+  ) -> bool {
+    let expected = self.shallow_resolve(expected);
+    let found = self.shallow_resolve(found);
+    if expected == found {
+      return true;
+    }
+    if *expected == Type::Error || *found == Type::Error {
+      let error = self.error_type();
+      for &ty in &[expected, found] {
+        if let Type::Infer(idx) = *ty {
+          self.variables[idx as usize] = Variable::Bound(error);
+        }
+      }
+      return true;
+    }
+
+    let kind_of = |ty: Interned<'cx, Type<'cx>>| match *ty {
+      Type::Infer(idx) => match self.variables[idx as usize] {
+        Variable::Unbound(kind) => Some((idx as usize, kind)),
+        Variable::Bound(_) => unreachable!(),
+      },
+      _ => None,
+    };
+
+    match (kind_of(expected), kind_of(found)) {
+      (Some((_, lhs_kind)), Some((rhs, rhs_kind)))
+        if lhs_kind == rhs_kind || rhs_kind == VariableKind::General =>
+      {
+        self.variables[rhs] = Variable::Bound(expected);
+        true
+      }
+      (Some((lhs, VariableKind::General)), Some(_)) => {
+        self.variables[lhs] = Variable::Bound(found);
+        true
+      }
+      (Some((var, kind)), None) if kind.accepts(&found) => {
+        self.variables[var] = Variable::Bound(found);
+        true
+      }
+      (None, Some((var, kind))) if kind.accepts(&expected) => {
+        self.variables[var] = Variable::Bound(expected);
+        true
+      }
+      (None, None) => self.unify_structure(&expected, &found),
+      _ => false,
+    }
+  }
+
+  // two types which aren't variables unify if their parts do
+  fn unify_structure(
+    &mut self,
+    expected: &Type<'cx>,
+    found: &Type<'cx>,
+  ) -> bool {
+    match (*expected, *found) {
+      (
+        Type::Pointer {
+          mutability: expected_mut,
+          pointee: expected_pointee,
+        },
+        Type::Pointer {
+          mutability: found_mut,
+          pointee: found_pointee,
+        },
+      ) => {
+        expected_mut == found_mut
+          && self.try_unify(
+            self.type_of(*expected_pointee),
+            self.type_of(*found_pointee),
+          )
+      }
+      (
+        Type::Array {
+          element: expected_element,
+          len: expected_len,
+        },
+        Type::Array {
+          element: found_element,
+          len: found_len,
+        },
+      ) => {
+        expected_len == found_len
+          && self.try_unify(
+            self.type_of(*expected_element),
+            self.type_of(*found_element),
+          )
+      }
+      (
+        Type::Struct {
+          fields: expected_fields,
+        },
+        Type::Struct {
+          fields: found_fields,
+        },
+      ) => {
+        expected_fields.len() == found_fields.len()
+          && expected_fields.iter().zip(found_fields).all(|(e, f)| {
+            e.name == f.name
+              && self.try_unify(self.type_of(*e.ty), self.type_of(*f.ty))
+          })
+      }
+      (
+        Type::Tuple {
+          elements: expected_elements,
+        },
+        Type::Tuple {
+          elements: found_elements,
+        },
+      ) => {
+        expected_elements.len() == found_elements.len()
+          && expected_elements
+            .iter()
+            .zip(found_elements)
+            .all(|(e, f)| self.try_unify(self.type_of(**e), self.type_of(**f)))
+      }
+      (
+        Type::Function {
+          params: expected_params,
+          ret: expected_ret,
+        },
+        Type::Function {
+          params: found_params,
+          ret: found_ret,
+        },
+      )
+      | (
+        Type::Closure {
+          params: expected_params,
+          ret: expected_ret,
+        },
+        Type::Closure {
+          params: found_params,
+          ret: found_ret,
+        },
+      ) => {
+        expected_params.len() == found_params.len()
+          && expected_params
+            .iter()
+            .zip(found_params)
+            .all(|(e, f)| self.try_unify(self.type_of(**e), self.type_of(**f)))
+          && self
+            .try_unify(self.type_of(*expected_ret), self.type_of(*found_ret))
+      }
+      (
+        Type::Enum {
+          variants: expected_variants,
+        },
+        Type::Enum {
+          variants: found_variants,
+        },
+      ) => {
+        expected_variants.len() == found_variants.len()
+          && expected_variants.iter().zip(found_variants).all(|(e, f)| {
+            e.name == f.name
+              && match (e.payload, f.payload) {
+                (Some(e), Some(f)) => {
+                  self.try_unify(self.type_of(*e), self.type_of(*f))
+                }
+                (None, None) => true,
+                _ => false,
+              }
+          })
+      }
+      _ => false,
+    }
+  }
+
+  // `unify`, except that `found` may be converted implicitly to `expected`
+  fn coerce(
+    &mut self,
+    expected: Interned<'cx, Type<'cx>>,
+    found: Interned<'cx, Type<'cx>>,
+    span: Span,
+  ) {
+    let expected = self.shallow_resolve(expected);
+    let found = self.shallow_resolve(found);
+    if *found == Type::Never {
+      return;
+    }
+    if let (
+      Type::Pointer {
+        mutability: expected_mut,
+        ..
+      },
+      Type::Pointer {
+        mutability: found_mut,
+        pointee: found_pointee,
+      },
+    ) = (*expected, *found)
+    {
+      // the pointees are unified as if the mutability were the same
+      let as_expected = self
+        .context
+        .pointer_type(expected_mut, self.type_of(*found_pointee));
+      if !self.try_unify(expected, as_expected) {
+        self.mismatch(expected, found, span);
+      } else if expected_mut == Mutability::Mutable
+        && found_mut == Mutability::Immutable
+      {
+        let msg = format!(
+          "mismatched types: expected {}, found {}; a pointer to const \
+           can't be used as a pointer to mut",
+          self.describe(expected),
+          self.describe(found),
+        );
+        self.error(span, msg);
+      }
+      return;
+    }
+    self.unify(expected, found, span)
+  }
+
+  /*
+    `expr`, as a value of the type `expected`; where a `closure` is
+    expected, a `func` with the same signature is made into one, and
+    otherwise this is `coerce`
+  */
+  fn coerce_value(
+    &mut self,
+    expected: Interned<'cx, Type<'cx>>,
+    expr: Expression<'cx>,
+    span: Span,
+  ) -> Expression<'cx> {
+    let found = self.shallow_resolve(expr.ty);
+    if let (Type::Closure { params, ret }, Type::Function { .. }) =
+      (*self.shallow_resolve(expected), *found)
+    {
+      let params = params
+        .iter()
+        .map(|&param| self.type_of(*param))
+        .collect::<Vec<_>>();
+      let as_function =
+        self
+          .context
+          .function_type(false, &params, self.type_of(*ret));
+      self.unify(as_function, found, span);
+      return Expression {
+        span: expr.span,
+        kind: ExpressionKind::ToClosure(Box::new(expr)),
+        ty: expected,
+      };
+    }
+    if let (Type::Function { .. }, Type::Closure { .. }) =
+      (*self.shallow_resolve(expected), *found)
+    {
+      let msg = format!(
+        "mismatched types: expected {}, found {}; a closure which captures \
+         locals can't be used as a `func`",
+        self.describe(expected),
+        self.describe(found),
+      );
+      self.error(span, msg);
+      return expr;
+    }
+    self.coerce(expected, expr.ty, span);
+    expr
+  }
+
+  // the type of two values which meet, like the branches of an `if`
+  fn join(
+    &mut self,
+    lhs: Interned<'cx, Type<'cx>>,
+    rhs: Interned<'cx, Type<'cx>>,
+    span: Span,
+  ) -> Interned<'cx, Type<'cx>> {
+    if *self.shallow_resolve(lhs) == Type::Never {
+      rhs
+    } else if *self.shallow_resolve(rhs) == Type::Never {
+      lhs
+    } else {
+      self.unify(lhs, rhs, span);
+      lhs
+    }
+  }
+
+  fn is_never(&self, ty: Interned<'cx, Type<'cx>>) -> bool {
+    *self.shallow_resolve(ty) == Type::Never
+  }
+
+  fn lookup(&self, name: Interned<'cx, NfcString>) -> Option<LocalId> {
+    let found = self.scope.iter().rev().find(|&&(n, _)| n == name);
+    found.map(|&(_, id)| id)
+  }
+
+  /*
+    the local `name` of a function around the closure being checked; it's
+    captured by that closure, and by each closure between the two
+  */
+  fn capture(
+    &mut self,
+    name: Interned<'cx, NfcString>,
+    span: Span,
+  ) -> Option<Expression<'cx>> {
+    let origin =
+      self
+        .enclosing
+        .iter()
+        .enumerate()
+        .rev()
+        .find_map(|(depth, frame)| {
+          let found = frame.scope.iter().rev().find(|&&(n, _)| n == name);
+          found.map(|&(_, id)| (depth, id))
+        })?;
+    let (depth, id) = origin;
+    let ty = self.enclosing[depth].locals[id].ty;
+    let mut value = Expression {
+      kind: ExpressionKind::Local(id),
+      ty,
+      span,
+    };
+    let context = self.context;
+    for level in depth + 1..=self.enclosing.len() {
+      let closure = match self.enclosing.get_mut(level) {
+        Some(frame) => frame.closure.as_mut(),
+        None => self.closure.as_mut(),
+      };
+      let closure = closure.expect("ICE: capture outside of a closure");
+      let captures = &mut closure.captures;
+      let idx = match captures.iter().position(|c| c.origin == origin) {
+        Some(idx) => idx,
+        None => {
+          captures.push(Capture {
+            name,
+            origin,
+            value,
+          });
+          captures.len() - 1
+        }
+      };
+      let env = Expression {
+        kind: ExpressionKind::Local(closure.env),
+        ty: context.pointer_type(Mutability::Immutable, closure.env_ty),
+        span,
+      };
+      let env = Expression {
+        kind: ExpressionKind::Deref(Box::new(env)),
+        ty: closure.env_ty,
+        span,
+      };
+      value = Expression {
+        kind: ExpressionKind::Field(Box::new(env), idx),
+        ty,
+        span,
+      };
+    }
+    Some(value)
+  }
+
+  // whether `expr` is the environment of the closure being checked
+  fn is_environment(&self, expr: &Expression<'cx>) -> bool {
+    let env = match self.closure {
+      Some(ref closure) => closure.env,
+      None => return false,
+    };
+    match expr.kind {
+      ExpressionKind::Deref(ref ptr) => match ptr.kind {
+        ExpressionKind::Local(id) => id == env,
+        _ => false,
+      },
+      _ => false,
+    }
+  }
+
+  // whether `name` is a local here, or of a function around this closure
+  fn is_local(&self, name: Interned<'cx, NfcString>) -> bool {
+    let scopes = self.enclosing.iter().map(|frame| &frame.scope);
+    let mut scopes = scopes.chain(Some(&self.scope));
+    scopes.any(|scope| scope.iter().any(|&(n, _)| n == name))
+  }
+
+  fn expression(&mut self, expr: &parser::Expression<'cx>) -> Expression<'cx> {
+    use parser::ExpressionKind as PK;
+
+    let span = expr.span;
+    let (kind, ty) = match expr.kind {
+      PK::IntegerLiteral(i, suffix) => {
+        let ty = match suffix {
+          Some(suffix) => self.type_of(Type::from_suffix(suffix)),
+          None => self.new_variable(VariableKind::Integer),
+        };
+        (ExpressionKind::IntegerLiteral(i), ty)
+      }
+      PK::FloatLiteral(f) => {
+        let ty = self.new_variable(VariableKind::Float);
+        (ExpressionKind::FloatLiteral(f), ty)
+      }
+      PK::CharLiteral(ch) => {
+        let ty = self.new_variable(VariableKind::Char);
+        (ExpressionKind::CharLiteral(ch), ty)
+      }
+      PK::BoolLiteral(b) => {
+        (ExpressionKind::BoolLiteral(b), self.type_of(Type::Bool))
+      }
+      PK::StringLiteral(kind, s) => {
+        let ty = self.context.string_type(kind);
+        (ExpressionKind::StringLiteral(kind, s), ty)
+      }
+      PK::Name(name) => match self.lookup(name) {
+        Some(id) => (ExpressionKind::Local(id), self.locals[id].ty),
+        None => match self.capture(name, span) {
+          Some(capture) => (capture.kind, capture.ty),
+          None => self.global(name, span),
+        },
+      },
+      PK::Path(ty_name, name) => self.variant(ty_name, name, None, span),
+      PK::Instance(name, ref type_args) => {
+        let type_args = type_args
+          .iter()
+          .map(|ty| self.items.resolve_type(ty))
+          .collect::<Vec<_>>();
+        let decl = match self.items.item_name(name, span) {
+          Some(item) => self.function_declaration(item, name, span),
+          None => None,
+        };
+        match decl {
+          Some(decl) => self.function_value(&decl, Some(type_args), span),
+          None => (ExpressionKind::Error, self.error_type()),
+        }
+      }
+      PK::UnaryOp(op, ref operand) => {
+        let operand = self.expression(operand);
+        let ty = operand.ty;
+        (ExpressionKind::UnaryOp(op, Box::new(operand)), ty)
+      }
+      PK::BinaryOp(op, ref lhs, ref rhs) => {
+        let lhs = self.expression(lhs);
+        let rhs = self.expression(rhs);
+        let operand_ty = if op.is_logical() {
+          let bool_ty = self.type_of(Type::Bool);
+          self.coerce(bool_ty, lhs.ty, lhs.span);
+          self.coerce(bool_ty, rhs.ty, rhs.span);
+          bool_ty
+        } else {
+          self.join(lhs.ty, rhs.ty, rhs.span)
+        };
+        let ty = if op.is_comparison() || op.is_logical() {
+          self.type_of(Type::Bool)
+        } else {
+          operand_ty
+        };
+        (
+          ExpressionKind::BinaryOp(op, Box::new(lhs), Box::new(rhs)),
+          ty,
+        )
+      }
+      PK::Call(ref callee, ref args) => self.call(callee, args, span),
+      PK::Array(ref elements) => {
+        let elements = elements
+          .iter()
+          .map(|element| self.expression(element))
+          .collect::<Vec<_>>();
+        let mut element_ty = self.new_variable(VariableKind::General);
+        for element in &elements {
+          element_ty = self.join(element_ty, element.ty, element.span);
+        }
+        let len = elements.len() as u64;
+        let ty = self.context.array_type(element_ty, len);
+        (ExpressionKind::Array(elements), ty)
+      }
+      PK::Tuple(ref elements) => {
+        let elements = elements
+          .iter()
+          .map(|element| self.expression(element))
+          .collect::<Vec<_>>();
+        let element_tys = elements
+          .iter()
+          .map(|element| element.ty)
+          .collect::<Vec<_>>();
+        let ty = self.context.tuple_type(&element_tys);
+        let values = elements.into_iter().enumerate().collect();
+        (ExpressionKind::Struct(values), ty)
+      }
+      PK::ArrayRepeat(ref value, ref len) => {
+        let value = self.expression(value);
+        match self.items.array_length(len) {
+          Some(len) => {
+            let ty = self.context.array_type(value.ty, len);
+            (ExpressionKind::ArrayRepeat(Box::new(value), len), ty)
+          }
+          None => (ExpressionKind::Error, self.error_type()),
+        }
+      }
+      PK::Index(ref base, ref index) => {
+        let base = self.expression(base);
+        let index = self.expression(index);
+        let usize_ty = self.type_of(Type::UnsignedInt {
+          size: IntSize::ISize,
+        });
+        self.coerce(usize_ty, index.ty, index.span);
+        let ty = match *self.shallow_resolve(base.ty) {
+          Type::Array { element, .. } => self.type_of(*element),
+          Type::Error => self.error_type(),
+          _ => {
+            let msg = format!(
+              "cannot index into a value of type {}",
+              self.describe(base.ty)
+            );
+            self.error(base.span, msg);
+            self.error_type()
+          }
+        };
+        (ExpressionKind::Index(Box::new(base), Box::new(index)), ty)
+      }
+      PK::Field(ref base, name) => {
+        let mut base = self.expression(base);
+        let mut base_ty = self.shallow_resolve(base.ty);
+        // fields are reached through pointers
+        if let Type::Pointer { pointee, .. } = *base_ty {
+          base_ty = self.type_of(*pointee);
+          base = self.deref(base, base_ty);
+          base_ty = self.shallow_resolve(base_ty);
+        }
+        let field = match *base_ty {
+          Type::Struct { fields } => {
+            fields.iter().position(|field| field.name == name)
+          }
+          Type::Tuple { elements } => match name.as_str().parse::<usize>() {
+            Ok(idx) if idx < elements.len() => Some(idx),
+            _ => None,
+          },
+          // a `Str` is a pointer to its bytes, and the number of them
+          Type::Str => ["ptr", "len"].iter().position(|&f| f == name.as_str()),
+          _ => None,
+        };
+        match (field, *base_ty) {
+          (Some(idx), Type::Struct { fields }) => {
+            let ty = self.type_of(*fields[idx].ty);
+            (ExpressionKind::Field(Box::new(base), idx), ty)
+          }
+          (Some(idx), Type::Tuple { elements }) => {
+            let ty = self.type_of(*elements[idx]);
+            (ExpressionKind::Field(Box::new(base), idx), ty)
+          }
+          (Some(idx), Type::Str) => {
+            let ty = if idx == 0 {
+              self.context.string_type(StringKind::Bytes)
+            } else {
+              self.type_of(Type::UnsignedInt {
+                size: IntSize::ISize,
+              })
+            };
+            (ExpressionKind::Field(Box::new(base), idx), ty)
+          }
+          (_, Type::Error) => (ExpressionKind::Error, self.error_type()),
+          _ => {
+            let msg =
+              format!("no field {} on type {}", name, self.describe(base_ty));
+            self.error(span, msg);
+            (ExpressionKind::Error, self.error_type())
+          }
+        }
+      }
+      PK::Closure(ref params, ref ret_ty, ref body) => {
+        self.closure(params, ret_ty, body, span)
+      }
+      PK::StructLiteral(name, ref inits) => {
+        self.struct_literal(name, inits, span)
+      }
+      PK::Block(ref statements, ref tail) => {
+        let scope_len = self.scope.len();
+        let mut lowered = vec![];
+        for stmt in statements {
+          self.statement(stmt, &mut lowered);
+        }
+        let statements = lowered;
+        let (tail, ty) = match *tail {
+          Some(ref tail) => {
+            let tail = self.expression(tail);
+            let ty = tail.ty;
+            (Some(Box::new(tail)), ty)
+          }
+          // a block which can't reach its end has no value
+          None => {
+            let diverges = statements.iter().any(|stmt| match *stmt {
+              Statement::Let(_, ref expr) | Statement::Expression(ref expr) => {
+                self.is_never(expr.ty)
+              }
+              // a deferred expression runs after the end, if that's reached
+              Statement::Defer(_) => false,
+            });
+            if diverges {
+              (None, self.type_of(Type::Never))
+            } else {
+              (None, self.context.unit_type())
+            }
+          }
+        };
+        self.scope.truncate(scope_len);
+        (ExpressionKind::Block(statements, tail), ty)
+      }
+      PK::If(ref cond, ref then, ref els) => {
+        let cond = self.expression(cond);
+        let bool_ty = self.type_of(Type::Bool);
+        self.coerce(bool_ty, cond.ty, cond.span);
+
+        // without an `else`, the `if` is `()`, and so must the block be
+        let then = self.expression(then);
+        let (els, ty) = match *els {
+          Some(ref els) => {
+            let els = self.expression(els);
+            let ty = self.join(then.ty, els.ty, Self::result_span(&els));
+            (Some(Box::new(els)), ty)
+          }
+          None => {
+            let unit = self.context.unit_type();
+            self.coerce(unit, then.ty, Self::result_span(&then));
+            (None, unit)
+          }
+        };
+        (ExpressionKind::If(Box::new(cond), Box::new(then), els), ty)
+      }
+      PK::While(label, ref cond, ref body) => {
+        let id = self.enter_loop(label, true);
+        let cond = self.expression(cond);
+        let bool_ty = self.type_of(Type::Bool);
+        self.coerce(bool_ty, cond.ty, cond.span);
+        let body = self.loop_body(body);
+        self.loops.pop();
+
+        let kind = ExpressionKind::While(id, Box::new(cond), Box::new(body));
+        (kind, self.context.unit_type())
+      }
+      PK::Loop(label, ref body) => {
+        let id = self.enter_loop(label, false);
+        let body = self.loop_body(body);
+        let scope = self.loops.pop().unwrap();
+
+        // without a `break`, a `loop` never ends
+        let ty = match scope.break_ty {
+          Some(ty) => ty,
+          None => self.type_of(Type::Never),
+        };
+        (ExpressionKind::Loop(id, Box::new(body)), ty)
+      }
+      PK::Break(label, ref value) => {
+        let value = value.as_ref().map(|value| self.expression(value));
+        match self.find_loop(label, span, "break") {
+          Some(idx) => {
+            let (value_ty, value_span) = match value {
+              Some(ref value) => (value.ty, value.span),
+              None => (self.context.unit_type(), span),
+            };
+            let scope = &self.loops[idx];
+            let id = scope.id;
+            match (scope.is_while, scope.break_ty) {
+              (true, _) if value.is_some() => self.error(
+                value_span,
+                "only `loop` can `break` with a value, not `while`",
+              ),
+              (true, _) => (),
+              (false, Some(break_ty)) => {
+                let ty = self.join(break_ty, value_ty, value_span);
+                self.loops[idx].break_ty = Some(ty);
+              }
+              (false, None) => self.loops[idx].break_ty = Some(value_ty),
+            }
+            let kind = ExpressionKind::Break(id, value.map(Box::new));
+            (kind, self.type_of(Type::Never))
+          }
+          None => (ExpressionKind::Error, self.error_type()),
+        }
+      }
+      PK::Continue(label) => match self.find_loop(label, span, "continue") {
+        Some(idx) => {
+          let kind = ExpressionKind::Continue(self.loops[idx].id);
+          (kind, self.type_of(Type::Never))
+        }
+        None => (ExpressionKind::Error, self.error_type()),
+      },
+      PK::Return(_) if self.ret_ty.is_none() => {
+        self.error(span, "`return` outside of a function");
+        (ExpressionKind::Error, self.error_type())
+      }
+      PK::Return(_) if self.deferring.is_some() => {
+        self.error(span, "can't `return` out of a `defer`");
+        (ExpressionKind::Error, self.error_type())
+      }
+      PK::Return(ref value) => {
+        let ret_ty = self.ret_ty.unwrap();
+        let value = match *value {
+          Some(ref value) => {
+            let value = self.expression(value);
+            let span = value.span;
+            Some(Box::new(self.coerce_value(ret_ty, value, span)))
+          }
+          None => {
+            let unit = self.context.unit_type();
+            self.coerce(ret_ty, unit, span);
+            None
+          }
+        };
+        (ExpressionKind::Return(value), self.type_of(Type::Never))
+      }
+      PK::Match(ref scrutinee, ref arms) => {
+        self.match_expression(scrutinee, arms)
+      }
+      PK::Assign(op, ref place, ref value) => {
+        let place = self.place(place);
+        let value = self.expression(value);
+        let span = value.span;
+        let value = self.coerce_value(place.ty, value, span);
+        let kind = ExpressionKind::Assign(op, Box::new(place), Box::new(value));
+        (kind, self.context.unit_type())
+      }
+    };
+
+    Expression { kind, ty, span }
+  }
+
+  /*
+    the left side of an assignment: a `let mut` local, or a `static mut`, or
+    an element or field of one
+  */
+  fn place(&mut self, place: &parser::Expression<'cx>) -> Expression<'cx> {
+    let expr = self.expression(place);
+    if self.check_place(&expr, "assign to") {
+      expr
+    } else {
+      self.error(expr.span, "invalid left-hand side of assignment");
+      Expression {
+        kind: ExpressionKind::Error,
+        ty: self.error_type(),
+        span: expr.span,
+      }
+    }
+  }
+
+  /*
+    reports it if the place `expr` can't be changed, where `action` is what
+    would change it, as in "assign to"; `false` if `expr` isn't a place at
+    all
+  */
+  fn check_place(&mut self, expr: &Expression<'cx>, action: &str) -> bool {
+    match expr.kind {
+      ExpressionKind::Local(id) => {
+        let local = self.locals[id];
+        if local.mutability == Mutability::Immutable {
+          let name = local.name.unwrap();
+          let msg = format!("cannot {} immutable local {}", action, name);
+          self.error(expr.span, msg);
+        }
+        true
+      }
+      ExpressionKind::Field(ref base, idx) if self.is_environment(base) => {
+        let name = self.closure.as_ref().unwrap().captures[idx].name;
+        let msg = format!(
+          "cannot {} {}, which the closure has a copy of",
+          action, name,
+        );
+        self.error(expr.span, msg);
+        true
+      }
+      ExpressionKind::Field(ref base, _)
+        if *self.shallow_resolve(base.ty) == Type::Str =>
+      {
+        let msg = format!("cannot {} a part of a Str", action);
+        self.error(expr.span, msg);
+        true
+      }
+      ExpressionKind::Index(ref base, _)
+      | ExpressionKind::Field(ref base, _) => self.check_place(base, action),
+      ExpressionKind::Deref(ref ptr) => {
+        if let Type::Pointer {
+          mutability: Mutability::Immutable,
+          ..
+        } = *self.shallow_resolve(ptr.ty)
+        {
+          let msg = format!("cannot {} a place behind a `*const`", action);
+          self.error(expr.span, msg);
+        }
+        true
+      }
+      ExpressionKind::Global(name) => {
+        let msg = match self.items.globals[&name].kind {
+          GlobalKind::Static(Mutability::Mutable) => None,
+          GlobalKind::Static(Mutability::Immutable) => {
+            Some(format!("cannot {} immutable static {}", action, name))
+          }
+          GlobalKind::Const => {
+            Some(format!("cannot {} constant {}", action, name))
+          }
+        };
+        if let Some(msg) = msg {
+          self.error(expr.span, msg);
+        }
+        true
+      }
+      ExpressionKind::Error => true,
+      _ => false,
+    }
+  }
+
+  // a `const` or `static`, or a function, as a value
+  fn global(
+    &mut self,
+    name: Interned<'cx, NfcString>,
+    span: Span,
+  ) -> (ExpressionKind<'cx>, Interned<'cx, Type<'cx>>) {
+    let item = match self.items.item_name(name, span) {
+      Some(item) => item,
+      None => return (ExpressionKind::Error, self.error_type()),
+    };
+    match self.items.global(item) {
+      Some(decl) => {
+        if let (true, GlobalKind::Static(_)) = (self.is_const, decl.kind) {
+          let msg = "the value of a `static` can't be used at compile time";
+          self.error(span, msg);
+        }
+        (ExpressionKind::Global(item), decl.ty)
+      }
+      None => {
+        if let Some(decl) = self.items.function(item) {
+          return self.function_value(&decl, None, span);
+        }
+        let msg = match self.items.cycle(item) {
+          Some(cycle) => format!("cyclic dependency: {}", cycle),
+          None => format!("unknown name: {}", name),
+        };
+        self.error(span, msg);
+        (ExpressionKind::Error, self.error_type())
+      }
+    }
+  }
+
+  /*
+    the type named `name`, written at `span` in an expression; the type
+    arguments of a generic type are inferred, so it's instantiated with new
+    variables
+  */
+  fn named_type(
+    &mut self,
+    name: Interned<'cx, NfcString>,
+    span: Span,
+  ) -> Interned<'cx, Type<'cx>> {
+    if let Some(ty) = self.items.type_param(name) {
+      return ty;
+    }
+    let name = match self.items.item_name(name, span) {
+      Some(name) => name,
+      None => return self.error_type(),
+    };
+    match self.items.generic_alias(name) {
+      Some((count, ty)) => {
+        let args = (0..count)
+          .map(|_| self.new_variable(VariableKind::General))
+          .collect::<Vec<_>>();
+        self.context.substitute(ty, &args)
+      }
+      None => self.items.resolve_name(name, span),
+    }
+  }
+
+  // `Name { field: value, ... }`, which must give every field a value
+  fn struct_literal(
+    &mut self,
+    name: Interned<'cx, NfcString>,
+    inits: &[parser::FieldInit<'cx>],
+    span: Span,
+  ) -> (ExpressionKind<'cx>, Interned<'cx, Type<'cx>>) {
+    let ty = self.named_type(name, span);
+    let fields = match *ty {
+      Type::Struct { fields } => fields,
+      Type::Error => {
+        for init in inits {
+          self.expression(&init.value);
+        }
+        return (ExpressionKind::Error, ty);
+      }
+      _ => {
+        self.error(span, format!("{} is not a struct type", name));
+        for init in inits {
+          self.expression(&init.value);
+        }
+        return (ExpressionKind::Error, self.error_type());
+      }
+    };
+
+    let mut values: Vec<(usize, Expression<'cx>)> = vec![];
+    for init in inits {
+      let value = self.expression(&init.value);
+      match fields.iter().position(|field| field.name == init.name) {
+        Some(idx) if values.iter().any(|&(i, _)| i == idx) => {
+          let msg =
+            format!("field {} is given a value more than once", init.name);
+          self.error(init.name_span, msg);
+        }
+        Some(idx) => {
+          let field_ty = self.type_of(*fields[idx].ty);
+          let span = value.span;
+          values.push((idx, self.coerce_value(field_ty, value, span)));
+        }
+        None => {
+          let msg = format!("no field {} on type {}", init.name, name);
+          self.error(init.name_span, msg);
+        }
+      }
+    }
+
+    let missing = (0..fields.len())
+      .filter(|&idx| values.iter().all(|&(i, _)| i != idx))
+      .map(|idx| fields[idx].name.as_str())
+      .collect::<Vec<_>>();
+    if !missing.is_empty() {
+      let msg = format!("missing fields in {}: {}", name, missing.join(", "));
+      self.error(span, msg);
+    }
+    (ExpressionKind::Struct(values), ty)
+  }
+
+  // the enum type named `ty_name`, and the index of its variant `name`
+  fn find_variant(
+    &mut self,
+    ty_name: Interned<'cx, NfcString>,
+    name: Interned<'cx, NfcString>,
+    span: Span,
+  ) -> Option<(Interned<'cx, Type<'cx>>, usize)> {
+    let ty = self.named_type(ty_name, span);
+    let variants = match *ty {
+      Type::Enum { variants } => variants,
+      Type::Error => return None,
+      _ => {
+        self.error(span, format!("{} is not an enum type", ty_name));
+        return None;
+      }
+    };
+    match variants.iter().position(|variant| variant.name == name) {
+      Some(idx) => Some((ty, idx)),
+      None => {
+        self.error(span, format!("no variant {} in {}", name, ty_name));
+        None
+      }
+    }
+  }
+
+  /*
+    `Type::Variant`, or `Type::Variant(value)`; `args` are the arguments, if
+    it's called
+  */
+  fn variant(
+    &mut self,
+    ty_name: Interned<'cx, NfcString>,
+    name: Interned<'cx, NfcString>,
+    args: Option<&[parser::Expression<'cx>]>,
+    span: Span,
+  ) -> (ExpressionKind<'cx>, Interned<'cx, Type<'cx>>) {
+    let mut args = args.map(|args| {
+      args
+        .iter()
+        .map(|arg| self.expression(arg))
+        .collect::<Vec<_>>()
+    });
+    let (ty, idx) = match self.find_variant(ty_name, name, span) {
+      Some(found) => found,
+      None => return (ExpressionKind::Error, self.error_type()),
+    };
+    let payload_ty = match *ty {
+      Type::Enum { variants } => variants[idx].payload,
+      _ => unreachable!(),
+    };
+
+    let msg = match (payload_ty, args.as_mut()) {
+      (None, None) => return (ExpressionKind::Variant(idx, None), ty),
+      (Some(payload_ty), Some(args)) if args.len() == 1 => {
+        let value = args.pop().unwrap();
+        let span = value.span;
+        let value = self.coerce_value(self.type_of(*payload_ty), value, span);
+        let kind = ExpressionKind::Variant(idx, Some(Box::new(value)));
+        return (kind, ty);
+      }
+      (Some(payload_ty), None) => format!(
+        "{}::{} holds a value of type {}, which must be given",
+        ty_name, name, payload_ty,
+      ),
+      (Some(_), Some(args)) => format!(
+        "{}::{} takes 1 argument, but {} were passed",
+        ty_name,
+        name,
+        args.len(),
+      ),
+      (None, Some(_)) => format!(
+        "{}::{} doesn't hold a value, and can't be called",
+        ty_name, name,
+      ),
+    };
+    self.error(span, msg);
+    (ExpressionKind::Error, self.error_type())
+  }
+
+  // the type of a `match` is that of its arms, which is `!` if it has none
+  fn match_expression(
+    &mut self,
+    scrutinee: &parser::Expression<'cx>,
+    arms: &[parser::MatchArm<'cx>],
+  ) -> (ExpressionKind<'cx>, Interned<'cx, Type<'cx>>) {
+    let scrutinee = self.expression(scrutinee);
+    let mut ty = self.type_of(Type::Never);
+    let arms = arms
+      .iter()
+      .map(|arm| {
+        let scope_len = self.scope.len();
+        let pattern = self.pattern(&arm.pattern, scrutinee.ty);
+        let body = self.expression(&arm.body);
+        self.scope.truncate(scope_len);
+        ty = self.join(ty, body.ty, Self::result_span(&body));
+        MatchArm { pattern, body }
+      })
+      .collect();
+    (ExpressionKind::Match(Box::new(scrutinee), arms), ty)
+  }
+
+  /*
+    a pattern which is matched against a value of type `ty`; the bindings it
+    makes are put in scope
+  */
+  fn pattern(
+    &mut self,
+    pattern: &parser::Pattern<'cx>,
+    ty: Interned<'cx, Type<'cx>>,
+  ) -> Pattern<'cx> {
+    use parser::PatternKind as PP;
+
+    let span = pattern.span;
+    let kind = match pattern.kind {
+      PP::Wildcard => PatternKind::Wildcard,
+      PP::Binding(name) => {
+        let id = self.locals.len();
+        self.locals.push(Local {
+          name: Some(name),
+          ty,
+          mutability: Mutability::Immutable,
+        });
+        self.scope.push((name, id));
+        PatternKind::Binding(id)
+      }
+      PP::Integer(value, suffix, negated) => {
+        let literal_ty = match suffix {
+          Some(suffix) => self.type_of(Type::from_suffix(suffix)),
+          None => self.new_variable(VariableKind::Integer),
+        };
+        let value = i128::from(value);
+        let value = if negated { -value } else { value };
+        self.literal_pattern(ty, literal_ty, span, PatternKind::Integer(value))
+      }
+      PP::Char(ch) => {
+        let literal_ty = self.new_variable(VariableKind::Char);
+        self.literal_pattern(ty, literal_ty, span, PatternKind::Char(ch))
+      }
+      PP::Bool(b) => {
+        let literal_ty = self.type_of(Type::Bool);
+        self.literal_pattern(ty, literal_ty, span, PatternKind::Bool(b))
+      }
+      PP::Variant(ty_name, name, ref payload) => {
+        let payload = payload.as_ref().map(|payload| &**payload);
+        self.variant_pattern(ty, ty_name, name, payload, span)
+      }
+    };
+    Pattern { kind, ty, span }
+  }
+
+  /*
+    a literal in a pattern has to be of the type it's matched against;
+    otherwise, the pattern is an error
+  */
+  fn literal_pattern(
+    &mut self,
+    ty: Interned<'cx, Type<'cx>>,
+    literal_ty: Interned<'cx, Type<'cx>>,
+    span: Span,
+    kind: PatternKind<'cx>,
+  ) -> PatternKind<'cx> {
+    self.unify(ty, literal_ty, span);
+    if self.shallow_resolve(ty) == self.shallow_resolve(literal_ty) {
+      kind
+    } else {
+      PatternKind::Error
+    }
+  }
+
+  fn variant_pattern(
+    &mut self,
+    ty: Interned<'cx, Type<'cx>>,
+    ty_name: Interned<'cx, NfcString>,
+    name: Interned<'cx, NfcString>,
+    payload: Option<&parser::Pattern<'cx>>,
+    span: Span,
+  ) -> PatternKind<'cx> {
+    let (enum_ty, idx) = match self.find_variant(ty_name, name, span) {
+      Some(found) => found,
+      None => return PatternKind::Error,
+    };
+    if !self.try_unify(ty, enum_ty) {
+      self.mismatch(ty, enum_ty, span);
+      return PatternKind::Error;
+    }
+    let payload_ty = match *enum_ty {
+      Type::Enum { variants } => variants[idx].payload,
+      _ => unreachable!(),
+    };
+
+    let msg = match (payload_ty, payload) {
+      (None, None) => return PatternKind::Variant(idx, None),
+      (Some(payload_ty), Some(payload)) => {
+        let payload = self.pattern(payload, self.type_of(*payload_ty));
+        return PatternKind::Variant(idx, Some(Box::new(payload)));
+      }
+      (Some(payload_ty), None) => format!(
+        "{}::{} holds a value of type {}, which must be matched, as in \
+         {}::{}(_)",
+        ty_name, name, payload_ty, ty_name, name,
+      ),
+      (None, Some(_)) => {
+        format!("{}::{} doesn't hold a value", ty_name, name)
+      }
+    };
+    self.error(span, msg);
+    PatternKind::Error
+  }
+
+  fn enter_loop(
+    &mut self,
+    label: Option<parser::Label<'cx>>,
+    is_while: bool,
+  ) -> LoopId {
+    let id = self.loop_count;
+    self.loop_count += 1;
+    self.loops.push(LoopScope {
+      id,
+      label: label.map(|label| label.name),
+      is_while,
+      break_ty: None,
+    });
+    id
+  }
+
+  // the body of a loop is a block, which can't have a value
+  fn loop_body(&mut self, body: &parser::Expression<'cx>) -> Expression<'cx> {
+    let body = self.expression(body);
+    let unit = self.context.unit_type();
+    self.coerce(unit, body.ty, Self::result_span(&body));
+    body
+  }
+
+  // the index into `self.loops` of the loop that a `break` or `continue` is for
+  fn find_loop(
+    &mut self,
+    label: Option<parser::Label<'cx>>,
+    span: Span,
+    keyword: &str,
+  ) -> Option<usize> {
+    let found = match label {
+      Some(label) => {
+        let found = self
+          .loops
+          .iter()
+          .rposition(|scope| scope.label == Some(label.name));
+        if found.is_none() {
+          let msg = format!("unknown label: '{}", label.name);
+          self.error(label.span, msg);
+        }
+        found
+      }
+      None => {
+        if self.loops.is_empty() {
+          self.error(span, format!("`{}` outside of a loop", keyword));
+        }
+        self.loops.len().checked_sub(1)
+      }
+    };
+    match (found, self.deferring) {
+      (Some(idx), Some(loops)) if idx < loops => {
+        self.error(span, format!("can't `{}` out of a `defer`", keyword));
+        None
+      }
+      _ => found,
+    }
+  }
+
+  fn call(
+    &mut self,
+    callee: &parser::Expression<'cx>,
+    args: &[parser::Expression<'cx>],
+    span: Span,
+  ) -> (ExpressionKind<'cx>, Interned<'cx, Type<'cx>>) {
+    let (name, type_args) = match callee.kind {
+      parser::ExpressionKind::Name(name) if !self.is_local(name) => {
+        (name, None)
+      }
+      parser::ExpressionKind::Instance(name, ref type_args) => {
+        let type_args = type_args
+          .iter()
+          .map(|ty| self.items.resolve_type(ty))
+          .collect::<Vec<_>>();
+        (name, Some(type_args))
+      }
+      parser::ExpressionKind::Path(ty_name, name) => {
+        return self.path_call(ty_name, name, callee.span, args, span);
+      }
+      parser::ExpressionKind::Field(ref base, name) => {
+        return self.method_call(base, name, callee.span, args, span);
+      }
+      _ => return self.call_indirect(callee, args, span),
+    };
+
+    let item = match self.items.item_name(name, callee.span) {
+      Some(item) => item,
+      None => {
+        self.arguments(None, &[], callee.span, args, span);
+        return (ExpressionKind::Error, self.error_type());
+      }
+    };
+    let decl = self.function_declaration(item, name, callee.span);
+    let type_args = match decl {
+      Some(ref decl) => self.type_arguments(decl, type_args, callee.span),
+      None => vec![],
+    };
+    let (args, ret_ty) =
+      self.arguments(decl.as_ref(), &type_args, callee.span, args, span);
+    (ExpressionKind::Call(item, type_args, args), ret_ty)
+  }
+
+  // a call to a value of a `func` or `closure` type
+  fn call_indirect(
+    &mut self,
+    callee: &parser::Expression<'cx>,
+    args: &[parser::Expression<'cx>],
+    span: Span,
+  ) -> (ExpressionKind<'cx>, Interned<'cx, Type<'cx>>) {
+    let callee = self.expression(callee);
+    let (params, ret_ty) = match *self.shallow_resolve(callee.ty) {
+      Type::Function { params, ret } | Type::Closure { params, ret } => {
+        (params, self.type_of(*ret))
+      }
+      Type::Error => {
+        self.arguments(None, &[], callee.span, args, span);
+        return (ExpressionKind::Error, self.error_type());
+      }
+      _ => {
+        let msg = format!(
+          "only functions may be called, and this is a {}",
+          self.describe(callee.ty),
+        );
+        self.error(callee.span, msg);
+        self.arguments(None, &[], callee.span, args, span);
+        return (ExpressionKind::Error, self.error_type());
+      }
+    };
+    if self.is_const {
+      let msg = "a function can't be called through a pointer at compile time";
+      self.error(callee.span, msg);
+    }
+    if params.len() != args.len() {
+      let msg = format!(
+        "a {} takes {} arguments, but {} were passed",
+        self.describe(callee.ty),
+        params.len(),
+        args.len(),
+      );
+      self.error(span, msg);
+    }
+
+    let args = args
+      .iter()
+      .enumerate()
+      .map(|(idx, arg)| {
+        let arg = self.expression(arg);
+        let span = arg.span;
+        match params.get(idx) {
+          Some(&param_ty) => {
+            self.coerce_value(self.type_of(*param_ty), arg, span)
+          }
+          None => arg,
+        }
+      })
+      .collect();
+    (ExpressionKind::CallIndirect(Box::new(callee), args), ret_ty)
+  }
+
+  // the function `decl`, as a `func`
+  fn function_value(
+    &mut self,
+    decl: &FunctionDeclaration<'cx>,
+    type_args: Option<Vec<Interned<'cx, Type<'cx>>>>,
+    span: Span,
+  ) -> (ExpressionKind<'cx>, Interned<'cx, Type<'cx>>) {
+    if self.is_const {
+      self.error(span, "a function can't be used as a value at compile time");
+    }
+    let type_args = self.type_arguments(decl, type_args, span);
+    let params = decl
+      .params
+      .iter()
+      .map(|param| self.context.substitute(param.ty, &type_args))
+      .collect::<Vec<_>>();
+    let ret_ty = self.context.substitute(decl.ret_ty, &type_args);
+    let ty = self.context.function_type(false, &params, ret_ty);
+    (ExpressionKind::Function(decl.name, type_args), ty)
+  }
+
+  /*
+    `func(params) -> ret_ty { body }`, which is checked along with the
+    function it's in, and lifted into a function of its own; the locals it
+    uses from around it are copied into its environment when it's created.
+    a closure which captures nothing is just that function, as a `func`
+  */
+  fn closure(
+    &mut self,
+    params: &[parser::Parameter<'cx>],
+    ret_ty: &parser::Type<'cx>,
+    body: &parser::Expression<'cx>,
+    span: Span,
+  ) -> (ExpressionKind<'cx>, Interned<'cx, Type<'cx>>) {
+    if self.is_const {
+      self.error(span, "closures can't be created at compile time");
+      return (ExpressionKind::Error, self.error_type());
+    }
+    let ret_ty = self.items.resolve_type(ret_ty);
+    let mut params = params
+      .iter()
+      .map(|param| Parameter {
+        name: param.name,
+        ty: self.items.resolve_type(&param.ty),
+      })
+      .collect::<Vec<_>>();
+    let param_tys = params.iter().map(|param| param.ty).collect::<Vec<_>>();
+
+    let env_ty = self.new_variable(VariableKind::General);
+    let env_ptr_ty = self.context.pointer_type(Mutability::Immutable, env_ty);
+    let closure = ClosureScope {
+      env: params.len(),
+      env_ty,
+      captures: vec![],
+    };
+    let frame = Frame {
+      ret_ty: self.ret_ty.replace(ret_ty),
+      locals: mem::take(&mut self.locals),
+      scope: mem::take(&mut self.scope),
+      loops: mem::take(&mut self.loops),
+      loop_count: mem::replace(&mut self.loop_count, 0),
+      deferring: self.deferring.take(),
+      closure: self.closure.replace(closure),
+    };
+    self.enclosing.push(frame);
+    self.parameters(&params);
+    self.locals.push(Local {
+      name: None,
+      ty: env_ptr_ty,
+      mutability: Mutability::Immutable,
+    });
+    let body = self.body("the closure", ret_ty, body);
+
+    let frame = self.enclosing.pop().unwrap();
+    self.ret_ty = frame.ret_ty;
+    let locals = mem::replace(&mut self.locals, frame.locals);
+    self.scope = frame.scope;
+    self.loops = frame.loops;
+    self.loop_count = frame.loop_count;
+    self.deferring = frame.deferring;
+    let closure = mem::replace(&mut self.closure, frame.closure).unwrap();
+
+    let fields = closure
+      .captures
+      .iter()
+      .map(|capture| StructField {
+        name: capture.name,
+        ty: Interned::as_ref(capture.value.ty),
+      })
+      .collect::<Vec<_>>();
+    let env = self.context.struct_type(&fields);
+    self.unify(env_ty, env, span);
+
+    let name = format!(
+      "{}::{{closure{}}}",
+      self.name.expect("ICE: closure outside of a function"),
+      self.closures.len(),
+    );
+    let name = self.context.parse_context.get_ident(&name);
+    let type_params = self.items.type_params.clone();
+    let type_args = type_params
+      .iter()
+      .map(|param| self.items.type_param(param.name).unwrap())
+      .collect::<Vec<_>>();
+    /*
+      a closure which captures nothing doesn't take its environment; its
+      local for it is never used
+    */
+    let (kind, is_closure) = if closure.captures.is_empty() {
+      (ExpressionKind::Function(name, type_args), false)
+    } else {
+      params.push(Parameter {
+        name: None,
+        ty: env_ptr_ty,
+      });
+      let captures = closure
+        .captures
+        .into_iter()
+        .map(|capture| capture.value)
+        .collect();
+      (ExpressionKind::Closure(name, type_args, captures), true)
+    };
+    self.closures.push(FunctionDefinition {
+      declaration: FunctionDeclaration {
+        name,
+        is_const: false,
+        type_params,
+        params,
+        ret_ty,
+      },
+      locals,
+      body,
+    });
+    let ty = self.context.function_type(is_closure, &param_tys, ret_ty);
+    (kind, ty)
+  }
+
+  /*
+    the declaration of the function `item`, written `name`, which is reported
+    if it's missing
+  */
+  fn function_declaration(
+    &mut self,
+    item: Interned<'cx, NfcString>,
+    name: Interned<'cx, NfcString>,
+    span: Span,
+  ) -> Option<FunctionDeclaration<'cx>> {
+    let decl = self.items.function(item);
+    if decl.is_none() {
+      let msg = match self.items.cycle(item) {
+        Some(cycle) => format!("cyclic dependency: {}", cycle),
+        None => format!("unknown function: {}", name),
+      };
+      self.error(span, msg);
+    }
+    decl
+  }
+
+  /*
+    `Type.name(args)`, a call to an associated function of `Type`, or
+    `value.name(args)`, to a method of the type of `value`, or of the type
+    it points to
+  */
+  fn method_call(
+    &mut self,
+    base: &parser::Expression<'cx>,
+    name: Interned<'cx, NfcString>,
+    callee_span: Span,
+    args: &[parser::Expression<'cx>],
+    span: Span,
+  ) -> (ExpressionKind<'cx>, Interned<'cx, Type<'cx>>) {
+    let ty_name = match base.kind {
+      parser::ExpressionKind::Name(name) if !self.is_local(name) => Some(name),
+      _ => None,
+    };
+    if let Some(ty_name) = ty_name {
+      let item = match self.items.item_name(ty_name, base.span) {
+        Some(item) => item,
+        None => {
+          self.arguments(None, &[], callee_span, args, span);
+          return (ExpressionKind::Error, self.error_type());
+        }
+      };
+      if self.items.global(item).is_none() && self.items.is_type_name(item) {
+        let ty = self.named_type(ty_name, base.span);
+        let decl = match self.items.method(ty, name) {
+          Some(method) => {
+            self.function_declaration(method.function, name, span)
+          }
+          None => {
+            let msg = format!("no associated function {} on {}", name, ty_name);
+            self.error(callee_span, msg);
+            None
+          }
+        };
+        let (args, ret_ty) =
+          self.arguments(decl.as_ref(), &[], callee_span, args, span);
+        return match decl {
+          Some(decl) => (ExpressionKind::Call(decl.name, vec![], args), ret_ty),
+          None => (ExpressionKind::Error, ret_ty),
+        };
+      }
+    }
+
+    let receiver = self.expression(base);
+    let receiver_ty = self.shallow_resolve(receiver.ty);
+    let owner = match *receiver_ty {
+      Type::Pointer { pointee, .. } => self.type_of(*pointee),
+      _ => receiver_ty,
+    };
+    if *owner != Type::Error && self.items.method(owner, name).is_none() {
+      let described = self.describe(owner);
+      let found = self.interface_function(owner, described, name, callee_span);
+      if let Some(found) = found {
+        return self.interface_method_call(
+          receiver,
+          owner,
+          found,
+          callee_span,
+          args,
+          span,
+        );
+      }
+    }
+    let method = match *owner {
+      Type::Error => None,
+      _ => {
+        let method = self.items.method(owner, name);
+        let msg = match method {
+          Some(method) if method.is_method => None,
+          Some(method) => Some(format!(
+            "{} is an associated function, and takes no receiver",
+            method.function,
+          )),
+          None => Some(format!(
+            "no method {} on type {}",
+            name,
+            self.describe(owner),
+          )),
+        };
+        if let Some(msg) = msg {
+          self.error(callee_span, msg);
+        }
+        method.filter(|method| method.is_method)
+      }
+    };
+    let mut decl = match method {
+      Some(method) => self.function_declaration(method.function, name, span),
+      None => None,
+    };
+    let decl = match decl.as_mut() {
+      Some(decl) => decl,
+      None => {
+        self.arguments(None, &[], callee_span, args, span);
+        return (ExpressionKind::Error, self.error_type());
+      }
+    };
+
+    let receiver_param = decl.params.remove(0);
+    let receiver = self.receiver(receiver, receiver_param.ty);
+    let (mut args, ret_ty) =
+      self.arguments(Some(decl), &[], callee_span, args, span);
+    args.insert(0, receiver);
+    (ExpressionKind::Call(decl.name, vec![], args), ret_ty)
+  }
+
+  /*
+    `receiver.name(args)`, where `name` is a function of an interface the
+    receiver's type implements, which takes the receiver as its first
+    argument; for a type parameter, which interface's impl is called is
+    only known once it's monomorphized
+  */
+  fn interface_method_call(
+    &mut self,
+    receiver: Expression<'cx>,
+    owner: Interned<'cx, Type<'cx>>,
+    (interface, decl): (Interned<'cx, NfcString>, FunctionDeclaration<'cx>),
+    callee_span: Span,
+    args: &[parser::Expression<'cx>],
+    span: Span,
+  ) -> (ExpressionKind<'cx>, Interned<'cx, Type<'cx>>) {
+    let name = decl.name;
+    let (callee, decl, type_args) = match *owner {
+      Type::Param { .. } => (name, Some(decl), vec![owner]),
+      _ => {
+        let callee = self.context.impl_function_name(interface, owner, name);
+        (callee, self.items.function(callee), vec![])
+      }
+    };
+    let mut decl = match decl {
+      Some(decl) if !decl.params.is_empty() => decl,
+      decl => {
+        if decl.is_some() {
+          let msg = format!(
+            "{}::{} takes no arguments, and can't be called as a method",
+            interface, name,
+          );
+          self.error(callee_span, msg);
+        }
+        self.arguments(None, &[], callee_span, args, span);
+        return (ExpressionKind::Error, self.error_type());
+      }
+    };
+
+    let receiver_param = decl.params.remove(0);
+    let receiver_ty = self.context.substitute(receiver_param.ty, &type_args);
+    let receiver = self.receiver(receiver, receiver_ty);
+    let (mut args, ret_ty) =
+      self.arguments(Some(&decl), &type_args, callee_span, args, span);
+    args.insert(0, receiver);
+    let kind = match *owner {
+      Type::Param { .. } => {
+        ExpressionKind::InterfaceCall(interface, owner, name, args)
+      }
+      _ => ExpressionKind::Call(callee, vec![], args),
+    };
+    (kind, ret_ty)
+  }
+
+  /*
+    the receiver of a method which takes `param_ty`; a value is passed by
+    pointer if the method takes a pointer, and a pointer is dereferenced if
+    it takes a value
+  */
+  fn receiver(
+    &mut self,
+    receiver: Expression<'cx>,
+    param_ty: Interned<'cx, Type<'cx>>,
+  ) -> Expression<'cx> {
+    let span = receiver.span;
+    let receiver_ty = self.shallow_resolve(receiver.ty);
+    match (*param_ty, *receiver_ty) {
+      (Type::Pointer { .. }, Type::Pointer { .. })
+      | (_, Type::Error)
+      | (Type::Pointer { .. }, Type::Never) => {
+        self.coerce(param_ty, receiver.ty, span);
+        receiver
+      }
+      (
+        Type::Pointer {
+          mutability,
+          pointee,
+        },
+        _,
+      ) => {
+        self.coerce(self.type_of(*pointee), receiver.ty, span);
+        if self.is_const {
+          let msg = "cannot take the address of a value at compile time";
+          self.error(span, msg);
+        }
+        if mutability == Mutability::Mutable {
+          self.check_place(&receiver, "mutably borrow");
+        }
+        Expression {
+          kind: ExpressionKind::AddressOf(Box::new(receiver)),
+          ty: param_ty,
+          span,
+        }
+      }
+      (_, Type::Pointer { pointee, .. }) => {
+        let deref = self.deref(receiver, self.type_of(*pointee));
+        self.coerce(param_ty, deref.ty, span);
+        deref
+      }
+      _ => {
+        self.coerce(param_ty, receiver.ty, span);
+        receiver
+      }
+    }
+  }
+
+  // the value `ptr` points to, which is a `pointee`
+  fn deref(
+    &mut self,
+    ptr: Expression<'cx>,
+    pointee: Interned<'cx, Type<'cx>>,
+  ) -> Expression<'cx> {
+    let span = ptr.span;
+    if self.is_const {
+      self.error(span, "cannot dereference a pointer at compile time");
+    }
+    Expression {
+      kind: ExpressionKind::Deref(Box::new(ptr)),
+      ty: pointee,
+      span,
+    }
+  }
+
+  /*
+    the arguments of a call to `decl`, with `type_args` for its type
+    parameters, and the type of the result; without a declaration, the
+    arguments are still checked on their own
+  */
+  fn arguments(
+    &mut self,
+    decl: Option<&FunctionDeclaration<'cx>>,
+    type_args: &[Interned<'cx, Type<'cx>>],
+    callee_span: Span,
+    args: &[parser::Expression<'cx>],
+    span: Span,
+  ) -> (Vec<Expression<'cx>>, Interned<'cx, Type<'cx>>) {
+    let (param_tys, ret_ty) = match decl {
+      Some(decl) => {
+        if self.is_const && !decl.is_const {
+          let msg = format!(
+            "{} is not a `const func`, and can't be called at compile time",
+            decl.name,
+          );
+          self.error(callee_span, msg);
+        }
+        if decl.params.len() != args.len() {
+          let msg = format!(
+            "{} takes {} arguments, but {} were passed",
+            decl.name,
+            decl.params.len(),
+            args.len(),
+          );
+          self.error(span, msg);
+        }
+        let param_tys = decl
+          .params
+          .iter()
+          .map(|param| self.context.substitute(param.ty, type_args))
+          .collect::<Vec<_>>();
+        let ret_ty = self.context.substitute(decl.ret_ty, type_args);
+        (param_tys, ret_ty)
+      }
+      None => (vec![], self.error_type()),
+    };
+
+    let args = args
+      .iter()
+      .enumerate()
+      .map(|(idx, arg)| {
+        let arg = self.expression(arg);
+        let span = arg.span;
+        match param_tys.get(idx) {
+          Some(&param_ty) => self.coerce_value(param_ty, arg, span),
+          None => arg,
+        }
+      })
+      .collect();
+    (args, ret_ty)
+  }
+
+  /*
+    `Type::name(args)`, which is a variant which holds a value, if `Type`
+    has one called `name`, and otherwise a function of an interface `Type`
+    implements
+  */
+  fn path_call(
+    &mut self,
+    ty_name: Interned<'cx, NfcString>,
+    name: Interned<'cx, NfcString>,
+    callee_span: Span,
+    args: &[parser::Expression<'cx>],
+    span: Span,
+  ) -> (ExpressionKind<'cx>, Interned<'cx, Type<'cx>>) {
+    let ty = self.named_type(ty_name, callee_span);
+    let (interface, decl) = match *ty {
+      Type::Enum { variants } if variants.iter().any(|v| v.name == name) => {
+        return self.variant(ty_name, name, Some(args), span);
+      }
+      Type::Error => {
+        self.arguments(None, &[], callee_span, args, span);
+        return (ExpressionKind::Error, ty);
+      }
+      _ => match self.interface_function(ty, ty_name, name, callee_span) {
+        Some(found) => found,
+        None if Self::is_enum(&ty) => {
+          return self.variant(ty_name, name, Some(args), span);
+        }
+        None => {
+          let msg = format!(
+            "{} doesn't implement an interface with a function {}",
+            ty_name, name,
+          );
+          self.error(callee_span, msg);
+          self.arguments(None, &[], callee_span, args, span);
+          return (ExpressionKind::Error, self.error_type());
+        }
+      },
+    };
+    if let Type::Param { .. } = *ty {
+      let (args, ret_ty) =
+        self.arguments(Some(&decl), &[ty], callee_span, args, span);
+      let kind = ExpressionKind::InterfaceCall(interface, ty, name, args);
+      return (kind, ret_ty);
+    }
+
+    let name = self.context.impl_function_name(interface, ty, name);
+    let decl = self.items.function(name);
+    let (args, ret_ty) =
+      self.arguments(decl.as_ref(), &[], callee_span, args, span);
+    (ExpressionKind::Call(name, vec![], args), ret_ty)
+  }
+
+  fn is_enum(ty: &Type) -> bool {
+    matches!(*ty, Type::Enum { .. })
+  }
+
+  /*
+    the interface with a function `name`, among those `ty` implements, or,
+    for a type parameter, those its bounds name; along with the declaration
+    of the function in the interface
+  */
+  fn interface_function(
+    &mut self,
+    ty: Interned<'cx, Type<'cx>>,
+    ty_name: impl std::fmt::Display,
+    name: Interned<'cx, NfcString>,
+    span: Span,
+  ) -> Option<(Interned<'cx, NfcString>, FunctionDeclaration<'cx>)> {
+    let interfaces = match *ty {
+      Type::Param { idx, .. } => {
+        self.items.type_params[idx as usize].bounds.clone()
+      }
+      _ => self.items.implemented_interfaces(ty),
+    };
+
+    let mut found = vec![];
+    for interface in interfaces {
+      let interface = self.items.interface(interface).unwrap();
+      if let Some(decl) = interface.functions.iter().find(|f| f.name == name) {
+        found.push((interface.name, decl.clone()));
+      }
+    }
+    if found.len() > 1 {
+      let msg = format!(
+        "{}::{} is ambiguous; it's a function of both {} and {}",
+        ty_name, name, found[0].0, found[1].0,
+      );
+      self.error(span, msg);
+    }
+    found.into_iter().next()
+  }
+
+  /*
+    reports the type arguments of a call to `name` which don't implement
+    the interfaces its type parameters require
+  */
+  fn check_bounds(
+    &mut self,
+    name: Interned<'cx, NfcString>,
+    type_args: &[Interned<'cx, Type<'cx>>],
+    span: Span,
+  ) {
+    let decl = match self.items.function(name) {
+      Some(decl) => decl,
+      None => return,
+    };
+    for (param, &ty) in decl.type_params.iter().zip(type_args) {
+      let implemented = match *ty {
+        Type::Param { idx, .. } => {
+          self.items.type_params[idx as usize].bounds.clone()
+        }
+        Type::Error => continue,
+        _ => self.items.implemented_interfaces(ty),
+      };
+      for &bound in &param.bounds {
+        if !implemented.contains(&bound) {
+          let msg = format!(
+            "{} doesn't implement {}, which {} requires of {}",
+            ty, bound, name, param.name,
+          );
+          self.error(span, msg);
+        }
+      }
+    }
+  }
+
+  /*
+    the type arguments of a call to `decl`: the ones written out, if they
+    were, or new variables, which are inferred from the arguments and from
+    how the result is used
+  */
+  fn type_arguments(
+    &mut self,
+    decl: &FunctionDeclaration<'cx>,
+    given: Option<Vec<Interned<'cx, Type<'cx>>>>,
+    span: Span,
+  ) -> Vec<Interned<'cx, Type<'cx>>> {
+    let count = decl.type_params.len();
+    match given {
+      Some(given) if given.len() == count => given,
+      Some(given) => {
+        let msg = format!(
+          "{} takes {} type arguments, but {} were given",
+          decl.name,
+          count,
+          given.len(),
+        );
+        self.error(span, msg);
+        vec![self.error_type(); count]
+      }
+      None => (0..count)
+        .map(|_| self.new_variable(VariableKind::General))
+        .collect(),
+    }
+  }
+
+  fn statement(
+    &mut self,
+    stmt: &parser::Statement<'cx>,
+    statements: &mut Vec<Statement<'cx>>,
+  ) {
+    match *stmt {
+      parser::Statement::Let {
+        ref binding,
+        ref ty,
+        ref init,
+      } => {
+        let mut init = self.expression(init);
+        let ty = match *ty {
+          Some(ref ty) => {
+            let ty = self.items.resolve_type(ty);
+            let span = init.span;
+            init = self.coerce_value(ty, init, span);
+            ty
+          }
+          None => init.ty,
+        };
+        self.bind(binding, init, ty, statements);
+      }
+      parser::Statement::Expression(ref expr) => {
+        statements.push(Statement::Expression(self.expression(expr)))
+      }
+      parser::Statement::Defer(ref expr) => {
+        let outer = self.deferring.replace(self.loops.len());
+        let expr = self.expression(expr);
+        self.deferring = outer;
+        statements.push(Statement::Defer(expr))
+      }
+    }
+  }
+
+  /*
+    binds `value` to the names in `binding`; a tuple which is taken apart is
+    put in a local of its own first, and its elements bound from there
+  */
+  fn bind(
+    &mut self,
+    binding: &parser::Binding<'cx>,
+    value: Expression<'cx>,
+    ty: Interned<'cx, Type<'cx>>,
+    statements: &mut Vec<Statement<'cx>>,
+  ) {
+    let (name, mutability) = match *binding {
+      parser::Binding::Name {
+        name,
+        mutable: true,
+      } => (Some(name), Mutability::Mutable),
+      parser::Binding::Name { name, .. } => (Some(name), Mutability::Immutable),
+      parser::Binding::Wildcard | parser::Binding::Tuple(_) => {
+        (None, Mutability::Immutable)
+      }
+    };
+    let id = self.locals.len();
+    self.locals.push(Local {
+      name,
+      ty,
+      mutability,
+    });
+    let span = value.span;
+    statements.push(Statement::Let(id, value));
+
+    match *binding {
+      parser::Binding::Name { name, .. } => self.scope.push((name, id)),
+      parser::Binding::Tuple(ref bindings) => {
+        let element_tys = self.tuple_elements(ty, bindings.len(), span);
+        for (idx, binding) in bindings.iter().enumerate() {
+          let tuple = Expression {
+            kind: ExpressionKind::Local(id),
+            ty,
+            span,
+          };
+          let element = Expression {
+            kind: ExpressionKind::Field(Box::new(tuple), idx),
+            ty: element_tys[idx],
+            span,
+          };
+          self.bind(binding, element, element_tys[idx], statements);
+        }
+      }
+      parser::Binding::Wildcard => (),
+    }
+  }
+
+  // the types of the elements of `ty`, which is taken apart into `len`
+  fn tuple_elements(
+    &mut self,
+    ty: Interned<'cx, Type<'cx>>,
+    len: usize,
+    span: Span,
+  ) -> Vec<Interned<'cx, Type<'cx>>> {
+    match *self.shallow_resolve(ty) {
+      Type::Tuple { elements } if elements.len() == len => elements
+        .iter()
+        .map(|&element| self.type_of(*element))
+        .collect(),
+      Type::Struct { fields } if fields.is_empty() && len == 0 => vec![],
+      Type::Infer(_) => {
+        let elements = (0..len)
+          .map(|_| self.new_variable(VariableKind::General))
+          .collect::<Vec<_>>();
+        let tuple = self.context.tuple_type(&elements);
+        if self.try_unify(tuple, ty) {
+          elements
+        } else {
+          self.mismatch(tuple, ty, span);
+          vec![self.error_type(); len]
+        }
+      }
+      Type::Never => vec![self.type_of(Type::Never); len],
+      Type::Error => vec![self.error_type(); len],
+      _ => {
+        let msg = format!(
+          "cannot take a value of type {} apart into {} elements",
+          self.describe(ty),
+          len,
+        );
+        self.error(span, msg);
+        vec![self.error_type(); len]
+      }
+    }
+  }
+
+  /*
+    replaces the variables in `expr` with what they were bound to, and
+    checks what couldn't be checked until the types were known
+
+    `negated` is for integer literals directly under a `-`, so that the most
+    negative value of a type can be written; `-128i8` fits, although `128i8`
+    doesn't
+  */
+  fn finish(&mut self, expr: &mut Expression<'cx>, negated: bool) {
+    expr.ty = self.resolve(expr.ty);
+    if Self::has_variables(&expr.ty) {
+      self.error(expr.span, "type annotations needed");
+    }
+
+    let ty = expr.ty;
+    let span = expr.span;
+    // the mistakes which led to an error type have already been reported
+    let is_error = *ty == Type::Error;
+    match expr.kind {
+      ExpressionKind::IntegerLiteral(_) if is_error => (),
+      ExpressionKind::IntegerLiteral(value) => {
+        self.check_integer_literal(value, negated, ty, span)
+      }
+      ExpressionKind::CharLiteral(ch) => self.check_char_literal(ch, ty, span),
+      ExpressionKind::FloatLiteral(value) => {
+        self.check_float_literal(value, negated, ty, span)
+      }
+      ExpressionKind::BoolLiteral(_)
+      | ExpressionKind::StringLiteral(..)
+      | ExpressionKind::Local(_)
+      | ExpressionKind::Global(_)
+      | ExpressionKind::Error => (),
+      ExpressionKind::UnaryOp(UnaryOp::Negate, ref mut operand) => {
+        self.finish(operand, true);
+        match *ty {
+          Type::SignedInt { .. }
+          | Type::Float { .. }
+          | Type::Never
+          | Type::Error => (),
+          _ => {
+            self.error(span, format!("cannot negate a value of type {}", ty))
+          }
+        }
+      }
+      ExpressionKind::UnaryOp(UnaryOp::Not, ref mut operand) => {
+        self.finish(operand, false);
+        match *ty {
+          Type::Bool | Type::Never | Type::Error => (),
+          _ => {
+            self.error(span, format!("cannot use ! on a value of type {}", ty))
+          }
+        }
+      }
+      ExpressionKind::BinaryOp(op, ref mut lhs, ref mut rhs) => {
+        self.finish(lhs, false);
+        self.finish(rhs, false);
+        let operand_ty = match *lhs.ty {
+          Type::Never => rhs.ty,
+          _ => lhs.ty,
+        };
+        // the operands of `&&` and `||` have already been unified with `Bool`
+        if *operand_ty != Type::Error
+          && *operand_ty != Type::Never
+          && !op.is_logical()
+          && !Self::operator_accepts(op, &operand_ty)
+        {
+          let msg =
+            format!("cannot use `{}` on values of type {}", op, operand_ty);
+          self.error(span, msg);
+        }
+      }
+      ExpressionKind::Call(name, ref mut type_args, ref mut args) => {
+        for arg in args {
+          self.finish(arg, false);
+        }
+        self.finish_type_arguments(name, type_args, ty, span);
+      }
+      ExpressionKind::Function(name, ref mut type_args) => {
+        self.finish_type_arguments(name, type_args, ty, span);
+      }
+      // the type arguments are those of the function the closure is in
+      ExpressionKind::Closure(_, _, ref mut captures) => {
+        for capture in captures {
+          self.finish(capture, false);
+        }
+      }
+      ExpressionKind::ToClosure(ref mut operand) => self.finish(operand, false),
+      ExpressionKind::CallIndirect(ref mut callee, ref mut args) => {
+        self.finish(callee, false);
+        for arg in args {
+          self.finish(arg, false);
+        }
+      }
+      ExpressionKind::InterfaceCall(_, _, _, ref mut args) => {
+        for arg in args {
+          self.finish(arg, false);
+        }
+      }
+      ExpressionKind::AddressOf(ref mut operand)
+      | ExpressionKind::Deref(ref mut operand) => self.finish(operand, false),
+      ExpressionKind::Array(ref mut elements) => {
+        for element in elements {
+          self.finish(element, false);
+        }
+      }
+      ExpressionKind::ArrayRepeat(ref mut value, _) => {
+        self.finish(value, false)
+      }
+      ExpressionKind::Index(ref mut base, ref mut index) => {
+        self.finish(base, false);
+        self.finish(index, false);
+      }
+      ExpressionKind::Field(ref mut base, _) => self.finish(base, false),
+      ExpressionKind::Struct(ref mut values) => {
+        for (_, value) in values {
+          self.finish(value, false);
+        }
+      }
+      ExpressionKind::Variant(_, ref mut value) => {
+        if let Some(value) = value {
+          self.finish(value, false);
+        }
+      }
+      ExpressionKind::Match(ref mut scrutinee, ref mut arms) => {
+        self.finish(scrutinee, false);
+        for arm in arms.iter_mut() {
+          self.finish_pattern(&mut arm.pattern);
+          self.finish(&mut arm.body, false);
+        }
+        self.check_match(scrutinee, arms);
+      }
+      ExpressionKind::Block(ref mut statements, ref mut tail) => {
+        for stmt in statements {
+          match *stmt {
+            Statement::Let(_, ref mut init) => self.finish(init, false),
+            Statement::Expression(ref mut expr)
+            | Statement::Defer(ref mut expr) => self.finish(expr, false),
+          }
+        }
+        if let Some(tail) = tail {
+          self.finish(tail, false);
+        }
+      }
+      ExpressionKind::If(ref mut cond, ref mut then, ref mut els) => {
+        self.finish(cond, false);
+        self.finish(then, false);
+        if let Some(els) = els {
+          self.finish(els, false);
+        }
+      }
+      ExpressionKind::While(_, ref mut cond, ref mut body) => {
+        self.finish(cond, false);
+        self.finish(body, false);
+      }
+      ExpressionKind::Loop(_, ref mut body) => self.finish(body, false),
+      ExpressionKind::Break(_, ref mut value) => {
+        if let Some(value) = value {
+          self.finish(value, false);
+        }
+      }
+      ExpressionKind::Continue(_) => (),
+      ExpressionKind::Return(ref mut value) => {
+        if let Some(value) = value {
+          self.finish(value, false);
+        }
+      }
+      ExpressionKind::Assign(op, ref mut place, ref mut value) => {
+        self.finish(place, false);
+        self.finish(value, false);
+        match op {
+          Some(op)
+            if *place.ty != Type::Error
+              && !Self::operator_accepts(op, &place.ty) =>
+          {
+            let msg =
+              format!("cannot use `{}` on values of type {}", op, place.ty);
+            self.error(span, msg);
+          }
+          _ => (),
+        }
+      }
+    }
+  }
+
+  // the type arguments of a use of the function `name`, whose type is `ty`
+  fn finish_type_arguments(
+    &mut self,
+    name: Interned<'cx, NfcString>,
+    type_args: &mut [Interned<'cx, Type<'cx>>],
+    ty: Interned<'cx, Type<'cx>>,
+    span: Span,
+  ) {
+    for type_arg in type_args.iter_mut() {
+      *type_arg = self.resolve(*type_arg);
+    }
+    // if the type has variables in it, that's already been reported
+    if type_args.iter().any(|ty| Self::has_variables(ty)) {
+      if !Self::has_variables(&ty) {
+        let msg =
+          format!("type annotations needed for the type arguments of {}", name);
+        self.error(span, msg);
+      }
+    } else {
+      self.check_bounds(name, type_args, span);
+    }
+  }
+
+  fn finish_pattern(&mut self, pattern: &mut Pattern<'cx>) {
+    pattern.ty = self.resolve(pattern.ty);
+    let ty = pattern.ty;
+    let span = pattern.span;
+    match pattern.kind {
+      PatternKind::Integer(value) => match *ty {
+        Type::UnsignedInt { .. } if value < 0 => {
+          self.error(span, format!("cannot negate a value of type {}", ty))
+        }
+        Type::SignedInt { .. } | Type::UnsignedInt { .. } => {
+          let magnitude = value.unsigned_abs() as u64;
+          self.check_integer_literal(magnitude, value < 0, ty, span)
+        }
+        _ => (),
+      },
+      PatternKind::Char(ch) => self.check_char_literal(ch, ty, span),
+      PatternKind::Variant(_, Some(ref mut payload)) => {
+        self.finish_pattern(payload)
+      }
+      PatternKind::Wildcard
+      | PatternKind::Binding(_)
+      | PatternKind::Bool(_)
+      | PatternKind::Variant(_, None)
+      | PatternKind::Error => (),
+    }
+  }
+
+  /*
+    a `match` has to cover every value of its scrutinee; this is skipped
+    where a pattern failed to check, since its mistake is already reported
+  */
+  fn check_match(
+    &mut self,
+    scrutinee: &Expression<'cx>,
+    arms: &[MatchArm<'cx>],
+  ) {
+    if *scrutinee.ty == Type::Error
+      || arms
+        .iter()
+        .any(|arm| Self::pattern_has_errors(&arm.pattern))
+    {
+      return;
+    }
+    let patterns = arms.iter().map(|arm| &arm.pattern).collect::<Vec<_>>();
+    let check = exhaustiveness::check_match(&scrutinee.ty, &patterns);
+    if let Some(missing) = check.missing {
+      let msg = format!("non-exhaustive match: {} is not covered", missing);
+      self.error(scrutinee.span, msg);
+    }
+    for idx in check.unreachable {
+      self.warning(arms[idx].pattern.span, "unreachable match arm");
+    }
+  }
+
+  fn pattern_has_errors(pattern: &Pattern) -> bool {
+    match pattern.kind {
+      PatternKind::Error => true,
+      PatternKind::Variant(_, Some(ref payload)) => {
+        Self::pattern_has_errors(payload)
+      }
+      _ => *pattern.ty == Type::Error,
+    }
+  }
+
+  fn check_char_literal(
+    &mut self,
+    ch: char,
+    ty: Interned<'cx, Type<'cx>>,
+    span: Span,
+  ) {
+    if *ty == Type::CChar && !ch.is_ascii() {
+      let msg = format!(
+        "character literal {:?} is not ASCII, and can't be a CChar",
+        ch,
+      );
+      self.error(span, msg);
+    }
+  }
+
+  // whether a type still has variables in it
+  fn has_variables(ty: &Type) -> bool {
+    ty.any(&|ty| matches!(*ty, Type::Infer(_)))
+  }
+
+  fn operator_accepts(op: BinaryOp, ty: &Type) -> bool {
+    match *ty {
+      Type::SignedInt { .. }
+      | Type::UnsignedInt { .. }
+      | Type::Float { .. } => true,
+      Type::Char | Type::CChar | Type::Pointer { .. } => op.is_comparison(),
+      Type::Bool => matches!(op, BinaryOp::Equal | BinaryOp::NotEqual),
+      _ => false,
+    }
+  }
+
+  fn check_integer_literal(
+    &mut self,
+    value: u64,
+    negated: bool,
+    ty: Interned<'cx, Type<'cx>>,
+    span: Span,
+  ) {
+    // the target isn't known yet, so `ISize` literals may be up to 64 bits
+    let bits = |size| match size {
+      IntSize::I8 => 8,
+      IntSize::I16 => 16,
+      IntSize::I32 => 32,
+      IntSize::I64 | IntSize::ISize => 64,
+    };
+    let max = match *ty {
+      Type::SignedInt { size } => {
+        let max = (1u64 << (bits(size) - 1)) - 1;
+        if negated {
+          max + 1
+        } else {
+          max
+        }
+      }
+      Type::UnsignedInt { size } => u64::MAX >> (64 - bits(size)),
+      _ => unreachable!(),
+    };
+    if value > max {
+      let sign = if negated { "-" } else { "" };
+      let msg =
+        format!("integer literal out of range for {}: {}{}", ty, sign, value);
+      self.error(span, msg);
+    }
+  }
+
+  /*
+    the lexer has already made sure the literal fits in a `Float64`; one
+    which rounds to infinity as a `Float32` doesn't fit in it
+  */
+  fn check_float_literal(
+    &mut self,
+    value: f64,
+    negated: bool,
+    ty: Interned<'cx, Type<'cx>>,
+    span: Span,
+  ) {
+    if let Type::Float {
+      size: FloatSize::F32,
+    } = *ty
+    {
+      if (value as f32).is_infinite() {
+        let sign = if negated { "-" } else { "" };
+        let msg =
+          format!("float literal out of range for {}: {}{:e}", ty, sign, value);
+        self.error(span, msg);
+      }
+    }
+  }
+}
@@ -1,4 +1,7 @@
-use crate::interner::Internable;
+use std::fmt;
+
+use crate::interner::{Internable, Interned};
+use crate::string::NfcString;
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub enum IntSize {
@@ -9,12 +12,31 @@ pub enum IntSize {
   ISize,
 }
 
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum FloatSize {
+  F32,
+  F64,
+}
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub enum Mutability {
   Immutable,
   Mutable,
 }
 
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct StructField<'tx> {
+  pub name: Interned<'tx, NfcString>,
+  pub ty: &'tx Type<'tx>,
+}
+
+// `payload` is the type of the value the variant holds, if it holds one
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Variant<'tx> {
+  pub name: Interned<'tx, NfcString>,
+  pub payload: Option<&'tx Type<'tx>>,
+}
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub enum Type<'tx> {
   SignedInt {
@@ -23,14 +45,92 @@ pub enum Type<'tx> {
   UnsignedInt {
     size: IntSize,
   },
+  Float {
+    size: FloatSize,
+  },
+  Bool,
+  /*
+    `Char` is a unicode scalar value, and what a character literal is;
+    `CChar` is a single byte of (UTF-8) text, with the layout of C's `char`.
+    `c"..."` strings point to `CChar`s, and a character literal may only be
+    used as a `CChar` if it's ASCII
+  */
+  Char,
+  CChar,
+  /*
+    a string literal: a pointer to UTF-8 bytes, and their length, which may
+    be read as the fields `ptr` and `len`
+  */
+  Str,
 
   Pointer {
     mutability: Mutability,
     pointee: &'tx Type<'tx>,
   },
+
+  Struct {
+    fields: &'tx [StructField<'tx>],
+  },
+
+  // `(A, B)`; `()` is the empty struct, so every tuple has an element
+  Tuple {
+    elements: &'tx [&'tx Type<'tx>],
+  },
+
+  Array {
+    element: &'tx Type<'tx>,
+    len: u64,
+  },
+
+  // a tagged union; variants are referred to by their index
+  Enum {
+    variants: &'tx [Variant<'tx>],
+  },
+
+  /*
+    `func(A, B) -> R` is a pointer to a function with the C calling
+    convention, which may be passed to, and called from, C code.
+    `closure(A, B) -> R` is a function along with the environment it's called
+    with; a closure which captures no locals is a `func`, and a `func` may be
+    used where a `closure` with the same signature is expected
+  */
+  Function {
+    params: &'tx [&'tx Type<'tx>],
+    ret: &'tx Type<'tx>,
+  },
+  Closure {
+    params: &'tx [&'tx Type<'tx>],
+    ret: &'tx Type<'tx>,
+  },
+
+  /*
+    the type of expressions which never finish, like `return`; they may be
+    used wherever a value of any type is expected
+  */
+  Never,
+
+  /*
+    a type parameter of the generic item it's in, by its index in that
+    item's parameters; generic items are checked with their parameters as
+    types of their own, which are replaced when the item is instantiated
+  */
+  Param {
+    idx: u32,
+    name: Interned<'tx, NfcString>,
+  },
+
+  // a type which hasn't been inferred yet; these never reach codegen
+  Infer(u32),
+
+  // the type of expressions which failed to type check
+  Error,
 }
 
-impl<'tx> Internable for Type<'tx> {
+/*
+  the interner hands out references to the element itself, and `BTreeSet`
+  moves its elements around on insertion, so types are interned boxed
+*/
+impl<'tx> Internable for Box<Type<'tx>> {
   type Borrowed = Type<'tx>;
   type External = Type<'tx>;
   type Comparable = Type<'tx>;
@@ -45,6 +145,196 @@ impl<'tx> Internable for Type<'tx> {
   }
   #[inline(always)]
   fn from_external(x: &Type<'tx>) -> Self {
-    *x
+    Box::new(*x)
+  }
+}
+
+impl<'tx> Internable for Vec<StructField<'tx>> {
+  type Borrowed = [StructField<'tx>];
+  type External = [StructField<'tx>];
+  type Comparable = [StructField<'tx>];
+
+  #[inline(always)]
+  fn external_to_cmp<'a>(x: &'a [StructField<'tx>]) -> &'a [StructField<'tx>] {
+    x
+  }
+  #[inline(always)]
+  fn as_borrowed(&self) -> &[StructField<'tx>] {
+    self
+  }
+  #[inline(always)]
+  fn from_external(x: &[StructField<'tx>]) -> Self {
+    x.to_vec()
+  }
+}
+
+impl<'tx> Internable for Vec<Variant<'tx>> {
+  type Borrowed = [Variant<'tx>];
+  type External = [Variant<'tx>];
+  type Comparable = [Variant<'tx>];
+
+  #[inline(always)]
+  fn external_to_cmp<'a>(x: &'a [Variant<'tx>]) -> &'a [Variant<'tx>] {
+    x
+  }
+  #[inline(always)]
+  fn as_borrowed(&self) -> &[Variant<'tx>] {
+    self
+  }
+  #[inline(always)]
+  fn from_external(x: &[Variant<'tx>]) -> Self {
+    x.to_vec()
+  }
+}
+
+impl<'tx> Internable for Vec<&'tx Type<'tx>> {
+  type Borrowed = [&'tx Type<'tx>];
+  type External = [&'tx Type<'tx>];
+  type Comparable = [&'tx Type<'tx>];
+
+  #[inline(always)]
+  fn external_to_cmp<'a>(x: &'a [&'tx Type<'tx>]) -> &'a [&'tx Type<'tx>] {
+    x
+  }
+  #[inline(always)]
+  fn as_borrowed(&self) -> &[&'tx Type<'tx>] {
+    self
+  }
+  #[inline(always)]
+  fn from_external(x: &[&'tx Type<'tx>]) -> Self {
+    x.to_vec()
+  }
+}
+
+impl<'tx> Type<'tx> {
+  pub fn is_aggregate(&self) -> bool {
+    matches!(
+      *self,
+      Type::Struct { .. }
+        | Type::Tuple { .. }
+        | Type::Array { .. }
+        | Type::Enum { .. }
+        | Type::Closure { .. }
+        | Type::Str
+    )
+  }
+
+  // the types of a struct's fields, or a tuple's elements, in order
+  pub fn members(&self) -> Option<Vec<&'tx Type<'tx>>> {
+    match *self {
+      Type::Struct { fields } => {
+        Some(fields.iter().map(|field| field.ty).collect())
+      }
+      Type::Tuple { elements } => Some(elements.to_vec()),
+      _ => None,
+    }
+  }
+
+  // whether `pred` holds for this type, or any of the types inside it
+  pub fn any(&self, pred: &impl Fn(&Type<'tx>) -> bool) -> bool {
+    if pred(self) {
+      return true;
+    }
+    match *self {
+      Type::Pointer { pointee, .. } => pointee.any(pred),
+      Type::Array { element, .. } => element.any(pred),
+      Type::Struct { fields } => fields.iter().any(|field| field.ty.any(pred)),
+      Type::Tuple { elements } => {
+        elements.iter().any(|element| element.any(pred))
+      }
+      Type::Enum { variants } => variants
+        .iter()
+        .filter_map(|variant| variant.payload)
+        .any(|payload| payload.any(pred)),
+      Type::Function { params, ret } | Type::Closure { params, ret } => {
+        params.iter().any(|param| param.any(pred)) || ret.any(pred)
+      }
+      _ => false,
+    }
+  }
+
+  pub fn has_params(&self) -> bool {
+    self.any(&|ty| matches!(*ty, Type::Param { .. }))
+  }
+}
+
+impl<'tx> fmt::Display for Type<'tx> {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let name = match *self {
+      Type::SignedInt { size: IntSize::I8 } => "Int8",
+      Type::SignedInt { size: IntSize::I16 } => "Int16",
+      Type::SignedInt { size: IntSize::I32 } => "Int32",
+      Type::SignedInt { size: IntSize::I64 } => "Int64",
+      Type::SignedInt { size: IntSize::ISize } => "ISize",
+      Type::UnsignedInt { size: IntSize::I8 } => "UInt8",
+      Type::UnsignedInt { size: IntSize::I16 } => "UInt16",
+      Type::UnsignedInt { size: IntSize::I32 } => "UInt32",
+      Type::UnsignedInt { size: IntSize::I64 } => "UInt64",
+      Type::UnsignedInt { size: IntSize::ISize } => "USize",
+      Type::Float { size: FloatSize::F32 } => "Float32",
+      Type::Float { size: FloatSize::F64 } => "Float64",
+      Type::Bool => "Bool",
+      Type::Char => "Char",
+      Type::CChar => "CChar",
+      Type::Str => "Str",
+      Type::Pointer {
+        mutability: Mutability::Immutable,
+        pointee,
+      } => return write!(f, "*const {}", pointee),
+      Type::Pointer {
+        mutability: Mutability::Mutable,
+        pointee,
+      } => return write!(f, "*mut {}", pointee),
+      Type::Struct { fields: [] } => "()",
+      Type::Struct { fields } => {
+        f.write_str("struct {")?;
+        for field in fields {
+          write!(f, " {}: {};", field.name, field.ty)?;
+        }
+        return f.write_str(" }");
+      }
+      Type::Tuple { elements } => {
+        f.write_str("(")?;
+        for (i, element) in elements.iter().enumerate() {
+          if i != 0 {
+            f.write_str(", ")?;
+          }
+          write!(f, "{}", element)?;
+        }
+        if elements.len() == 1 {
+          f.write_str(",")?;
+        }
+        return f.write_str(")");
+      }
+      Type::Array { element, len } => return write!(f, "[{}]{}", len, element),
+      Type::Enum { variants } => {
+        f.write_str("enum {")?;
+        for variant in variants {
+          match variant.payload {
+            Some(payload) => write!(f, " {}: {};", variant.name, payload)?,
+            None => write!(f, " {};", variant.name)?,
+          }
+        }
+        return f.write_str(" }");
+      }
+      Type::Function { params, ret } | Type::Closure { params, ret } => {
+        match *self {
+          Type::Function { .. } => f.write_str("func(")?,
+          _ => f.write_str("closure(")?,
+        }
+        for (i, param) in params.iter().enumerate() {
+          if i != 0 {
+            f.write_str(", ")?;
+          }
+          write!(f, "{}", param)?;
+        }
+        return write!(f, ") -> {}", ret);
+      }
+      Type::Param { name, .. } => return write!(f, "{}", name),
+      Type::Infer(_) => "_",
+      Type::Never => "!",
+      Type::Error => "{error}",
+    };
+    f.write_str(name)
   }
 }
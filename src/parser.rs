@@ -1,51 +1,437 @@
 mod lexer;
 
-use self::lexer::{Lexer, StringKind, Token};
+use self::lexer::{Lexer, Token};
+pub use self::lexer::{IntegerSuffix, SourceFile, Span, StringKind};
 
 use crate::interner::{Interned, Interner};
 use crate::string::{self, NfcString};
 
 #[derive(Debug)]
 pub enum Type<'cx> {
-  Named(Interned<'cx, NfcString>),
+  /*
+    `Name`, or `Name[T, U]` for a generic type, given its type arguments;
+    along with the span of the name
+  */
+  Named(Interned<'cx, NfcString>, Vec<Type<'cx>>, Span),
+  Pointer { mutable: bool, pointee: Box<Type<'cx>> },
+  // `[len]T`, where the length is a constant expression
+  Array(Box<Expression<'cx>>, Box<Type<'cx>>),
+  Struct(Vec<StructField<'cx>>),
+  Enum(Vec<EnumVariant<'cx>>),
+  // `(A, B)`; a tuple of one element is written `(A,)`
+  Tuple(Vec<Type<'cx>>),
+  // `func(A, B) -> R`, a pointer to a function with the C calling convention
+  Function(Vec<Type<'cx>>, Box<Type<'cx>>),
+  // `closure(A, B) -> R`, a function pointer along with its environment
+  Closure(Vec<Type<'cx>>, Box<Type<'cx>>),
+  Unit,
+  Never,
+}
+
+#[derive(Debug)]
+pub struct StructField<'cx> {
+  pub name: Interned<'cx, NfcString>,
+  pub name_span: Span,
+  pub ty: Type<'cx>,
+}
+
+// `Name;`, or `Name: T;` for a variant which holds a value
+#[derive(Debug)]
+pub struct EnumVariant<'cx> {
+  pub name: Interned<'cx, NfcString>,
+  pub name_span: Span,
+  pub payload: Option<Type<'cx>>,
 }
 
 pub struct Parser<'cx, 's> {
   lexer: Lexer<'s>,
   ctxt: &'cx Context,
-  peek: Option<Token<'cx>>,
+  // where the file starts, in the spans of the program
+  offset: usize,
+  peek: Option<(Token<'cx>, Span)>,
+  // the span of the last token taken with `next_token`
+  last_span: Span,
+  /*
+    whether `Name {` starts a struct literal; it doesn't in the condition of
+    an `if` or `while`, where the `{` is the start of the body
+  */
+  struct_literals: bool,
+}
+
+// `name` is `None` for parameters declared as `_`
+#[derive(Debug)]
+pub struct Parameter<'cx> {
+  pub name: Option<Interned<'cx, NfcString>>,
+  pub name_span: Span,
+  pub ty: Type<'cx>,
 }
 
 #[derive(Debug)]
 pub struct FunctionDecl<'cx> {
-  name: Interned<'cx, NfcString>,
-  ret_ty: Type<'cx>,
+  pub name: Interned<'cx, NfcString>,
+  pub name_span: Span,
+  // a `const func` may be called at compile time
+  pub is_const: bool,
+  // `func name[T, U](...)`; empty for functions which aren't generic
+  pub type_params: Vec<TypeParam<'cx>>,
+  /*
+    the type a method or an associated function belongs to: `func
+    Type.name(...)` declares an associated function, and `func (self: T)
+    name(...)` a method, where `T` is `Type`, `*const Type`, or `*mut Type`;
+    along with the span of `Type`
+  */
+  pub owner: Option<(Interned<'cx, NfcString>, Span)>,
+  // whether this is a method; the receiver is the first parameter
+  pub is_method: bool,
+  pub params: Vec<Parameter<'cx>>,
+  pub ret_ty: Type<'cx>,
+}
+
+// `T`, or `T: A + B`, where `A` and `B` are interfaces `T` must implement
+#[derive(Debug)]
+pub struct TypeParam<'cx> {
+  pub name: Interned<'cx, NfcString>,
+  pub name_span: Span,
+  // each with the span of its name
+  pub bounds: Vec<(Interned<'cx, NfcString>, Span)>,
 }
 
 #[derive(Debug)]
 pub struct Function<'cx> {
-  decl: FunctionDecl<'cx>,
-  body: Expression<'cx>,
+  pub decl: FunctionDecl<'cx>,
+  pub body: Expression<'cx>,
+}
+
+#[derive(Debug)]
+pub struct TypeAlias<'cx> {
+  pub name: Interned<'cx, NfcString>,
+  pub name_span: Span,
+  pub type_params: Vec<TypeParam<'cx>>,
+  pub ty: Type<'cx>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum GlobalKind {
+  Const,
+  Static { mutable: bool },
+}
+
+// `const NAME: T = init;`, `static NAME: T = init;`, or `static mut ...`
+#[derive(Debug)]
+pub struct Global<'cx> {
+  pub name: Interned<'cx, NfcString>,
+  pub name_span: Span,
+  pub kind: GlobalKind,
+  pub ty: Type<'cx>,
+  pub init: Expression<'cx>,
+}
+
+/*
+  `interface Name { func f(a: Self) -> T; ... }`, where `Self` is the type
+  implementing it
+*/
+#[derive(Debug)]
+pub struct Interface<'cx> {
+  pub name: Interned<'cx, NfcString>,
+  pub name_span: Span,
+  pub functions: Vec<FunctionDecl<'cx>>,
+}
+
+// `impl Name for T { func f(a: T) -> U { ... } ... }`
+#[derive(Debug)]
+pub struct Impl<'cx> {
+  pub interface: Interned<'cx, NfcString>,
+  pub interface_span: Span,
+  pub ty: Type<'cx>,
+  pub functions: Vec<Function<'cx>>,
+}
+
+/*
+  `import a.b;`, which names the module in the file `a/b.nwt`, from the
+  directory of the root module
+*/
+#[derive(Debug)]
+pub struct Import<'cx> {
+  pub path: Vec<Interned<'cx, NfcString>>,
+  pub span: Span,
+}
+
+// an item written with `pub` may be used by the modules which import it
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Visibility {
+  Public,
+  Private,
 }
 
 #[derive(Debug)]
 pub enum Item<'cx> {
+  Import(Import<'cx>),
   ExternFunction(FunctionDecl<'cx>),
   Function(Function<'cx>),
+  TypeAlias(TypeAlias<'cx>),
+  Global(Global<'cx>),
+  Interface(Interface<'cx>),
+  Impl(Impl<'cx>),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum UnaryOp {
+  Negate,
+  Not,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BinaryOp {
+  Add,
+  Sub,
+  Mul,
+  Div,
+  Rem,
+
+  Equal,
+  NotEqual,
+  Less,
+  LessEqual,
+  Greater,
+  GreaterEqual,
+
+  // these short-circuit, so they aren't quite like the others
+  And,
+  Or,
+}
+
+#[derive(Debug)]
+pub enum Statement<'cx> {
+  Let {
+    binding: Binding<'cx>,
+    ty: Option<Type<'cx>>,
+    init: Expression<'cx>,
+  },
+  Expression(Expression<'cx>),
+  // `defer expr;`, which runs `expr` when the block it's in is left
+  Defer(Expression<'cx>),
+}
+
+// what a `let` binds: a name, or the elements of a tuple, as in `(a, b)`
+#[derive(Debug)]
+pub enum Binding<'cx> {
+  // `_`
+  Wildcard,
+  Name {
+    name: Interned<'cx, NfcString>,
+    mutable: bool,
+  },
+  Tuple(Vec<Binding<'cx>>),
+}
+
+#[derive(Debug)]
+pub struct Expression<'cx> {
+  pub kind: ExpressionKind<'cx>,
+  pub span: Span,
 }
 
 #[derive(Debug)]
-pub enum Expression<'cx> {
-  IntegerLiteral(u64),
+pub enum ExpressionKind<'cx> {
+  IntegerLiteral(u64, Option<IntegerSuffix>),
+  FloatLiteral(f64),
+  CharLiteral(char),
+  BoolLiteral(bool),
   Name(Interned<'cx, NfcString>),
+  // `name::[T, U]`, a generic function given its type arguments
+  Instance(Interned<'cx, NfcString>, Vec<Type<'cx>>),
+  // `Type::Variant`, which names a variant of an enum
+  Path(Interned<'cx, NfcString>, Interned<'cx, NfcString>),
   StringLiteral(StringKind, &'cx str),
+  UnaryOp(UnaryOp, Box<Expression<'cx>>),
+  BinaryOp(BinaryOp, Box<Expression<'cx>>, Box<Expression<'cx>>),
+  Call(Box<Expression<'cx>>, Vec<Expression<'cx>>),
+  // `[a, b, c]`, and `[value; len]`, where the length is a constant
+  Array(Vec<Expression<'cx>>),
+  ArrayRepeat(Box<Expression<'cx>>, Box<Expression<'cx>>),
+  Index(Box<Expression<'cx>>, Box<Expression<'cx>>),
+  // the elements of a tuple are named by their index, as in `.0`
+  Field(Box<Expression<'cx>>, Interned<'cx, NfcString>),
+  // `(a, b)`, and `()`, the unit value
+  Tuple(Vec<Expression<'cx>>),
+  // `func(x: T) -> R { body }`, which may use the locals around it
+  Closure(Vec<Parameter<'cx>>, Box<Type<'cx>>, Box<Expression<'cx>>),
+  // `Name { field: value, ... }`, where `Name` is a struct type
+  StructLiteral(Interned<'cx, NfcString>, Vec<FieldInit<'cx>>),
+  // `{ statements; tail }`, where the tail is optional
+  Block(Vec<Statement<'cx>>, Option<Box<Expression<'cx>>>),
+  // `if cond { then } else { else }`; the `else` is a block, or another `if`
+  If(
+    Box<Expression<'cx>>,
+    Box<Expression<'cx>>,
+    Option<Box<Expression<'cx>>>,
+  ),
+  // loops may be labeled, as in `'outer: loop { ... }`
+  While(Option<Label<'cx>>, Box<Expression<'cx>>, Box<Expression<'cx>>),
+  Loop(Option<Label<'cx>>, Box<Expression<'cx>>),
+  Break(Option<Label<'cx>>, Option<Box<Expression<'cx>>>),
+  Continue(Option<Label<'cx>>),
+  Return(Option<Box<Expression<'cx>>>),
+  Match(Box<Expression<'cx>>, Vec<MatchArm<'cx>>),
+  // `place = value`, or `place op= value` for the arithmetic operators
+  Assign(
+    Option<BinaryOp>,
+    Box<Expression<'cx>>,
+    Box<Expression<'cx>>,
+  ),
+}
+
+// `name: value`, in a struct literal
+#[derive(Debug)]
+pub struct FieldInit<'cx> {
+  pub name: Interned<'cx, NfcString>,
+  pub name_span: Span,
+  pub value: Expression<'cx>,
+}
+
+// `pattern => body`
+#[derive(Debug)]
+pub struct MatchArm<'cx> {
+  pub pattern: Pattern<'cx>,
+  pub body: Expression<'cx>,
+}
+
+#[derive(Debug)]
+pub struct Pattern<'cx> {
+  pub kind: PatternKind<'cx>,
+  pub span: Span,
+}
+
+#[derive(Debug)]
+pub enum PatternKind<'cx> {
+  // `_`
+  Wildcard,
+  Binding(Interned<'cx, NfcString>),
+  // the `bool` is whether the literal is negated, as in `-1`
+  Integer(u64, Option<IntegerSuffix>, bool),
+  Char(char),
+  Bool(bool),
+  // `Type::Variant`, or `Type::Variant(pattern)` for the value it holds
+  Variant(
+    Interned<'cx, NfcString>,
+    Interned<'cx, NfcString>,
+    Option<Box<Pattern<'cx>>>,
+  ),
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Label<'cx> {
+  pub name: Interned<'cx, NfcString>,
+  pub span: Span,
+}
+
+impl<'cx> ExpressionKind<'cx> {
+  fn ends_with_block(&self) -> bool {
+    matches!(
+      *self,
+      ExpressionKind::Block(..)
+        | ExpressionKind::If(..)
+        | ExpressionKind::While(..)
+        | ExpressionKind::Loop(..)
+        | ExpressionKind::Match(..)
+    )
+  }
+}
+
+impl UnaryOp {
+  fn from_operator(op: &str) -> Option<Self> {
+    match op {
+      "-" => Some(UnaryOp::Negate),
+      "!" => Some(UnaryOp::Not),
+      _ => None,
+    }
+  }
+}
+
+impl BinaryOp {
+  fn from_operator(op: &str) -> Option<Self> {
+    let op = match op {
+      "+" => BinaryOp::Add,
+      "-" => BinaryOp::Sub,
+      "*" => BinaryOp::Mul,
+      "/" => BinaryOp::Div,
+      "%" => BinaryOp::Rem,
+      "==" => BinaryOp::Equal,
+      "!=" => BinaryOp::NotEqual,
+      "<" => BinaryOp::Less,
+      "<=" => BinaryOp::LessEqual,
+      ">" => BinaryOp::Greater,
+      ">=" => BinaryOp::GreaterEqual,
+      "&&" => BinaryOp::And,
+      "||" => BinaryOp::Or,
+      _ => return None,
+    };
+    Some(op)
+  }
+
+  // the operator of a compound assignment, like `+=`
+  fn from_assignment_operator(op: &str) -> Option<Self> {
+    match op.strip_suffix('=').and_then(BinaryOp::from_operator) {
+      Some(op) if op.precedence() >= 4 => Some(op),
+      _ => None,
+    }
+  }
+
+  // higher binds tighter
+  fn precedence(self) -> u32 {
+    match self {
+      BinaryOp::Mul | BinaryOp::Div | BinaryOp::Rem => 5,
+      BinaryOp::Add | BinaryOp::Sub => 4,
+      BinaryOp::And => 2,
+      BinaryOp::Or => 1,
+      _ => 3,
+    }
+  }
+
+  pub fn is_comparison(self) -> bool {
+    self.precedence() == 3
+  }
+
+  pub fn is_logical(self) -> bool {
+    matches!(self, BinaryOp::And | BinaryOp::Or)
+  }
+
+  // the operator as it's written, for diagnostics
+  pub fn as_str(self) -> &'static str {
+    match self {
+      BinaryOp::Add => "+",
+      BinaryOp::Sub => "-",
+      BinaryOp::Mul => "*",
+      BinaryOp::Div => "/",
+      BinaryOp::Rem => "%",
+      BinaryOp::Equal => "==",
+      BinaryOp::NotEqual => "!=",
+      BinaryOp::Less => "<",
+      BinaryOp::LessEqual => "<=",
+      BinaryOp::Greater => ">",
+      BinaryOp::GreaterEqual => ">=",
+      BinaryOp::And => "&&",
+      BinaryOp::Or => "||",
+    }
+  }
+}
+
+impl std::fmt::Display for BinaryOp {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    f.write_str(self.as_str())
+  }
 }
 
 use std::cell::UnsafeCell;
+use std::rc::Rc;
 
 pub struct Context {
   identifiers: Interner<string::NfcStringBuf>,
   string_literals: UnsafeCell<Vec<String>>,
+  /*
+    the files of the program, in the order they were read; each is kept in
+    an allocation of its own, which `add_file` hands out references into
+  */
+  files: UnsafeCell<Vec<Rc<SourceFile>>>,
 }
 
 impl Context {
@@ -53,9 +439,63 @@ impl Context {
     Self {
       identifiers: Interner::new(),
       string_literals: UnsafeCell::new(vec![]),
+      files: UnsafeCell::new(vec![]),
+    }
+  }
+
+  /*
+    each file starts one past the end of the one before, so that a span at
+    the very end of a file is still in it
+  */
+  pub fn add_file(&self, path: String, source: String) -> &SourceFile {
+    unsafe {
+      let files = &mut *self.files.get();
+      let start = match files.last() {
+        Some(last) => last.start + last.source.len() + 1,
+        None => 0,
+      };
+      files.push(Rc::new(SourceFile {
+        path,
+        start,
+        source,
+      }));
+      let raw = &*files[files.len() - 1] as *const SourceFile;
+      &*raw
     }
   }
 
+  // formats an error, pointing at `span` in whichever file it's in
+  pub fn render(&self, span: Span, msg: impl std::fmt::Display) -> String {
+    self.render_as("error", span, msg)
+  }
+
+  // like `render`, but for something which doesn't stop compilation
+  pub fn render_warning(
+    &self,
+    span: Span,
+    msg: impl std::fmt::Display,
+  ) -> String {
+    self.render_as("warning", span, msg)
+  }
+
+  fn render_as(
+    &self,
+    level: &str,
+    span: Span,
+    msg: impl std::fmt::Display,
+  ) -> String {
+    let files = unsafe { &*self.files.get() };
+    let file = match files.iter().find(|file| file.contains(span)) {
+      Some(file) => file,
+      None => panic!("ICE: span {:?} isn't in any file", span),
+    };
+    let span = Span {
+      start: span.start - file.start,
+      end: span.end - file.start,
+    };
+    file.render(level, span, msg)
+  }
+
   pub fn get_ident(&self, id: &str) -> Interned<string::NfcString> {
     self.identifiers.add_element(id)
   }
@@ -72,20 +512,43 @@ impl Context {
 }
 
 impl<'cx, 's> Parser<'cx, 's> {
-  pub fn new(buffer: &'s str, ctxt: &'cx Context) -> Self {
-    let lexer = Lexer::new(buffer);
+  pub fn new(file: &'s SourceFile, ctxt: &'cx Context) -> Self {
+    let lexer = Lexer::new(file);
     Parser {
       lexer,
       ctxt,
+      offset: file.start,
       peek: None,
+      last_span: Span {
+        start: file.start,
+        end: file.start,
+      },
+      struct_literals: true,
     }
   }
 
+  // the text of `span`, which is in the file being parsed
+  fn source_of(&self, span: Span) -> &'s str {
+    let source = &self.lexer.file().source;
+    &source[span.start - self.offset..span.end - self.offset]
+  }
+
+  fn lex_token(&mut self) -> (Token<'cx>, Span) {
+    let tok = self.lexer.next_token(self.ctxt);
+    let span = self.lexer.token_span();
+    let span = Span {
+      start: span.start + self.offset,
+      end: span.end + self.offset,
+    };
+    (tok, span)
+  }
+
   fn next_token(&mut self) -> Token<'cx> {
-    let ret = match self.peek.take() {
-      Some(tok) => tok,
-      None => self.lexer.next_token(&self.ctxt),
+    let (ret, span) = match self.peek.take() {
+      Some(peek) => peek,
+      None => self.lex_token(),
     };
+    self.last_span = span;
 
     //println!("{:?}", ret);
 
@@ -93,16 +556,29 @@ impl<'cx, 's> Parser<'cx, 's> {
   }
 
   fn peek_token(&mut self) -> Token<'cx> {
+    self.peek_token_and_span().0
+  }
+
+  fn peek_span(&mut self) -> Span {
+    self.peek_token_and_span().1
+  }
+
+  fn peek_token_and_span(&mut self) -> (Token<'cx>, Span) {
     match self.peek {
-      Some(tok) => tok,
+      Some(peek) => peek,
       None => {
-        let tok = self.lexer.next_token(&self.ctxt);
-        self.peek = Some(tok);
-        tok
+        let peek = self.lex_token();
+        self.peek = Some(peek);
+        peek
       }
     }
   }
 
+  // the span from `start` to the end of the last token
+  fn span_from(&self, start: Span) -> Span {
+    start.to(self.last_span)
+  }
+
   fn get_ident(&mut self) -> Interned<'cx, NfcString> {
     match self.next_token() {
       Token::Identifier(s) => s,
@@ -110,6 +586,16 @@ impl<'cx, 's> Parser<'cx, 's> {
     }
   }
 
+  // the index of an element of a tuple, written in decimal without a suffix
+  fn tuple_index(&self, digits: &str) -> Interned<'cx, NfcString> {
+    match digits.parse::<usize>() {
+      Ok(idx) if digits.bytes().all(|b| b.is_ascii_digit()) => {
+        self.ctxt.get_ident(&idx.to_string())
+      }
+      _ => panic!("Expected tuple index, found {}", digits),
+    }
+  }
+
   fn eat_token(&mut self, tok: Token) {
     let next_tok = self.next_token();
     if tok != next_tok {
@@ -117,50 +603,964 @@ impl<'cx, 's> Parser<'cx, 's> {
     }
   }
 
+  // runs `f` with struct literals allowed, or not, and then restores that
+  fn with_struct_literals<T>(
+    &mut self,
+    allowed: bool,
+    f: impl FnOnce(&mut Self) -> T,
+  ) -> T {
+    let old = std::mem::replace(&mut self.struct_literals, allowed);
+    let ret = f(self);
+    self.struct_literals = old;
+    ret
+  }
+
   fn parse_type(&mut self) -> Type<'cx> {
     match self.next_token() {
-      Token::Identifier(s) => Type::Named(s),
+      Token::KeywordFunc => {
+        let (params, ret) = self.parse_function_type();
+        Type::Function(params, ret)
+      }
+      Token::Identifier(s)
+        if s.as_str() == "closure" && self.peek_token() == Token::OpenParen =>
+      {
+        let (params, ret) = self.parse_function_type();
+        Type::Closure(params, ret)
+      }
+      Token::Identifier(s) => {
+        let span = self.last_span;
+        let args = match self.peek_token() {
+          Token::OpenBracket => {
+            self.next_token();
+            self.parse_list(Token::CloseBracket, Self::parse_type)
+          }
+          _ => vec![],
+        };
+        Type::Named(s, args, span)
+      }
+      Token::OpenBracket => {
+        let len = self.with_struct_literals(true, Self::parse_expression);
+        let () = self.eat_token(Token::CloseBracket);
+        let element = self.parse_type();
+        Type::Array(Box::new(len), Box::new(element))
+      }
+      Token::OpenParen => match self.parse_tuple(Self::parse_type) {
+        (elements, true) if elements.is_empty() => Type::Unit,
+        (elements, true) => Type::Tuple(elements),
+        (mut elements, false) => elements.pop().unwrap(),
+      },
+      Token::Operator(op) if op.as_str() == "!" => Type::Never,
+      // `*const T` or `*mut T`
+      Token::Operator(op) if op.as_str() == "*" => {
+        let mutable = match self.next_token() {
+          Token::KeywordConst => false,
+          Token::Identifier(s) if s.as_str() == "mut" => true,
+          tok => panic!("Expected `const` or `mut`, found {:?}", tok),
+        };
+        let pointee = Box::new(self.parse_type());
+        Type::Pointer { mutable, pointee }
+      }
+      Token::KeywordStruct => {
+        let () = self.eat_token(Token::OpenBrace);
+        let mut fields = vec![];
+        loop {
+          match self.peek_token() {
+            Token::CloseBrace => {
+              self.next_token();
+              break;
+            }
+            _ => {
+              let name_span = self.peek_span();
+              let name = self.get_ident();
+              let () = self.eat_token(Token::Colon);
+              let ty = self.parse_type();
+              let () = self.eat_token(Token::Semicolon);
+              fields.push(StructField {
+                name,
+                name_span,
+                ty,
+              });
+            }
+          }
+        }
+        Type::Struct(fields)
+      }
+      Token::KeywordEnum => {
+        let () = self.eat_token(Token::OpenBrace);
+        let mut variants = vec![];
+        loop {
+          match self.peek_token() {
+            Token::CloseBrace => {
+              self.next_token();
+              break;
+            }
+            _ => {
+              let name_span = self.peek_span();
+              let name = self.get_ident();
+              let payload = match self.next_token() {
+                Token::Colon => {
+                  let ty = self.parse_type();
+                  let () = self.eat_token(Token::Semicolon);
+                  Some(ty)
+                }
+                Token::Semicolon => None,
+                tok => panic!("Expected `:` or `;`, found {:?}", tok),
+              };
+              variants.push(EnumVariant {
+                name,
+                name_span,
+                payload,
+              });
+            }
+          }
+        }
+        Type::Enum(variants)
+      }
       tok => panic!("Expected type, found {:?}", tok),
     }
   }
 
-  fn parse_expression(&mut self) -> Expression<'cx> {
+  // `(A, B) -> R`, after `func` or `closure`
+  fn parse_function_type(&mut self) -> (Vec<Type<'cx>>, Box<Type<'cx>>) {
+    let () = self.eat_token(Token::OpenParen);
+    let params = self.parse_list(Token::CloseParen, Self::parse_type);
+    let () = self.eat_token(Token::Arrow);
+    (params, Box::new(self.parse_type()))
+  }
+
+  /*
+    parses a comma separated list, with an optional trailing comma, up to and
+    including `close`; the opening token should already have been eaten
+  */
+  fn parse_list<T>(
+    &mut self,
+    close: Token,
+    mut parse_element: impl FnMut(&mut Self) -> T,
+  ) -> Vec<T> {
+    let mut list = vec![];
+    loop {
+      if self.peek_token() == close {
+        self.next_token();
+        return list;
+      }
+      list.push(parse_element(self));
+      match self.next_token() {
+        Token::Comma => (),
+        tok if tok == close => return list,
+        tok => panic!("Expected `,` or {:?}, found {:?}", close, tok),
+      }
+    }
+  }
+
+  /*
+    note: the `(` should already have been eaten
+
+    the elements, and whether they're a tuple; `(x)` is just `x`, and a
+    tuple of one element is written `(x,)`
+  */
+  fn parse_tuple<T>(
+    &mut self,
+    mut parse_element: impl FnMut(&mut Self) -> T,
+  ) -> (Vec<T>, bool) {
+    if self.peek_token() == Token::CloseParen {
+      self.next_token();
+      return (vec![], true);
+    }
+    let first = parse_element(self);
+    match self.next_token() {
+      Token::CloseParen => (vec![first], false),
+      Token::Comma => {
+        let mut elements = vec![first];
+        elements.extend(self.parse_list(Token::CloseParen, parse_element));
+        (elements, true)
+      }
+      tok => panic!("Expected `,` or `)`, found {:?}", tok),
+    }
+  }
+
+  fn parse_binding(&mut self) -> Binding<'cx> {
+    match self.next_token() {
+      Token::KeywordUnderscore => Binding::Wildcard,
+      Token::Identifier(s) if s.as_str() == "mut" => Binding::Name {
+        name: self.get_ident(),
+        mutable: true,
+      },
+      Token::Identifier(name) => Binding::Name {
+        name,
+        mutable: false,
+      },
+      Token::OpenParen => match self.parse_tuple(Self::parse_binding) {
+        (elements, true) => Binding::Tuple(elements),
+        (mut elements, false) => elements.pop().unwrap(),
+      },
+      tok => panic!("Expected a name or `(`, found {:?}", tok),
+    }
+  }
+
+  /*
+    note: the `{` should already have been eaten
+
+    a statement is a `let`, a `defer`, or an expression followed by a `;`;
+    the expression before the `}`, if there's no `;` after it, is the tail
+  */
+  fn parse_block(&mut self) -> ExpressionKind<'cx> {
+    let mut statements = vec![];
+    loop {
+      match self.peek_token() {
+        Token::CloseBrace => {
+          self.next_token();
+          return ExpressionKind::Block(statements, None);
+        }
+        Token::KeywordLet => {
+          self.next_token();
+          let binding = self.parse_binding();
+          let ty = match self.peek_token() {
+            Token::Colon => {
+              self.next_token();
+              Some(self.parse_type())
+            }
+            _ => None,
+          };
+          let () = self.eat_token(Token::Equals);
+          let init = self.parse_expression();
+          let () = self.eat_token(Token::Semicolon);
+          statements.push(Statement::Let { binding, ty, init });
+        }
+        Token::KeywordDefer => {
+          self.next_token();
+          let expr = self.parse_expression();
+          match self.peek_token() {
+            Token::Semicolon => {
+              self.next_token();
+            }
+            _ if expr.kind.ends_with_block() => (),
+            tok => panic!("Expected `;`, found {:?}", tok),
+          }
+          statements.push(Statement::Defer(expr));
+        }
+        _ => {
+          let expr = self.parse_expression();
+          match self.peek_token() {
+            Token::Semicolon => {
+              self.next_token();
+              statements.push(Statement::Expression(expr));
+            }
+            Token::CloseBrace => {
+              self.next_token();
+              let tail = Some(Box::new(expr));
+              return ExpressionKind::Block(statements, tail);
+            }
+            // expressions ending in a block don't need a `;`
+            _ if expr.kind.ends_with_block() => {
+              statements.push(Statement::Expression(expr));
+            }
+            tok => panic!("Expected `;` or `}}`, found {:?}", tok),
+          }
+        }
+      }
+    }
+  }
+
+  // the condition of an `if` or `while`, which is followed by a block
+  fn parse_condition(&mut self) -> Expression<'cx> {
+    self.with_struct_literals(false, Self::parse_expression)
+  }
+
+  // note: the `if` should already have been eaten
+  fn parse_if(&mut self) -> ExpressionKind<'cx> {
+    let cond = self.parse_condition();
+    let then = self.parse_block_expression();
+    let els = match self.peek_token() {
+      Token::KeywordElse => {
+        self.next_token();
+        let els = match self.peek_token() {
+          Token::KeywordIf => {
+            let start = self.peek_span();
+            self.next_token();
+            let kind = self.parse_if();
+            Expression {
+              kind,
+              span: self.span_from(start),
+            }
+          }
+          _ => self.parse_block_expression(),
+        };
+        Some(Box::new(els))
+      }
+      _ => None,
+    };
+    ExpressionKind::If(Box::new(cond), Box::new(then), els)
+  }
+
+  // note: the `while` or `loop` should already have been eaten
+  fn parse_loop(
+    &mut self,
+    label: Option<Label<'cx>>,
+    keyword: Token<'cx>,
+  ) -> ExpressionKind<'cx> {
+    match keyword {
+      Token::KeywordWhile => {
+        let cond = self.parse_condition();
+        let body = self.parse_block_expression();
+        ExpressionKind::While(label, Box::new(cond), Box::new(body))
+      }
+      _ => ExpressionKind::Loop(label, Box::new(self.parse_block_expression())),
+    }
+  }
+
+  // the label after a `break` or `continue`, if there is one
+  fn parse_label(&mut self) -> Option<Label<'cx>> {
+    match self.peek_token_and_span() {
+      (Token::Label(name), span) => {
+        self.next_token();
+        Some(Label { name, span })
+      }
+      _ => None,
+    }
+  }
+
+  // whether the next token can't start an expression, as after a `return`
+  fn at_expression_end(&mut self) -> bool {
+    matches!(
+      self.peek_token(),
+      Token::Semicolon
+        | Token::CloseBrace
+        | Token::CloseParen
+        | Token::Comma
+        | Token::Eof
+    )
+  }
+
+  // a block, including its `{`
+  fn parse_block_expression(&mut self) -> Expression<'cx> {
+    let start = self.peek_span();
+    let () = self.eat_token(Token::OpenBrace);
+    Expression {
+      kind: self.with_struct_literals(true, Self::parse_block),
+      span: self.span_from(start),
+    }
+  }
+
+  /*
+    note: the `[` should already have been eaten
+
+    `[]`, `[a, b, c]`, or `[value; len]`
+  */
+  fn parse_array(&mut self) -> ExpressionKind<'cx> {
+    if let Token::CloseBracket = self.peek_token() {
+      self.next_token();
+      return ExpressionKind::Array(vec![]);
+    }
+    let first = self.parse_expression();
     match self.next_token() {
-      Token::IntegerLiteral(i) => Expression::IntegerLiteral(i),
-      Token::Identifier(s) => Expression::Name(s),
-      Token::StringLiteral(kind, s) => Expression::StringLiteral(kind, s),
+      Token::Semicolon => {
+        let len = self.parse_expression();
+        let () = self.eat_token(Token::CloseBracket);
+        ExpressionKind::ArrayRepeat(Box::new(first), Box::new(len))
+      }
+      Token::Comma => {
+        let mut elements = vec![first];
+        elements.extend(
+          self.parse_list(Token::CloseBracket, Self::parse_expression),
+        );
+        ExpressionKind::Array(elements)
+      }
+      Token::CloseBracket => ExpressionKind::Array(vec![first]),
+      tok => panic!("Expected `,`, `;`, or `]`, found {:?}", tok),
+    }
+  }
+
+  fn parse_pattern(&mut self) -> Pattern<'cx> {
+    let start = self.peek_span();
+    let kind = match self.next_token() {
+      Token::KeywordUnderscore => PatternKind::Wildcard,
+      Token::Identifier(ty) if self.peek_token() == Token::DoubleColon => {
+        self.next_token();
+        let variant = self.get_ident();
+        let payload = match self.peek_token() {
+          Token::OpenParen => {
+            self.next_token();
+            let payload = self.parse_pattern();
+            let () = self.eat_token(Token::CloseParen);
+            Some(Box::new(payload))
+          }
+          _ => None,
+        };
+        PatternKind::Variant(ty, variant, payload)
+      }
+      Token::Identifier(name) => PatternKind::Binding(name),
+      Token::IntegerLiteral(i, suffix) => {
+        PatternKind::Integer(i, suffix, false)
+      }
+      Token::Operator(op) if op.as_str() == "-" => match self.next_token() {
+        Token::IntegerLiteral(i, suffix) => {
+          PatternKind::Integer(i, suffix, true)
+        }
+        tok => panic!("Expected integer literal, found {:?}", tok),
+      },
+      Token::CharLiteral(ch) => PatternKind::Char(ch),
+      Token::KeywordTrue => PatternKind::Bool(true),
+      Token::KeywordFalse => PatternKind::Bool(false),
+      tok => panic!("Expected pattern, found {:?}", tok),
+    };
+    Pattern {
+      kind,
+      span: self.span_from(start),
+    }
+  }
+
+  /*
+    note: the `match` should already have been eaten
+
+    arms are separated by `,`s, which may be left out after an arm whose
+    body ends with a block
+  */
+  fn parse_match(&mut self) -> ExpressionKind<'cx> {
+    let scrutinee = self.parse_condition();
+    let () = self.eat_token(Token::OpenBrace);
+    let mut arms = vec![];
+    loop {
+      if self.peek_token() == Token::CloseBrace {
+        self.next_token();
+        break;
+      }
+      let pattern = self.parse_pattern();
+      let () = self.eat_token(Token::FatArrow);
+      let body = self.with_struct_literals(true, Self::parse_expression);
+      let ends_with_block = body.kind.ends_with_block();
+      arms.push(MatchArm { pattern, body });
+      match self.peek_token() {
+        Token::Comma => {
+          self.next_token();
+        }
+        Token::CloseBrace => (),
+        _ if ends_with_block => (),
+        tok => panic!("Expected `,` or `}}`, found {:?}", tok),
+      }
+    }
+    ExpressionKind::Match(Box::new(scrutinee), arms)
+  }
+
+  fn parse_field_init(&mut self) -> FieldInit<'cx> {
+    let name_span = self.peek_span();
+    let name = self.get_ident();
+    let () = self.eat_token(Token::Colon);
+    let value = self.parse_expression();
+    FieldInit {
+      name,
+      name_span,
+      value,
+    }
+  }
+
+  fn parse_primary_expression(&mut self) -> Expression<'cx> {
+    let start = self.peek_span();
+    let kind = match self.next_token() {
+      Token::IntegerLiteral(i, suffix) => {
+        ExpressionKind::IntegerLiteral(i, suffix)
+      }
+      Token::FloatLiteral(f) => ExpressionKind::FloatLiteral(f),
+      Token::CharLiteral(ch) => ExpressionKind::CharLiteral(ch),
+      Token::KeywordTrue => ExpressionKind::BoolLiteral(true),
+      Token::KeywordFalse => ExpressionKind::BoolLiteral(false),
+      Token::Identifier(s) => match self.peek_token() {
+        Token::DoubleColon => {
+          self.next_token();
+          match self.peek_token() {
+            Token::OpenBracket => {
+              self.next_token();
+              let args = self.parse_list(Token::CloseBracket, Self::parse_type);
+              ExpressionKind::Instance(s, args)
+            }
+            _ => ExpressionKind::Path(s, self.get_ident()),
+          }
+        }
+        Token::OpenBrace if self.struct_literals => {
+          self.next_token();
+          let fields = self.with_struct_literals(true, |this| {
+            this.parse_list(Token::CloseBrace, Self::parse_field_init)
+          });
+          ExpressionKind::StructLiteral(s, fields)
+        }
+        _ => ExpressionKind::Name(s),
+      },
+      Token::StringLiteral(kind, s) => ExpressionKind::StringLiteral(kind, s),
+      Token::OpenParen => {
+        let tuple = self.with_struct_literals(true, |this| {
+          this.parse_tuple(Self::parse_expression)
+        });
+        match tuple {
+          (elements, true) => ExpressionKind::Tuple(elements),
+          (mut elements, false) => return elements.pop().unwrap(),
+        }
+      }
+      Token::OpenBracket => self.with_struct_literals(true, Self::parse_array),
+      Token::OpenBrace => self.with_struct_literals(true, Self::parse_block),
+      Token::KeywordIf => self.parse_if(),
+      Token::KeywordWhile => self.parse_loop(None, Token::KeywordWhile),
+      Token::KeywordLoop => self.parse_loop(None, Token::KeywordLoop),
+      Token::KeywordMatch => self.parse_match(),
+      Token::KeywordFunc => {
+        let () = self.eat_token(Token::OpenParen);
+        let params = self.parse_list(Token::CloseParen, Self::parse_parameter);
+        let () = self.eat_token(Token::Arrow);
+        let ret_ty = self.parse_type();
+        let body = self.parse_block_expression();
+        ExpressionKind::Closure(params, Box::new(ret_ty), Box::new(body))
+      }
+      Token::Label(name) => {
+        let label = Label { name, span: start };
+        let () = self.eat_token(Token::Colon);
+        match self.next_token() {
+          tok @ Token::KeywordWhile | tok @ Token::KeywordLoop => {
+            self.parse_loop(Some(label), tok)
+          }
+          tok => panic!("Expected `while` or `loop`, found {:?}", tok),
+        }
+      }
+      Token::KeywordBreak => {
+        let label = self.parse_label();
+        let value = if self.at_expression_end() {
+          None
+        } else {
+          Some(Box::new(self.parse_expression()))
+        };
+        ExpressionKind::Break(label, value)
+      }
+      Token::KeywordContinue => ExpressionKind::Continue(self.parse_label()),
+      Token::KeywordReturn => {
+        let value = if self.at_expression_end() {
+          None
+        } else {
+          Some(Box::new(self.parse_expression()))
+        };
+        ExpressionKind::Return(value)
+      }
+      Token::Operator(op) => match UnaryOp::from_operator(op.as_str()) {
+        Some(op) => {
+          let operand = self.parse_postfix_expression();
+          ExpressionKind::UnaryOp(op, Box::new(operand))
+        }
+        None => panic!("unknown prefix operator: {}", op),
+      },
       tok => panic!("Expected expression, found {:?}", tok),
+    };
+    Expression {
+      kind,
+      span: self.span_from(start),
+    }
+  }
+
+  // calls, indexing, and field accesses, which bind tighter than operators
+  fn parse_postfix_expression(&mut self) -> Expression<'cx> {
+    let mut expr = self.parse_primary_expression();
+    loop {
+      let start = expr.span;
+      let kind = match self.peek_token() {
+        Token::OpenParen => {
+          self.next_token();
+          let args = self.with_struct_literals(true, |this| {
+            this.parse_list(Token::CloseParen, Self::parse_expression)
+          });
+          ExpressionKind::Call(Box::new(expr), args)
+        }
+        Token::OpenBracket => {
+          self.next_token();
+          let index = self.with_struct_literals(true, Self::parse_expression);
+          let () = self.eat_token(Token::CloseBracket);
+          ExpressionKind::Index(Box::new(expr), Box::new(index))
+        }
+        Token::Dot => {
+          self.next_token();
+          match self.next_token() {
+            Token::Identifier(name) => {
+              ExpressionKind::Field(Box::new(expr), name)
+            }
+            /*
+              `.0`, an element of a tuple; `.0.1` is lexed as a float
+              literal, and taken apart into its two indices
+            */
+            Token::IntegerLiteral(..) | Token::FloatLiteral(_) => {
+              let span = self.last_span;
+              let mut indices = self.source_of(span).split('.');
+              let first = self.tuple_index(indices.next().unwrap());
+              match indices.next() {
+                Some(second) => {
+                  let second = self.tuple_index(second);
+                  let expr = Expression {
+                    kind: ExpressionKind::Field(Box::new(expr), first),
+                    span: self.span_from(start),
+                  };
+                  ExpressionKind::Field(Box::new(expr), second)
+                }
+                None => ExpressionKind::Field(Box::new(expr), first),
+              }
+            }
+            tok => panic!("Expected ident, found {:?}", tok),
+          }
+        }
+        _ => return expr,
+      };
+      expr = Expression {
+        kind,
+        span: self.span_from(start),
+      };
+    }
+  }
+
+  /*
+    precedence climbing; `min_prec` is the loosest binding operator that this
+    call is allowed to consume
+
+    comparisons don't chain - `a < b < c` is an error, not `(a < b) < c`
+  */
+  fn parse_binary_expression(&mut self, min_prec: u32) -> Expression<'cx> {
+    let mut lhs = self.parse_postfix_expression();
+    let mut lhs_is_comparison = false;
+    loop {
+      let op = match self.peek_token() {
+        Token::Operator(op) => match BinaryOp::from_operator(op.as_str()) {
+          Some(op) => op,
+          // a compound assignment ends the expression before it
+          None => match BinaryOp::from_assignment_operator(op.as_str()) {
+            Some(_) => return lhs,
+            None => panic!("unknown binary operator: {}", op),
+          },
+        },
+        _ => return lhs,
+      };
+      if op.precedence() < min_prec {
+        return lhs;
+      }
+      if lhs_is_comparison && op.is_comparison() {
+        panic!("comparison operators cannot be chained; add parentheses");
+      }
+      self.next_token();
+
+      let rhs = self.parse_binary_expression(op.precedence() + 1);
+      let span = lhs.span.to(rhs.span);
+      lhs = Expression {
+        kind: ExpressionKind::BinaryOp(op, Box::new(lhs), Box::new(rhs)),
+        span,
+      };
+      lhs_is_comparison = op.is_comparison();
     }
   }
 
-  fn parse_function_decl(&mut self) -> FunctionDecl<'cx> {
+  /*
+    assignment binds looser than any binary operator, and to the right;
+    it's `()`, though, so `a = b = c` doesn't type check
+  */
+  fn parse_expression(&mut self) -> Expression<'cx> {
+    let place = self.parse_binary_expression(0);
+    let op = match self.peek_token() {
+      Token::Equals => None,
+      Token::Operator(op) => {
+        match BinaryOp::from_assignment_operator(op.as_str()) {
+          Some(op) => Some(op),
+          None => return place,
+        }
+      }
+      _ => return place,
+    };
+    self.next_token();
+    let value = self.parse_expression();
+    let span = place.span.to(value.span);
+    Expression {
+      kind: ExpressionKind::Assign(op, Box::new(place), Box::new(value)),
+      span,
+    }
+  }
+
+  fn parse_parameter(&mut self) -> Parameter<'cx> {
+    let name = match self.next_token() {
+      Token::Identifier(name) => Some(name),
+      Token::KeywordUnderscore => None,
+      tok => panic!("Expected parameter name, found {:?}", tok),
+    };
+    let name_span = self.last_span;
+    let () = self.eat_token(Token::Colon);
+    let ty = self.parse_type();
+
+    Parameter {
+      name,
+      name_span,
+      ty,
+    }
+  }
+
+  fn parse_type_param(&mut self) -> TypeParam<'cx> {
+    let name_span = self.peek_span();
     let name = self.get_ident();
+    let mut bounds = vec![];
+    if let Token::Colon = self.peek_token() {
+      self.next_token();
+      bounds.push((self.get_ident(), self.last_span));
+      loop {
+        match self.peek_token() {
+          Token::Operator(op) if op.as_str() == "+" => {
+            self.next_token();
+            bounds.push((self.get_ident(), self.last_span));
+          }
+          _ => break,
+        }
+      }
+    }
+
+    TypeParam {
+      name,
+      name_span,
+      bounds,
+    }
+  }
+
+  // `[T, U]`, after the name of a generic item
+  fn parse_type_params(&mut self) -> Vec<TypeParam<'cx>> {
+    match self.peek_token() {
+      Token::OpenBracket => {
+        self.next_token();
+        self.parse_list(Token::CloseBracket, Self::parse_type_param)
+      }
+      _ => vec![],
+    }
+  }
+
+  fn parse_function_decl(&mut self, is_const: bool) -> FunctionDecl<'cx> {
+    let receiver = match self.peek_token() {
+      Token::OpenParen => {
+        self.next_token();
+        let receiver = self.parse_parameter();
+        let () = self.eat_token(Token::CloseParen);
+        Some(receiver)
+      }
+      _ => None,
+    };
+    let mut name = self.get_ident();
+    let mut name_span = self.last_span;
+    let owner = match receiver {
+      Some(ref receiver) => Some(Self::receiver_owner(&receiver.ty)),
+      None if self.peek_token() == Token::Dot => {
+        self.next_token();
+        let owner = (name, name_span);
+        name = self.get_ident();
+        name_span = self.last_span;
+        Some(owner)
+      }
+      None => None,
+    };
+    let type_params = self.parse_type_params();
     let () = self.eat_token(Token::OpenParen);
-    let () = self.eat_token(Token::CloseParen);
+    let mut params = self.parse_list(Token::CloseParen, Self::parse_parameter);
     let () = self.eat_token(Token::Arrow);
     let ret_ty = self.parse_type();
 
-    FunctionDecl { name, ret_ty }
+    let is_method = receiver.is_some();
+    if let Some(receiver) = receiver {
+      params.insert(0, receiver);
+    }
+    FunctionDecl {
+      name,
+      name_span,
+      is_const,
+      type_params,
+      owner,
+      is_method,
+      params,
+      ret_ty,
+    }
   }
 
-  fn parse_function(&mut self) -> Function<'cx> {
-    let decl = self.parse_function_decl();
-    let () = self.eat_token(Token::OpenBrace);
-    let body = self.parse_expression();
-    let () = self.eat_token(Token::CloseBrace);
+  // the type a method belongs to, given the type of its receiver
+  fn receiver_owner(ty: &Type<'cx>) -> (Interned<'cx, NfcString>, Span) {
+    let ty = match *ty {
+      Type::Pointer { ref pointee, .. } => pointee,
+      _ => ty,
+    };
+    match *ty {
+      Type::Named(name, ref args, span) if args.is_empty() => (name, span),
+      _ => panic!(
+        "the receiver of a method must have a named type, or point to one"
+      ),
+    }
+  }
+
+  fn parse_function(&mut self, is_const: bool) -> Function<'cx> {
+    let decl = self.parse_function_decl(is_const);
+    let body = self.parse_block_expression();
 
     Function { decl, body }
   }
 
-  pub fn next_item(&mut self) -> Option<Item<'cx>> {
+  fn parse_type_alias(&mut self) -> TypeAlias<'cx> {
+    let name = self.get_ident();
+    let name_span = self.last_span;
+    let type_params = self.parse_type_params();
+    let () = self.eat_token(Token::Equals);
+    let ty = self.parse_type();
+    let () = self.eat_token(Token::Semicolon);
+
+    TypeAlias {
+      name,
+      name_span,
+      type_params,
+      ty,
+    }
+  }
+
+  // note: the `interface` should already have been eaten
+  fn parse_interface(&mut self) -> Interface<'cx> {
+    let name = self.get_ident();
+    let name_span = self.last_span;
+    let () = self.eat_token(Token::OpenBrace);
+    let mut functions = vec![];
+    loop {
+      let is_const = match self.next_token() {
+        Token::CloseBrace => break,
+        Token::KeywordFunc => false,
+        Token::KeywordConst => {
+          let () = self.eat_token(Token::KeywordFunc);
+          true
+        }
+        tok => panic!("Expected function declaration, found {:?}", tok),
+      };
+      functions.push(self.parse_function_decl(is_const));
+      let () = self.eat_token(Token::Semicolon);
+    }
+
+    Interface {
+      name,
+      name_span,
+      functions,
+    }
+  }
+
+  // note: the `impl` should already have been eaten
+  fn parse_impl(&mut self) -> Impl<'cx> {
+    let interface = self.get_ident();
+    let interface_span = self.last_span;
+    match self.next_token() {
+      Token::Identifier(s) if s.as_str() == "for" => (),
+      tok => panic!("Expected `for`, found {:?}", tok),
+    }
+    let ty = self.parse_type();
+    let () = self.eat_token(Token::OpenBrace);
+    let mut functions = vec![];
+    loop {
+      let is_const = match self.next_token() {
+        Token::CloseBrace => break,
+        Token::KeywordFunc => false,
+        Token::KeywordConst => {
+          let () = self.eat_token(Token::KeywordFunc);
+          true
+        }
+        tok => panic!("Expected function, found {:?}", tok),
+      };
+      functions.push(self.parse_function(is_const));
+    }
+
+    Impl {
+      interface,
+      interface_span,
+      ty,
+      functions,
+    }
+  }
+
+  // note: the `const` or `static` should already have been eaten
+  fn parse_global(&mut self, keyword: Token<'cx>) -> Global<'cx> {
+    let kind = match keyword {
+      Token::KeywordConst => GlobalKind::Const,
+      _ => match self.peek_token() {
+        Token::Identifier(s) if s.as_str() == "mut" => {
+          self.next_token();
+          GlobalKind::Static { mutable: true }
+        }
+        _ => GlobalKind::Static { mutable: false },
+      },
+    };
+    let name = self.get_ident();
+    let name_span = self.last_span;
+    let () = self.eat_token(Token::Colon);
+    let ty = self.parse_type();
+    let () = self.eat_token(Token::Equals);
+    let init = self.parse_expression();
+    let () = self.eat_token(Token::Semicolon);
+
+    Global {
+      name,
+      name_span,
+      kind,
+      ty,
+      init,
+    }
+  }
+
+  // note: the `import` should already have been eaten
+  fn parse_import(&mut self) -> Import<'cx> {
+    let start = self.last_span;
+    let mut path = vec![self.get_ident()];
+    while let Token::Dot = self.peek_token() {
+      self.next_token();
+      path.push(self.get_ident());
+    }
+    let span = self.span_from(start);
+    let () = self.eat_token(Token::Semicolon);
+
+    Import { path, span }
+  }
+
+  // an item, and whether it's written with `pub`
+  pub fn next_item(&mut self) -> Option<(Visibility, Item<'cx>)> {
+    let visibility = match self.peek_token() {
+      Token::Identifier(s) if s.as_str() == "pub" => {
+        self.next_token();
+        Visibility::Public
+      }
+      _ => Visibility::Private,
+    };
+    let item = match self.parse_item() {
+      Some(Item::Impl(_)) if visibility == Visibility::Public => {
+        panic!("impls can't be `pub`; they're used wherever their type is")
+      }
+      Some(item) => item,
+      None if visibility == Visibility::Public => {
+        panic!("expected an item after `pub`")
+      }
+      None => return None,
+    };
+    Some((visibility, item))
+  }
+
+  fn parse_item(&mut self) -> Option<Item<'cx>> {
     match self.next_token() {
       Token::KeywordExtern => {
         let () = self.eat_token(Token::KeywordFunc);
-        let decl = self.parse_function_decl();
+        let decl = self.parse_function_decl(false);
         let () = self.eat_token(Token::Semicolon);
         Some(Item::ExternFunction(decl))
       }
-      Token::KeywordFunc => Some(Item::Function(self.parse_function())),
+      Token::KeywordFunc => Some(Item::Function(self.parse_function(false))),
+      Token::KeywordConst if self.peek_token() == Token::KeywordFunc => {
+        self.next_token();
+        Some(Item::Function(self.parse_function(true)))
+      }
+      Token::KeywordType => Some(Item::TypeAlias(self.parse_type_alias())),
+      tok @ Token::KeywordConst | tok @ Token::KeywordStatic => {
+        Some(Item::Global(self.parse_global(tok)))
+      }
+      /*
+        `import`, `pub`, `interface` and `impl` are only keywords at the
+        start of an item
+      */
+      Token::Identifier(s) if s.as_str() == "import" => {
+        Some(Item::Import(self.parse_import()))
+      }
+      Token::Identifier(s) if s.as_str() == "interface" => {
+        Some(Item::Interface(self.parse_interface()))
+      }
+      Token::Identifier(s) if s.as_str() == "impl" => {
+        Some(Item::Impl(self.parse_impl()))
+      }
       Token::Eof => None,
       tok => panic!("unexpected token: {:?}", tok),
     }
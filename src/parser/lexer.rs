@@ -4,57 +4,268 @@ use crate::interner::Interned;
 use crate::string::NfcString;
 use unicode_xid::UnicodeXID;
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub enum Token<'cx> {
   Eof,
 
-  IntegerLiteral(u64),
+  IntegerLiteral(u64, Option<IntegerSuffix>),
+  FloatLiteral(f64),
+  CharLiteral(char),
   StringLiteral(StringKind, &'cx str),
 
   Operator(Interned<'cx, NfcString>),
   Identifier(Interned<'cx, NfcString>),
+  // `'name`, without the `'`
+  Label(Interned<'cx, NfcString>),
 
   Arrow,
+  FatArrow,
+  Equals,
   KeywordFunc,
   KeywordExtern,
+  KeywordType,
+  KeywordStruct,
+  KeywordEnum,
+  KeywordLet,
+  KeywordIf,
+  KeywordElse,
+  KeywordTrue,
+  KeywordFalse,
+  KeywordWhile,
+  KeywordLoop,
+  KeywordBreak,
+  KeywordContinue,
+  KeywordReturn,
+  KeywordDefer,
+  KeywordMatch,
+  KeywordConst,
+  KeywordStatic,
   KeywordUnderscore,
 
   Colon,
+  DoubleColon,
   Semicolon,
+  Comma,
+  Dot,
   OpenParen,
   CloseParen,
   OpenBrace,
   CloseBrace,
+  OpenBracket,
+  CloseBracket,
 }
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub enum StringKind {
   Normal,
   CString,
+  /*
+    the contents of a byte string aren't necessarily UTF-8, so each byte is
+    stored as the `char` with the same value, `\u{0}` through `\u{FF}`
+  */
+  Bytes,
+  Utf16,
+  Utf32,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum IntegerSuffix {
+  I8,
+  I16,
+  I32,
+  I64,
+  ISize,
+  U8,
+  U16,
+  U32,
+  U64,
+  USize,
+}
+
+/*
+  byte offsets into the source, `end` exclusive; the parser offsets the
+  spans of each file by where the file starts, so that a span in any file of
+  the program says which file it's in
+*/
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Span {
+  pub start: usize,
+  pub end: usize,
+}
+
+// a file of the program, which starts at `start` in the spans of the program
+pub struct SourceFile {
+  pub path: String,
+  pub start: usize,
+  pub source: String,
 }
 
 pub struct Lexer<'s> {
+  file: &'s SourceFile,
   buffer: &'s str,
   iter: std::iter::Peekable<std::str::CharIndices<'s>>,
+  // where the most recently lexed token starts
+  token_start: usize,
 }
 
-fn is_operator_start(ch: char) -> bool {
-  const OPERATOR_START: &[char] = &['-'];
+impl Span {
+  // the smallest span containing both `self` and `other`
+  pub fn to(self, other: Span) -> Span {
+    Span {
+      start: std::cmp::min(self.start, other.start),
+      end: std::cmp::max(self.end, other.end),
+    }
+  }
+}
+
+impl SourceFile {
+  // whether `span`, in the spans of the program, is in this file
+  pub fn contains(&self, span: Span) -> bool {
+    self.start <= span.start && span.start <= self.start + self.source.len()
+  }
+
+  /*
+    formats a diagnostic in this file, pointing at `span`, which is relative
+    to the start of the file, like
+
+      error: unknown escape sequence: `\q`
+       --> hello.nwt:1:8
+        |
+      1 | "Hello\q"
+        |       ^^
+  */
+  pub fn render(
+    &self,
+    level: &str,
+    span: Span,
+    msg: impl std::fmt::Display,
+  ) -> String {
+    let source = &*self.source;
+    let line_start = match source[..span.start].rfind('\n') {
+      Some(idx) => idx + 1,
+      None => 0,
+    };
+    let line_end = match source[span.start..].find('\n') {
+      Some(idx) => span.start + idx,
+      None => source.len(),
+    };
+    let line = &source[line_start..line_end];
+    let line_number = source[..line_start].matches('\n').count() + 1;
+    let column = source[line_start..span.start].chars().count();
+    let width = match source.get(span.start..span.end) {
+      Some(s) if span.end <= line_end => std::cmp::max(s.chars().count(), 1),
+      _ => 1,
+    };
 
-  OPERATOR_START.contains(&ch)
+    let gutter = " ".repeat(line_number.to_string().len());
+    format!(
+      "{}: {}\n{}--> {}:{}:{}\n{} |\n{} | {}\n{} | {}{}\n",
+      level,
+      msg,
+      gutter,
+      self.path,
+      line_number,
+      column + 1,
+      gutter,
+      line_number,
+      line,
+      gutter,
+      " ".repeat(column),
+      "^".repeat(width),
+    )
+  }
 }
-fn is_operator_continue(ch: char) -> bool {
-  const OPERATOR_CONTINUE: &[char] = &['>'];
 
-  is_operator_start(ch) || OPERATOR_CONTINUE.contains(&ch)
+/*
+  an operator is lexed as the longest of these which the source starts with,
+  so that `x==-1` is `x`, `==`, `-`, and `1`
+*/
+const OPERATORS: &[&str] = &[
+  "->", "=>", "==", "!=", "<=", ">=", "&&", "||", "+=", "-=", "*=", "/=", "%=",
+  "-", "+", "*", "/", "%", "<", ">", "=", "!",
+];
+
+fn is_operator_start(ch: char) -> bool {
+  OPERATORS.iter().any(|op| op.starts_with(ch))
 }
 
+/*
+  `'` continues an identifier, as in `x'`, but can't start one; so a `'` at
+  the start of a token begins a character literal, or a label like `'outer`
+*/
 fn is_ident_start(ch: char) -> bool {
   ch == '_' || UnicodeXID::is_xid_start(ch)
 }
 fn is_ident_continue(ch: char) -> bool {
   ch == '-' || ch == '\'' || UnicodeXID::is_xid_continue(ch)
 }
+/*
+  `int` and `frac` are hexadecimal digits, and `exp` is a power of two
+
+  only the first sixteen significant digits are kept; any digits after those
+  are folded into the lowest bit, so that the conversion to `f64` still rounds
+  in the correct direction
+*/
+fn hex_float_value(int: &str, frac: &str, mut exp: i32) -> f64 {
+  let mut mantissa = 0u64;
+  let mut kept = 0;
+  let mut sticky = false;
+  let digits = int.chars().map(|ch| (ch, false));
+  let digits = digits.chain(frac.chars().map(|ch| (ch, true)));
+  for (ch, is_frac) in digits {
+    let digit = ch.to_digit(16).unwrap() as u64;
+    if kept < 16 {
+      if mantissa != 0 || digit != 0 {
+        kept += 1;
+      }
+      mantissa = mantissa << 4 | digit;
+      if is_frac {
+        exp = exp.saturating_sub(4);
+      }
+    } else {
+      sticky |= digit != 0;
+      if !is_frac {
+        exp = exp.saturating_add(4);
+      }
+    }
+  }
+  if sticky {
+    mantissa |= 1;
+  }
+
+  let mut value = mantissa as f64;
+  while exp > 0 {
+    let step = std::cmp::min(exp, 1000);
+    value *= 2f64.powi(step);
+    exp -= step;
+  }
+  while exp < 0 {
+    let step = std::cmp::max(exp, -1000);
+    value *= 2f64.powi(step);
+    exp -= step;
+  }
+  value
+}
+
+impl IntegerSuffix {
+  fn from_str(s: &str) -> Option<Self> {
+    let suffix = match s {
+      "i8" => IntegerSuffix::I8,
+      "i16" => IntegerSuffix::I16,
+      "i32" => IntegerSuffix::I32,
+      "i64" => IntegerSuffix::I64,
+      "isize" => IntegerSuffix::ISize,
+      "u8" => IntegerSuffix::U8,
+      "u16" => IntegerSuffix::U16,
+      "u32" => IntegerSuffix::U32,
+      "u64" => IntegerSuffix::U64,
+      "usize" => IntegerSuffix::USize,
+      _ => return None,
+    };
+    Some(suffix)
+  }
+}
+
 fn base_of_letter(ch: char) -> Option<u32> {
   match ch {
     'b' | 'B' => Some(2),
@@ -66,10 +277,13 @@ fn base_of_letter(ch: char) -> Option<u32> {
 }
 
 impl<'s> Lexer<'s> {
-  pub fn new(program: &'s str) -> Self {
+  // the spans the lexer gives are relative to the start of `file`
+  pub fn new(file: &'s SourceFile) -> Self {
     Lexer {
-      buffer: program,
-      iter: program.char_indices().peekable(),
+      file,
+      buffer: &file.source,
+      iter: file.source.char_indices().peekable(),
+      token_start: 0,
     }
   }
 
@@ -84,6 +298,23 @@ impl<'s> Lexer<'s> {
       "_" => Token::KeywordUnderscore,
       "func" => Token::KeywordFunc,
       "extern" => Token::KeywordExtern,
+      "type" => Token::KeywordType,
+      "struct" => Token::KeywordStruct,
+      "enum" => Token::KeywordEnum,
+      "let" => Token::KeywordLet,
+      "if" => Token::KeywordIf,
+      "else" => Token::KeywordElse,
+      "true" => Token::KeywordTrue,
+      "false" => Token::KeywordFalse,
+      "while" => Token::KeywordWhile,
+      "loop" => Token::KeywordLoop,
+      "break" => Token::KeywordBreak,
+      "continue" => Token::KeywordContinue,
+      "return" => Token::KeywordReturn,
+      "defer" => Token::KeywordDefer,
+      "match" => Token::KeywordMatch,
+      "const" => Token::KeywordConst,
+      "static" => Token::KeywordStatic,
       _ => Token::Identifier(ident),
     }
   }
@@ -97,6 +328,8 @@ impl<'s> Lexer<'s> {
     let ident = &self.buffer[first..last];
     match ident {
       "->" => Token::Arrow,
+      "=>" => Token::FatArrow,
+      "=" => Token::Equals,
       _ => {
         let id = ctxt.get_ident(ident);
         Token::Operator(id)
@@ -104,54 +337,442 @@ impl<'s> Lexer<'s> {
     }
   }
 
-  fn lex_number<'cx>(&mut self, first: usize, base: u32) -> Token<'cx> {
-    let buffer = &self.buffer;
-    let helper = move |last| {
-      let buff = &buffer[first..last];
-      let int = match u64::from_str_radix(buff, base) {
-        Ok(i) => i,
-        Err(e) => panic!("ICE: invalid integer literal: {} ({})", buff, e),
+  fn char_at(&self, idx: usize) -> Option<char> {
+    self.buffer[idx..].chars().next()
+  }
+
+  fn is_digit_at(&self, idx: usize, base: u32) -> bool {
+    match self.char_at(idx) {
+      Some(ch) => ch.is_digit(base),
+      None => false,
+    }
+  }
+
+  // `_`s may be used to separate digits, but the first must be a digit
+  fn digits_end(&self, mut idx: usize, base: u32) -> usize {
+    while self.is_digit_at(idx, base) || self.char_at(idx) == Some('_') {
+      idx += 1;
+    }
+    idx
+  }
+
+  /*
+    returns the end of the exponent starting at `idx`, if there is one
+    i.e., for `1.5e-3`, `idx` should be pointed at the `e`
+  */
+  fn exponent_end(&self, idx: usize, letter: char) -> Option<usize> {
+    match self.char_at(idx) {
+      Some(ch) if ch.to_ascii_lowercase() == letter => (),
+      _ => return None,
+    }
+    let digits = match self.char_at(idx + 1) {
+      Some('+') | Some('-') => idx + 2,
+      _ => idx + 1,
+    };
+    if self.is_digit_at(digits, 10) {
+      Some(self.digits_end(digits, 10))
+    } else {
+      None
+    }
+  }
+
+  // moves the iterator forward, so that the next character is at `idx`
+  fn skip_to(&mut self, idx: usize) {
+    while let Some(&(i, _)) = self.iter.peek() {
+      if i >= idx {
+        break;
+      }
+      self.iter.next();
+    }
+  }
+
+  /*
+    note: the first digit, at `start`, has already been taken from the iterator
+
+    numbers are `123`, `0x7F` (or `0b`, `0o`, `0d`), `1.5`, `1e9`, `2.5E-3`,
+    or hexadecimal floats like `0x1.8p3`; for the latter, the exponent is a
+    power of two, and is required, as in C
+
+    digits may be separated by `_`s, as in `1_000_000`, and integers may have
+    a suffix giving their type, as in `255u8` or `1_usize`
+  */
+  fn lex_number<'cx>(&mut self, start: usize) -> Token<'cx> {
+    let mut base = 10;
+    let mut digits_start = start;
+    if self.char_at(start) == Some('0') {
+      if let Some(b) = self.char_at(start + 1).and_then(base_of_letter) {
+        if !self.is_digit_at(start + 2, b) {
+          let span = Span {
+            start,
+            end: start + 2,
+          };
+          let prefix = &self.buffer[start..start + 2];
+          let msg = format!("expected a base {} digit after `{}`", b, prefix);
+          self.error(span, msg);
+        }
+        base = b;
+        digits_start = start + 2;
+      }
+    }
+
+    let int_end = self.digits_end(digits_start, base);
+    let mut end = int_end;
+    let mut frac = None;
+    if self.char_at(end) == Some('.') && self.is_digit_at(end + 1, base) {
+      let frac_end = self.digits_end(end + 1, base);
+      if base != 10 && base != 16 {
+        let span = Span {
+          start,
+          end: frac_end,
+        };
+        let msg = "float literals must be decimal or hexadecimal";
+        self.error(span, msg);
+      }
+      frac = Some(&self.buffer[end + 1..frac_end]);
+      end = frac_end;
+    }
+
+    let exp_letter = if base == 16 { 'p' } else { 'e' };
+    let exp_start = end;
+    let exp = match self.exponent_end(end, exp_letter) {
+      Some(exp_end) if base == 10 || base == 16 => {
+        end = exp_end;
+        Some(&self.buffer[exp_start + 1..exp_end])
+      }
+      _ => None,
+    };
+
+    let mut suffix_end = end;
+    while let Some(ch) = self.char_at(suffix_end) {
+      if !is_ident_continue(ch) {
+        break;
+      }
+      suffix_end += ch.len_utf8();
+    }
+    self.skip_to(suffix_end);
+
+    let suffix = if suffix_end == end {
+      None
+    } else {
+      let span = Span {
+        start: end,
+        end: suffix_end,
       };
-      Token::IntegerLiteral(int)
+      let suffix_text = &self.buffer[end..suffix_end];
+      if frac.is_some() || exp.is_some() {
+        self.error(span, "float literals may not have a suffix")
+      }
+      match IntegerSuffix::from_str(suffix_text) {
+        Some(suffix) => Some(suffix),
+        None => {
+          let msg = format!("invalid integer suffix: `{}`", suffix_text);
+          self.error(span, msg)
+        }
+      }
     };
-    loop {
-      match self.iter.peek() {
-        Some(&(_, ch)) if ch.is_digit(base) => {
-          self.iter.next();
+
+    let span = Span { start, end };
+    let frac = frac.map(|frac| frac.replace('_', ""));
+    let exp = exp.map(|exp| exp.replace('_', ""));
+    let value = match (frac, exp) {
+      (None, None) => {
+        let digits = self.buffer[digits_start..int_end].replace('_', "");
+        match u64::from_str_radix(&digits, base) {
+          Ok(i) => return Token::IntegerLiteral(i, suffix),
+          Err(_) => {
+            self.error(span, "integer literal is too large to fit in 64 bits")
+          }
+        }
+      }
+      (_, None) if base == 16 => {
+        let msg = "hexadecimal float literals require a `p` exponent";
+        self.error(span, msg)
+      }
+      (frac, exp) if base == 16 => {
+        let int = self.buffer[digits_start..int_end].replace('_', "");
+        let exp = match exp.unwrap().parse::<i32>() {
+          Ok(exp) => exp,
+          Err(_) => self.error(span, "float literal exponent out of range"),
+        };
+        let frac = frac.unwrap_or_default();
+        hex_float_value(&int, &frac, exp)
+      }
+      _ => {
+        let digits = self.buffer[digits_start..end].replace('_', "");
+        match digits.parse::<f64>() {
+          Ok(f) => f,
+          Err(e) => self.error(span, format!("invalid float literal: {}", e)),
+        }
+      }
+    };
+    // whether it fits in a `Float32` is checked once its type is known
+    if value.is_infinite() {
+      self.error(span, "float literal is too large to fit in Float64")
+    }
+    Token::FloatLiteral(value)
+  }
+
+  pub fn file(&self) -> &'s SourceFile {
+    self.file
+  }
+
+  // reports an error in the source, pointing at `span`
+  pub fn error(&self, span: Span, msg: impl std::fmt::Display) -> ! {
+    panic!("{}", self.file.render("error", span, msg))
+  }
+
+  // the span of the most recently lexed token
+  pub fn token_span(&mut self) -> Span {
+    let end = match self.iter.peek() {
+      Some(&(idx, _)) => idx,
+      None => self.buffer.len(),
+    };
+    Span {
+      start: self.token_start,
+      end,
+    }
+  }
+
+  /*
+    note: the `\` has already been taken from the iterator, and is at `start`
+
+    returns the character that the escape sequence stands for; in byte
+    strings, `\x80` through `\xFF` are allowed, and `\u{..}` is not
+  */
+  fn lex_escape(&mut self, start: usize, kind: StringKind) -> char {
+    let (idx, ch) = match self.iter.next() {
+      Some(next) => next,
+      None => self.error(Span { start, end: start + 1 }, "unexpected EOF"),
+    };
+    let mut end = idx + ch.len_utf8();
+    match ch {
+      'n' => '\n',
+      't' => '\t',
+      'r' => '\r',
+      '0' => '\0',
+      '\\' => '\\',
+      '"' => '"',
+      '\'' => '\'',
+      'x' => {
+        let digits = &self.buffer[end..];
+        let len = digits.chars().take(2).take_while(char::is_ascii_hexdigit);
+        let len = len.count();
+        end += len;
+        self.skip_to(end);
+        let span = Span { start, end };
+        if len != 2 {
+          self.error(span, "`\\x` escapes must have exactly two hex digits");
         }
-        Some(&(_, ch)) if is_ident_continue(ch) => {
-          panic!("add whitespace after numbers, before identifier characters")
+        let value = u32::from_str_radix(&digits[..2], 16).unwrap();
+        if value > 0x7F && kind != StringKind::Bytes {
+          let msg = "`\\x` escapes must be at most `\\x7F`; use `\\u{..}`";
+          self.error(span, msg)
         }
-        Some(&(idx, _)) => return helper(idx - 1),
-        None => return helper(self.buffer.len()),
+        value as u8 as char
+      }
+      'u' if kind == StringKind::Bytes => {
+        let span = Span { start, end };
+        self.error(span, "unicode escapes are not allowed in byte strings")
+      }
+      'u' => {
+        if self.char_at(end) != Some('{') {
+          self.error(Span { start, end }, "expected `{` after `\\u`");
+        }
+        let digits_start = end + 1;
+        let digits_end = self.digits_end(digits_start, 16);
+        if self.char_at(digits_end) != Some('}') {
+          let span = Span {
+            start,
+            end: digits_end,
+          };
+          self.error(span, "unterminated unicode escape; expected `}`");
+        }
+        end = digits_end + 1;
+        self.skip_to(end);
+        let span = Span { start, end };
+
+        let digits = &self.buffer[digits_start..digits_end];
+        if digits.is_empty() || digits.len() > 6 {
+          self.error(span, "unicode escapes must have one to six hex digits");
+        }
+        let value = u32::from_str_radix(digits, 16).unwrap();
+        match std::char::from_u32(value) {
+          Some(ch) => ch,
+          None => {
+            self.error(span, "unicode escape is not a unicode scalar value")
+          }
+        }
+      }
+      _ => {
+        let span = Span { start, end };
+        let escape = &self.buffer[start..end];
+        self.error(span, format!("unknown escape sequence: `{}`", escape))
       }
     }
   }
 
+  /*
+    `prefix` is the identifier directly before the `"`, without any `#`s
+
+    the prefixes are `c` (C strings), `b` (byte strings), and `u16` and `u32`
+    (NUL-terminated UTF-16 and UTF-32); any of them, or none, may be followed
+    by an `r`, for raw strings, which have no escapes
+  */
+  fn string_prefix(&self, prefix: Span) -> (StringKind, bool) {
+    let text = &self.buffer[prefix.start..prefix.end];
+    let (kind_text, raw) = match text.strip_suffix('r') {
+      Some(kind_text) => (kind_text, true),
+      None => (text, false),
+    };
+    let kind = match kind_text {
+      "" => StringKind::Normal,
+      "c" | "C" => StringKind::CString,
+      "b" => StringKind::Bytes,
+      "u16" => StringKind::Utf16,
+      "u32" => StringKind::Utf32,
+      _ => {
+        let msg = format!("unsupported string literal prefix: `{}`", text);
+        self.error(prefix, msg)
+      }
+    };
+    (kind, raw)
+  }
+
   /*
     note: the iterator should be pointed at the character after the leading `"`
     i.e., for "Hello", the iterator should be at
                ^
+    `start` is the index of the leading `"`, for diagnostics
+
+    `raw_hashes` is the number of `#`s around a raw string, or `None` if the
+    string isn't raw; a raw string ends at a `"` followed by that many `#`s
   */
   fn lex_string<'cx>(
     &mut self,
+    start: usize,
     kind: StringKind,
+    raw_hashes: Option<usize>,
     ctxt: &'cx Context,
   ) -> Token<'cx> {
-    let start = match self.iter.peek() {
-      Some(&(idx, _)) => idx,
-      None => panic!("Unexpected EOF"),
-    };
-
+    let mut value = String::new();
     loop {
       match self.iter.next() {
-        Some((last, '"')) => {
-          let s = &self.buffer[start..last];
-          return Token::StringLiteral(kind, ctxt.get_string_literal(s));
+        Some((idx, '"')) => {
+          let hashes = raw_hashes.unwrap_or(0);
+          let closing = &self.buffer[idx + 1..];
+          let closing_hashes = closing.chars().take(hashes);
+          if closing_hashes.filter(|&c| c == '#').count() < hashes {
+            value.push('"');
+            continue;
+          }
+          self.skip_to(idx + 1 + hashes);
+          let s = ctxt.get_string_literal(&value);
+          return Token::StringLiteral(kind, s);
+        }
+        Some((idx, '\\')) if raw_hashes.is_none() => {
+          value.push(self.lex_escape(idx, kind))
+        }
+        Some((idx, ch)) if kind == StringKind::Bytes && !ch.is_ascii() => {
+          let span = Span {
+            start: idx,
+            end: idx + ch.len_utf8(),
+          };
+          let msg = "byte strings may only contain ASCII; use `\\xNN`";
+          self.error(span, msg)
+        }
+        Some((_, ch)) => value.push(ch),
+        None => {
+          let span = Span {
+            start,
+            end: start + 1,
+          };
+          self.error(span, "unterminated string literal")
         }
-        Some((_, '\\')) => panic!("escapes not yet supported"),
-        Some(_) => (),
-        None => panic!("Unexpected EOF"),
+      }
+    }
+  }
+
+  /*
+    note: the leading `'`, at `start`, has already been taken from the iterator
+  */
+  /*
+    `'a'` is a character, and `'a` is a label; labels can't contain `'`, so
+    that the two are told apart by the character after the first
+  */
+  fn lex_char_or_label<'cx>(
+    &mut self,
+    start: usize,
+    ctxt: &'cx Context,
+  ) -> Token<'cx> {
+    let mut lookahead = self.iter.clone();
+    let is_label = match (lookahead.next(), lookahead.next()) {
+      (Some((_, first)), Some((_, second))) => {
+        is_ident_start(first) && second != '\''
+      }
+      (Some((_, first)), None) => is_ident_start(first),
+      _ => false,
+    };
+    if !is_label {
+      return self.lex_char(start);
+    }
+
+    self.iter.next();
+    let end = loop {
+      match self.iter.peek() {
+        Some(&(_, ch)) if ch != '\'' && is_ident_continue(ch) => {
+          self.iter.next();
+        }
+        Some(&(idx, _)) => break idx,
+        None => break self.buffer.len(),
+      }
+    };
+    Token::Label(ctxt.get_ident(&self.buffer[start + 1..end]))
+  }
+
+  fn lex_char<'cx>(&mut self, start: usize) -> Token<'cx> {
+    let ch = match self.iter.next() {
+      Some((idx, '\\')) => self.lex_escape(idx, StringKind::Normal),
+      Some((idx, '\'')) => {
+        let span = Span { start, end: idx + 1 };
+        self.error(span, "empty character literal")
+      }
+      Some((_, '\n')) | None => {
+        let span = Span {
+          start,
+          end: start + 1,
+        };
+        self.error(span, "unterminated character literal")
+      }
+      Some((_, ch)) => ch,
+    };
+    match self.iter.next() {
+      Some((_, '\'')) => Token::CharLiteral(ch),
+      Some((idx, _)) => {
+        let rest = &self.buffer[idx..];
+        match rest.find(['\'', '\n']) {
+          Some(len) if rest[len..].starts_with('\'') => {
+            let span = Span {
+              start,
+              end: idx + len + 1,
+            };
+            let msg = "character literals must contain exactly one character";
+            self.error(span, msg)
+          }
+          _ => {
+            let span = Span {
+              start,
+              end: start + 1,
+            };
+            self.error(span, "unterminated character literal")
+          }
+        }
+      }
+      None => {
+        let span = Span {
+          start,
+          end: start + 1,
+        };
+        self.error(span, "unterminated character literal")
       }
     }
   }
@@ -184,14 +805,28 @@ impl<'s> Lexer<'s> {
   }
 
   pub fn next_token<'cx>(&mut self, ctxt: &'cx Context) -> Token<'cx> {
+    // whitespace and comments recurse, so this ends up at the real token
+    self.token_start = match self.iter.peek() {
+      Some(&(idx, _)) => idx,
+      None => self.buffer.len(),
+    };
     match self.iter.next() {
       None => Token::Eof,
-      Some((_, ':')) => Token::Colon,
+      Some((_, ':')) => match self.iter.peek() {
+        Some((_, ':')) => {
+          self.iter.next();
+          Token::DoubleColon
+        }
+        _ => Token::Colon,
+      },
       Some((_, ';')) => Token::Semicolon,
+      Some((_, ',')) => Token::Comma,
+      Some((_, '.')) => Token::Dot,
       Some((_, '(')) => Token::OpenParen,
       Some((_, ')')) => Token::CloseParen,
       Some((_, '{')) => Token::OpenBrace,
       Some((_, '}')) => Token::CloseBrace,
+      Some((_, ']')) => Token::CloseBracket,
       Some((_, '[')) => {
         match self.iter.peek() {
           Some((_, '%')) => {
@@ -199,10 +834,13 @@ impl<'s> Lexer<'s> {
             self.block_comment();
             self.next_token(ctxt)
           }
-          _ => panic!("`[` and `]` are not yet supported")
+          _ => Token::OpenBracket,
         }
       }
-      Some((_, '"')) => self.lex_string(StringKind::Normal, ctxt),
+      Some((start, '"')) => {
+        self.lex_string(start, StringKind::Normal, None, ctxt)
+      }
+      Some((start, '\'')) => self.lex_char_or_label(start, ctxt),
       Some((_, ch)) if ch.is_whitespace() => loop {
         match self.iter.peek() {
           Some(&(_, ch)) if ch.is_whitespace() => self.iter.next(),
@@ -214,46 +852,51 @@ impl<'s> Lexer<'s> {
           Some(&(_, ch)) if is_ident_continue(ch) => self.iter.next(),
           Some(&(idx, '"')) => {
             self.iter.next();
-            let kind = match &self.buffer[start..idx] {
-              "c" | "C" => StringKind::CString,
-              other => panic!("Unsupported string literal prefix: `{}`", other),
-            };
-            return self.lex_string(kind, ctxt);
+            let (kind, raw) = self.string_prefix(Span { start, end: idx });
+            let raw_hashes = if raw { Some(0) } else { None };
+            return self.lex_string(idx, kind, raw_hashes, ctxt);
           }
-          Some(&(idx, _)) => return self.match_identifier(ctxt, start, idx),
-          None => return self.match_identifier(ctxt, start, self.buffer.len()),
-        };
-      },
-      Some((start, ch)) if is_operator_start(ch) => loop {
-        match self.iter.peek() {
-          Some(&(_, ch)) if is_operator_continue(ch) => self.iter.next(),
-          Some(&(idx, _)) => return self.match_operator(ctxt, start, idx),
-          None => return self.match_operator(ctxt, start, self.buffer.len()),
-        };
-      },
-      Some((start0, ch0)) if ch0.is_digit(10) => match self.iter.peek() {
-        Some(&(_, ch1)) => {
-          self.iter.next();
-
-          if let (true, Some(base)) = (ch0 == '0', base_of_letter(ch1)) {
+          Some(&(idx, '#')) => {
+            let prefix = Span { start, end: idx };
+            let (kind, raw) = self.string_prefix(prefix);
+            if !raw {
+              self.error(prefix, "only raw strings may be delimited by `#`s");
+            }
+            let mut hashes = 0;
+            while let Some(&(_, '#')) = self.iter.peek() {
+              self.iter.next();
+              hashes += 1;
+            }
             match self.iter.next() {
-              Some((start, ch)) if ch.is_digit(base) => {
-                self.lex_number(start, base)
+              Some((quote, '"')) => {
+                return self.lex_string(quote, kind, Some(hashes), ctxt)
+              }
+              _ => {
+                let span = Span {
+                  start,
+                  end: idx + hashes,
+                };
+                self.error(span, "expected `\"` after raw string prefix")
               }
-              Some((_, ch)) => panic!(
-                "Invalid integral literal with base {}; found {}",
-                base, ch,
-              ),
-              None => panic!("Unexpected EOF"),
             }
-          } else if is_ident_continue(ch1) {
-            panic!("add whitespace after numbers, before identifier characters")
-          } else {
-            self.lex_number(start0, 10)
           }
-        }
-        _ => Token::IntegerLiteral(0),
+          Some(&(idx, _)) => return self.match_identifier(ctxt, start, idx),
+          None => return self.match_identifier(ctxt, start, self.buffer.len()),
+        };
       },
+      // a lone `&` or `|` isn't an operator, and is left to the parser
+      Some((start, ch)) if is_operator_start(ch) => {
+        let rest = &self.buffer[start..];
+        let len = OPERATORS
+          .iter()
+          .filter(|op| rest.starts_with(*op))
+          .map(|op| op.len())
+          .max()
+          .unwrap_or_else(|| ch.len_utf8());
+        self.skip_to(start + len);
+        self.match_operator(ctxt, start, start + len)
+      }
+      Some((start, ch)) if ch.is_ascii_digit() => self.lex_number(start),
       Some((idx, ch)) => panic!(
         "Unrecognized character {} ({:x}) at index {}",
         ch, ch as u32, idx
@@ -261,3 +904,178 @@ impl<'s> Lexer<'s> {
     }
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::panic::AssertUnwindSafe;
+
+  fn lex<'cx>(ctxt: &'cx Context, source: &str) -> Vec<Token<'cx>> {
+    let file = ctxt.add_file("test.nwt".to_owned(), source.to_owned());
+    let mut lexer = Lexer::new(file);
+    let mut tokens = vec![];
+    loop {
+      match lexer.next_token(ctxt) {
+        Token::Eof => return tokens,
+        tok => tokens.push(tok),
+      }
+    }
+  }
+
+  // the error lexing `source` reports
+  fn lex_error(source: &str) -> String {
+    let ctxt = Context::new();
+    let result = std::panic::catch_unwind(AssertUnwindSafe(|| {
+      lex(&ctxt, source);
+    }));
+    match result {
+      Ok(()) => panic!("`{}` lexed without an error", source),
+      Err(payload) => *payload.downcast::<String>().unwrap(),
+    }
+  }
+
+  // the operators in `source`, as they're written
+  fn operators(source: &str) -> Vec<String> {
+    let ctxt = Context::new();
+    lex(&ctxt, source)
+      .into_iter()
+      .filter_map(|tok| match tok {
+        Token::Operator(op) => Some(op.as_str().to_owned()),
+        Token::Arrow => Some("->".to_owned()),
+        Token::FatArrow => Some("=>".to_owned()),
+        Token::Equals => Some("=".to_owned()),
+        _ => None,
+      })
+      .collect()
+  }
+
+  #[test]
+  fn longest_known_operator() {
+    assert_eq!(operators("a*-b"), ["*", "-"]);
+    assert_eq!(operators("x==-1"), ["==", "-"]);
+    assert_eq!(operators("x=-1"), ["=", "-"]);
+    assert_eq!(operators("x<=-y"), ["<=", "-"]);
+    assert_eq!(operators("x+=-1"), ["+=", "-"]);
+    assert_eq!(operators("!!x"), ["!", "!"]);
+    assert_eq!(operators("a&&!b"), ["&&", "!"]);
+    assert_eq!(operators("a||!b"), ["||", "!"]);
+    assert_eq!(operators("func() -> Int32"), ["->"]);
+    assert_eq!(operators("_ =>-1"), ["=>", "-"]);
+  }
+
+  #[test]
+  fn floats() {
+    let ctxt = Context::new();
+    let float = |source| lex(&ctxt, source);
+    assert_eq!(float("1.5"), [Token::FloatLiteral(1.5)]);
+    assert_eq!(float("2.5E-3"), [Token::FloatLiteral(2.5e-3)]);
+    assert_eq!(float("1_000.0"), [Token::FloatLiteral(1000.0)]);
+    assert_eq!(float("0x1.8p3"), [Token::FloatLiteral(12.0)]);
+    assert_eq!(float("0x10p-4"), [Token::FloatLiteral(1.0)]);
+  }
+
+  #[test]
+  fn malformed_numbers() {
+    let error = lex_error("0x");
+    assert!(error.contains("expected a base 16 digit after `0x`"));
+    assert!(error.contains("test.nwt:1:1"));
+    let error = lex_error("let x = 0bz;");
+    assert!(error.contains("expected a base 2 digit after `0b`"));
+    assert!(error.contains("test.nwt:1:9"));
+
+    let error = lex_error("0o1.5");
+    assert!(error.contains("float literals must be decimal or hexadecimal"));
+    let error = lex_error("0x1.8");
+    assert!(error.contains("float literals require a `p` exponent"));
+    let error = lex_error("0x1p99999999999");
+    assert!(error.contains("float literal exponent out of range"));
+    let error = lex_error("1e999");
+    assert!(error.contains("float literal is too large to fit in Float64"));
+  }
+
+  #[test]
+  fn escapes() {
+    let ctxt = Context::new();
+    let string = |source| match lex(&ctxt, source)[..] {
+      [Token::StringLiteral(_, s)] => s,
+      ref tokens => panic!("expected a string, found {:?}", tokens),
+    };
+    assert_eq!(string(r#""\n\t\r\0""#), "\n\t\r\0");
+    assert_eq!(string(r#""\\\"\'""#), "\\\"'");
+    assert_eq!(string(r#""\x41\x7F""#), "A\x7F");
+    assert_eq!(string(r#""\u{48}\u{e9}\u{1F600}""#), "H\u{e9}\u{1F600}");
+    assert_eq!(string(r#"b"\x80\xFF""#), "\u{80}\u{FF}");
+    assert_eq!(string(r#"r"\n""#), "\\n");
+
+    let chars = lex(&ctxt, r"'\n' '\'' '\x41' '\u{3bb}' 'a'");
+    let expected = ['\n', '\'', 'A', '\u{3bb}', 'a'];
+    let expected = expected.iter().map(|&ch| Token::CharLiteral(ch));
+    assert!(chars.into_iter().eq(expected));
+  }
+
+  #[test]
+  fn bad_escapes() {
+    let error = lex_error(r#""a\qb""#);
+    assert!(error.contains("unknown escape sequence: `\\q`"));
+    assert!(error.contains("test.nwt:1:3"));
+    let error = lex_error(r#""\x4""#);
+    assert!(error.contains("`\\x` escapes must have exactly two hex digits"));
+    let error = lex_error(r#""\x80""#);
+    assert!(error.contains("`\\x` escapes must be at most `\\x7F`"));
+    let error = lex_error(r#"b"\u{41}""#);
+    assert!(error.contains("unicode escapes are not allowed in byte strings"));
+    let error = lex_error(r#""\u41""#);
+    assert!(error.contains("expected `{` after `\\u`"));
+    let error = lex_error(r#""\u{41""#);
+    assert!(error.contains("unterminated unicode escape; expected `}`"));
+    let error = lex_error(r#""\u{}""#);
+    assert!(error.contains("unicode escapes must have one to six hex digits"));
+    let error = lex_error(r#""\u{1000000}""#);
+    assert!(error.contains("unicode escapes must have one to six hex digits"));
+    let error = lex_error(r#""\u{D800}""#);
+    assert!(error.contains("unicode escape is not a unicode scalar value"));
+    let error = lex_error("\"\\");
+    assert!(error.contains("unexpected EOF"));
+  }
+
+  #[test]
+  fn bad_chars() {
+    let error = lex_error("''");
+    assert!(error.contains("empty character literal"));
+    // `'ab` would be a label
+    let error = lex_error("'12'");
+    assert!(error.contains("character literals must contain exactly one"));
+    let error = lex_error(r"'\nx'");
+    assert!(error.contains("character literals must contain exactly one"));
+    let error = lex_error("'");
+    assert!(error.contains("unterminated character literal"));
+    let error = lex_error("'\\n");
+    assert!(error.contains("unterminated character literal"));
+    let error = lex_error("'\n'");
+    assert!(error.contains("unterminated character literal"));
+  }
+
+  #[test]
+  fn integers() {
+    let ctxt = Context::new();
+    let int = |source| lex(&ctxt, source);
+    let literal = |value, suffix| [Token::IntegerLiteral(value, suffix)];
+    assert_eq!(int("1_000_000"), literal(1_000_000, None));
+    assert_eq!(int("0xFF_FF"), literal(0xFFFF, None));
+    assert_eq!(int("0b1010_1010"), literal(0b1010_1010, None));
+    assert_eq!(int("0o777"), literal(0o777, None));
+    assert_eq!(int("0d99"), literal(99, None));
+    assert_eq!(int("255u8"), literal(255, Some(IntegerSuffix::U8)));
+    assert_eq!(int("1_usize"), literal(1, Some(IntegerSuffix::USize)));
+    assert_eq!(int("0x7Fi64"), literal(0x7F, Some(IntegerSuffix::I64)));
+    assert_eq!(int("18446744073709551615"), literal(u64::MAX, None));
+
+    let error = lex_error("10u7");
+    assert!(error.contains("invalid integer suffix: `u7`"));
+    assert!(error.contains("test.nwt:1:3"));
+    let error = lex_error("1.5u8");
+    assert!(error.contains("float literals may not have a suffix"));
+    let error = lex_error("18446744073709551616");
+    assert!(error.contains("integer literal is too large to fit in 64 bits"));
+  }
+}
@@ -0,0 +1,233 @@
+/*
+  a compilation of a program, starting from the file of its root module
+
+  each `.nwt` file is a module, and its `import`s name other files by their
+  path from the directory of the root module: `import a.b;` is the file
+  `a/b.nwt`. Modules are loaded depth first from the root, so each module
+  comes after the modules it imports, and the root comes last; `loading`
+  holds the chain of modules currently being loaded, which is how we find
+  import cycles. A module is loaded once, however many modules import it.
+
+  the standard library is built into the compiler, as the modules `std.*`,
+  and compiled along with every program. Its `std.prelude` is loaded
+  first, and imported into each of the program's modules
+
+  the contexts are leaked, since everything interned in them lives as long
+  as the compilation does, and the compilation is the rest of the process
+*/
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::interner::Interned;
+use crate::module::{self, Module};
+use crate::parser::{self, Item, Parser, Span, Visibility};
+use crate::string::NfcString;
+
+type ModulePath = Vec<Interned<'static, NfcString>>;
+
+// the modules of the standard library, as `std.name`
+const STD: &[(&str, &str)] = &[
+  ("prelude", include_str!("../std/prelude.nwt")),
+  ("io", include_str!("../std/io.nwt")),
+  ("str", include_str!("../std/str.nwt")),
+  ("mem", include_str!("../std/mem.nwt")),
+  ("math", include_str!("../std/math.nwt")),
+];
+
+// a module, as it was parsed, with its imports found
+pub struct SourceModule<'cx> {
+  // the path it's imported by; the root module's is the name of its file
+  pub path: Vec<Interned<'cx, NfcString>>,
+  // the items, apart from the imports
+  pub items: Vec<(Visibility, Item<'cx>)>,
+  /*
+    the modules it imports, by their index among the modules, which is
+    always lower than its own; a `pub import` exports the public items of
+    the module it imports, along with the module's own
+  */
+  pub imports: Vec<(Visibility, usize)>,
+}
+
+pub struct Session {
+  parse_context: &'static parser::Context,
+  module_context: &'static module::Context<'static>,
+  // the directory of the root module, which import paths start from
+  directory: PathBuf,
+  modules: Vec<SourceModule<'static>>,
+  loaded: HashMap<ModulePath, usize>,
+  loading: Vec<ModulePath>,
+  // the index of `std.prelude`, once it's loaded
+  prelude: Option<usize>,
+  errors: Vec<String>,
+}
+
+// `a.b`
+fn describe_path(path: &[Interned<NfcString>]) -> String {
+  let path = path.iter().map(|name| name.as_str()).collect::<Vec<_>>();
+  path.join(".")
+}
+
+fn is_std(path: &[Interned<NfcString>]) -> bool {
+  path.len() > 1 && path[0].as_str() == "std"
+}
+
+// the source of the module `path`, if it's in the standard library
+fn std_source(path: &[Interned<NfcString>]) -> Option<&'static str> {
+  if path.len() != 2 || path[0].as_str() != "std" {
+    return None;
+  }
+  STD
+    .iter()
+    .find(|&&(name, _)| name == path[1].as_str())
+    .map(|&(_, source)| source)
+}
+
+impl Session {
+  // loads the module in the file `root`, and every module it imports
+  pub fn new(root: &Path) -> Self {
+    let parse_context: &'static _ = Box::leak(Box::new(parser::Context::new()));
+    let module_context: &'static _ =
+      Box::leak(Box::new(module::Context::new(parse_context)));
+    let directory = match root.parent() {
+      Some(directory) => directory.to_path_buf(),
+      None => PathBuf::new(),
+    };
+    let name = match root.file_stem().and_then(|stem| stem.to_str()) {
+      Some(name) => parse_context.get_ident(name),
+      None => panic!("the file {} has no name", root.display()),
+    };
+
+    let mut session = Session {
+      parse_context,
+      module_context,
+      directory,
+      modules: vec![],
+      loaded: HashMap::new(),
+      loading: vec![],
+      prelude: None,
+      errors: vec![],
+    };
+    let prelude = vec![
+      parse_context.get_ident("std"),
+      parse_context.get_ident("prelude"),
+    ];
+    let file = session.file(&prelude);
+    session.prelude = session.load(prelude, &file, None);
+    session.load(vec![name], root, None);
+    if !session.errors.is_empty() {
+      panic!("{}", session.errors.join("\n"));
+    }
+    session
+  }
+
+  // checks the modules, and puts them together into one
+  pub fn module(self) -> Module<'static> {
+    Module::new(self.modules, self.module_context)
+  }
+
+  // an error in the import at `span`, or in loading the root module
+  fn error(&mut self, span: Option<Span>, msg: impl std::fmt::Display) {
+    let msg = match span {
+      Some(span) => self.parse_context.render(span, msg),
+      None => format!("error: {}\n", msg),
+    };
+    self.errors.push(msg);
+  }
+
+  /*
+    the file of the module `path`; the standard library's files don't
+    exist, but are named as if they were in `std`, for errors
+  */
+  fn file(&self, path: &[Interned<NfcString>]) -> PathBuf {
+    let mut file = if is_std(path) {
+      PathBuf::new()
+    } else {
+      self.directory.clone()
+    };
+    for name in path {
+      file.push(name.as_str());
+    }
+    file.set_extension("nwt");
+    file
+  }
+
+  /*
+    the index of the module `path`, which is loaded from `file` if it hasn't
+    been already; `None` if it couldn't be, which has been reported, at
+    `span` if the module was imported
+  */
+  fn load(
+    &mut self,
+    path: ModulePath,
+    file: &Path,
+    span: Option<Span>,
+  ) -> Option<usize> {
+    if let Some(&idx) = self.loaded.get(&path) {
+      return Some(idx);
+    }
+    if let Some(idx) = self.loading.iter().position(|p| *p == path) {
+      let mut cycle = String::new();
+      for module in &self.loading[idx..] {
+        cycle.push_str(&describe_path(module));
+        cycle.push_str(" -> ");
+      }
+      cycle.push_str(&describe_path(&path));
+      self.error(span, format!("cyclic import: {}", cycle));
+      return None;
+    }
+    let source = if is_std(&path) {
+      std_source(&path).map(String::from).ok_or_else(|| {
+        let msg = "there's no such module in the standard library";
+        std::io::Error::new(std::io::ErrorKind::NotFound, msg)
+      })
+    } else {
+      std::fs::read_to_string(file)
+    };
+    let source = match source {
+      Ok(source) => source,
+      Err(err) => {
+        let msg = format!(
+          "couldn't read the module {}, from {}: {}",
+          describe_path(&path),
+          file.display(),
+          err,
+        );
+        self.error(span, msg);
+        return None;
+      }
+    };
+    let file = self
+      .parse_context
+      .add_file(file.display().to_string(), source);
+
+    self.loading.push(path.clone());
+    let mut parser = Parser::new(file, self.parse_context);
+    let mut items = vec![];
+    let mut imports = vec![];
+    while let Some((visibility, item)) = parser.next_item() {
+      match item {
+        Item::Import(import) => {
+          let file = self.file(&import.path);
+          if let Some(idx) = self.load(import.path, &file, Some(import.span)) {
+            imports.push((visibility, idx));
+          }
+        }
+        item => items.push((visibility, item)),
+      }
+    }
+    self.loading.pop();
+    if let (Some(prelude), false) = (self.prelude, is_std(&path)) {
+      imports.insert(0, (Visibility::Private, prelude));
+    }
+
+    let idx = self.modules.len();
+    self.modules.push(SourceModule {
+      path: path.clone(),
+      items,
+      imports,
+    });
+    self.loaded.insert(path, idx);
+    Some(idx)
+  }
+}
@@ -0,0 +1,33 @@
+/*
+  the C side of the tests in src/module/abi.rs; each `check_` function
+  returns its share of the expected exit code, 42
+*/
+
+#include <stdint.h>
+
+typedef struct { float x, y; } Complex;
+typedef struct { int32_t a; float b; double c; } Mixed;
+typedef struct { int64_t a, b, c; } Big;
+typedef struct { int64_t a, b; } Pair;
+
+Complex make_complex(float x, float y) { Complex c = {x, y}; return c; }
+int32_t check_complex(Complex c) {
+  return c.x == 1.5f && c.y == 2.5f ? 10 : 0;
+}
+
+Mixed make_mixed(void) { Mixed m = {7, 0.25f, 3.5}; return m; }
+int32_t check_mixed(Mixed m) {
+  return m.a == 7 && m.b == 0.25f && m.c == 3.5 ? 10 : 0;
+}
+
+Big make_big(void) { Big b = {1, 2, 3}; return b; }
+int32_t check_big(Big b) {
+  return b.a == 1 && b.b == 2 && b.c == 3 ? 10 : 0;
+}
+
+Pair make_pair(int64_t a) { Pair p = {a, -a}; return p; }
+int32_t check_pairs(Pair a, Pair b, Pair c, Pair d, int8_t e) {
+  int ok = a.a == 1 && a.b == -1 && b.a == 2 && c.a == 3;
+  ok = ok && d.a == 4 && d.b == -4 && e == -5;
+  return ok ? 12 : 0;
+}